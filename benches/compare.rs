@@ -0,0 +1,226 @@
+// BoundQueue and UnboundQueue against the std collections they stand in
+// for, at a few queue sizes and with a Copy and a heap-owning element.
+// Each group can be run on its own, for example
+//
+//     cargo bench --bench compare -- churn/u64
+//
+// and criterion keeps the last run around to compare the next one with.
+
+#[macro_use]
+extern crate criterion;
+extern crate queue;
+
+use criterion::measurement::WallTime;
+use criterion::{black_box, BenchmarkGroup, BenchmarkId, Criterion};
+use queue::bound::BoundQueue;
+use queue::unbound::UnboundQueue;
+use queue::Queue;
+use std::collections::{LinkedList, VecDeque};
+
+const SIZES: &[usize] = &[16, 1024, 16384];
+
+trait Subject<T>: Queue<T> {
+    // room for at least `cap` elements, for the queues that need a bound
+    fn with_cap(cap: usize) -> Self;
+    // visits every element front to back
+    fn walk(&self) -> usize;
+}
+
+impl<T> Subject<T> for BoundQueue<T> {
+    fn with_cap(cap: usize) -> Self {
+        BoundQueue::new(cap)
+    }
+
+    fn walk(&self) -> usize {
+        self.iter().map(black_box).count()
+    }
+}
+
+// with_pow2_capacity: the same queue, wrapped by masking
+struct Pow2<T>(BoundQueue<T>);
+
+impl<T> Queue<T> for Pow2<T> {
+    fn push(&mut self, item: T) {
+        self.0.push(item)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.0.pop()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> Subject<T> for Pow2<T> {
+    fn with_cap(cap: usize) -> Self {
+        Pow2(BoundQueue::with_pow2_capacity(cap))
+    }
+
+    fn walk(&self) -> usize {
+        self.0.walk()
+    }
+}
+
+impl<T> Subject<T> for UnboundQueue<T> {
+    fn with_cap(_: usize) -> Self {
+        UnboundQueue::new()
+    }
+
+    fn walk(&self) -> usize {
+        self.iter().map(black_box).count()
+    }
+}
+
+struct Deque<T>(VecDeque<T>);
+
+impl<T> Queue<T> for Deque<T> {
+    fn push(&mut self, item: T) {
+        self.0.push_back(item)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> Subject<T> for Deque<T> {
+    fn with_cap(cap: usize) -> Self {
+        Deque(VecDeque::with_capacity(cap))
+    }
+
+    fn walk(&self) -> usize {
+        self.0.iter().map(black_box).count()
+    }
+}
+
+struct List<T>(LinkedList<T>);
+
+impl<T> Queue<T> for List<T> {
+    fn push(&mut self, item: T) {
+        self.0.push_back(item)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> Subject<T> for List<T> {
+    fn with_cap(_: usize) -> Self {
+        List(LinkedList::new())
+    }
+
+    fn walk(&self) -> usize {
+        self.0.iter().map(black_box).count()
+    }
+}
+
+fn filled<T, Q: Subject<T>>(cap: usize, len: usize, make: fn(u64) -> T) -> Q {
+    let mut q = Q::with_cap(cap);
+    for i in 0..len as u64 {
+        q.push(make(i));
+    }
+    q
+}
+
+// one push and one pop with `size` elements queued
+fn churn<T, Q: Subject<T>>(g: &mut BenchmarkGroup<WallTime>, name: &str, make: fn(u64) -> T) {
+    for &size in SIZES {
+        g.bench_with_input(BenchmarkId::new(name, size), &size, |b, &size| {
+            let mut q: Q = filled(size + 1, size, make);
+            let mut i = 0;
+            b.iter(|| {
+                q.push(make(black_box(i)));
+                i += 1;
+                black_box(q.pop())
+            })
+        });
+    }
+}
+
+// `size` pushes onto an empty queue, then pops until it is empty again
+fn fill_drain<T, Q: Subject<T>>(g: &mut BenchmarkGroup<WallTime>, name: &str, make: fn(u64) -> T) {
+    for &size in SIZES {
+        g.bench_with_input(BenchmarkId::new(name, size), &size, |b, &size| {
+            let mut q = Q::with_cap(size);
+            b.iter(|| {
+                for i in 0..size as u64 {
+                    q.push(make(black_box(i)));
+                }
+                while let Some(v) = q.pop() {
+                    black_box(v);
+                }
+            })
+        });
+    }
+}
+
+fn iterate<T, Q: Subject<T>>(g: &mut BenchmarkGroup<WallTime>, name: &str, make: fn(u64) -> T) {
+    for &size in SIZES {
+        g.bench_with_input(BenchmarkId::new(name, size), &size, |b, &size| {
+            let q: Q = filled(size, size, make);
+            b.iter(|| q.walk())
+        });
+    }
+}
+
+// two pushes for every pop until `size` elements are queued, then a drain
+fn mixed<T, Q: Subject<T>>(g: &mut BenchmarkGroup<WallTime>, name: &str, make: fn(u64) -> T) {
+    for &size in SIZES {
+        g.bench_with_input(BenchmarkId::new(name, size), &size, |b, &size| {
+            let mut q = Q::with_cap(size);
+            b.iter(|| {
+                for i in 0..size as u64 {
+                    q.push(make(black_box(i)));
+                    if i % 2 == 1 {
+                        black_box(q.pop());
+                    }
+                }
+                while let Some(v) = q.pop() {
+                    black_box(v);
+                }
+            })
+        });
+    }
+}
+
+macro_rules! compare {
+    ($c:expr, $workload:ident, $ty:ident, $make:expr) => {{
+        let mut g = $c.benchmark_group(concat!(stringify!($workload), "/", stringify!($ty)));
+        $workload::<$ty, BoundQueue<$ty>>(&mut g, "bound", $make);
+        $workload::<$ty, Pow2<$ty>>(&mut g, "bound_pow2", $make);
+        $workload::<$ty, UnboundQueue<$ty>>(&mut g, "unbound", $make);
+        $workload::<$ty, Deque<$ty>>(&mut g, "vecdeque", $make);
+        $workload::<$ty, List<$ty>>(&mut g, "linkedlist", $make);
+        g.finish();
+    }};
+}
+
+fn bench_u64(c: &mut Criterion) {
+    let make = |i| i;
+    compare!(c, churn, u64, make);
+    compare!(c, fill_drain, u64, make);
+    compare!(c, iterate, u64, make);
+    compare!(c, mixed, u64, make);
+}
+
+fn bench_string(c: &mut Criterion) {
+    let make = |i: u64| i.to_string();
+    compare!(c, churn, String, make);
+    compare!(c, fill_drain, String, make);
+    compare!(c, iterate, String, make);
+    compare!(c, mixed, String, make);
+}
+
+criterion_group!(benches, bench_u64, bench_string);
+criterion_main!(benches);
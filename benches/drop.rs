@@ -0,0 +1,36 @@
+#[macro_use]
+extern crate criterion;
+extern crate queue;
+
+use criterion::{BatchSize, Criterion};
+use queue::bound::BoundQueue;
+use queue::Queue;
+
+const N: usize = 1_000_000;
+
+// a full queue whose live run crosses the end of the buffer
+fn wrapped<T, F: Fn(usize) -> T>(f: F) -> BoundQueue<T> {
+    let mut q = BoundQueue::new(N);
+    for i in 0..N / 2 {
+        q.push(f(i));
+    }
+    for _ in 0..N / 2 {
+        q.pop();
+    }
+    for i in 0..N {
+        q.push(f(i));
+    }
+    q
+}
+
+fn bench_drop(c: &mut Criterion) {
+    c.bench_function("drop/u64", |b| {
+        b.iter_batched(|| wrapped(|i| i as u64), drop, BatchSize::LargeInput)
+    });
+    c.bench_function("drop/string", |b| {
+        b.iter_batched(|| wrapped(|i| i.to_string()), drop, BatchSize::LargeInput)
+    });
+}
+
+criterion_group!(benches, bench_drop);
+criterion_main!(benches);
@@ -0,0 +1,38 @@
+#[macro_use]
+extern crate criterion;
+extern crate queue;
+
+use criterion::Criterion;
+use queue::bound::BoundQueue;
+use queue::Queue;
+
+const N: usize = 1_000_000;
+
+fn samples() -> BoundQueue<f32> {
+    let mut q = BoundQueue::new(N);
+    for i in 0..N / 3 {
+        q.push(i as f32);
+        q.pop();
+    }
+    for i in 0..N {
+        q.push(i as f32);
+    }
+    q
+}
+
+fn bench_duplicate(c: &mut Criterion) {
+    let q = samples();
+    c.bench_function("copy/duplicate", |b| b.iter(|| q.duplicate()));
+    c.bench_function("copy/per_element", |b| {
+        b.iter(|| {
+            let mut d = BoundQueue::new(N);
+            for x in q.iter() {
+                d.push(*x);
+            }
+            d
+        })
+    });
+}
+
+criterion_group!(benches, bench_duplicate);
+criterion_main!(benches);
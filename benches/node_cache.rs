@@ -0,0 +1,31 @@
+#[macro_use]
+extern crate criterion;
+extern crate queue;
+
+use criterion::{black_box, Criterion};
+use queue::unbound::UnboundQueue;
+use queue::Queue;
+
+const BURST: u64 = 1000;
+
+// Fills the queue with a burst of small items and drains it again, so
+// every push needs a node.
+fn burst(q: &mut UnboundQueue<u64>) {
+    for i in 0..BURST {
+        q.push(black_box(i));
+    }
+    while let Some(v) = q.pop() {
+        black_box(v);
+    }
+}
+
+fn bench_node_cache(c: &mut Criterion) {
+    let mut q = UnboundQueue::new();
+    c.bench_function("unbound/burst", |b| b.iter(|| burst(&mut q)));
+    // a burst leaves the cache full for the next one
+    let mut q = UnboundQueue::with_node_cache(BURST as usize);
+    c.bench_function("unbound/burst cached", |b| b.iter(|| burst(&mut q)));
+}
+
+criterion_group!(benches, bench_node_cache);
+criterion_main!(benches);
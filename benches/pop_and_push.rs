@@ -0,0 +1,75 @@
+#[macro_use]
+extern crate criterion;
+extern crate queue;
+
+use criterion::{black_box, Criterion};
+use queue::unbound::UnboundQueue;
+use queue::Queue;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Counts allocations so the report shows the traffic pop_and_push saves.
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const CYCLES: u64 = 1024;
+
+fn ring() -> UnboundQueue<u64> {
+    let mut q = UnboundQueue::new();
+    for i in 0..64 {
+        q.push(i);
+    }
+    q
+}
+
+fn pop_push(q: &mut UnboundQueue<u64>) {
+    for i in 0..CYCLES {
+        let x = q.pop().unwrap();
+        q.push(black_box(x + i));
+    }
+}
+
+fn pop_and_push(q: &mut UnboundQueue<u64>) {
+    for i in 0..CYCLES {
+        let x = q.pop_and_push(black_box(i)).unwrap();
+        black_box(x);
+    }
+}
+
+fn allocs(f: fn(&mut UnboundQueue<u64>)) -> usize {
+    let mut q = ring();
+    let before = ALLOCS.load(Ordering::Relaxed);
+    f(&mut q);
+    ALLOCS.load(Ordering::Relaxed) - before
+}
+
+fn bench_pop_and_push(c: &mut Criterion) {
+    println!(
+        "allocations per {} cycles: pop+push {}, pop_and_push {}",
+        CYCLES,
+        allocs(pop_push),
+        allocs(pop_and_push)
+    );
+    let mut q = ring();
+    c.bench_function("unbound/pop+push", |b| b.iter(|| pop_push(&mut q)));
+    let mut q = ring();
+    c.bench_function("unbound/pop_and_push", |b| b.iter(|| pop_and_push(&mut q)));
+}
+
+criterion_group!(benches, bench_pop_and_push);
+criterion_main!(benches);
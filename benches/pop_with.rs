@@ -0,0 +1,67 @@
+#[macro_use]
+extern crate criterion;
+extern crate queue;
+
+use criterion::{black_box, BatchSize, Criterion};
+use queue::bound::BoundQueue;
+use queue::unbound::UnboundQueue;
+use queue::Queue;
+
+type Page = [u8; 4096];
+
+fn filled<Q: Queue<Page>>(mut q: Q) -> Q {
+    for i in 0..64 {
+        q.push([i as u8; 4096]);
+    }
+    q
+}
+
+fn bench_pop_with(c: &mut Criterion) {
+    c.bench_function("bound/pop", |b| {
+        b.iter_batched_ref(
+            || filled(BoundQueue::new(64)),
+            |q| {
+                while let Some(page) = q.pop() {
+                    black_box(page);
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("bound/pop_with", |b| {
+        b.iter_batched_ref(
+            || filled(BoundQueue::new(64)),
+            |q| {
+                while let Some(h) = q.pop_with(|page| page[0]) {
+                    black_box(h);
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("unbound/pop", |b| {
+        b.iter_batched_ref(
+            || filled(UnboundQueue::new()),
+            |q| {
+                while let Some(page) = q.pop() {
+                    black_box(page);
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("unbound/pop_with", |b| {
+        b.iter_batched_ref(
+            || filled(UnboundQueue::new()),
+            |q| {
+                while let Some(h) = q.pop_with(|page| page[0]) {
+                    black_box(h);
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_pop_with);
+criterion_main!(benches);
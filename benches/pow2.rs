@@ -0,0 +1,47 @@
+#[macro_use]
+extern crate criterion;
+extern crate queue;
+
+use criterion::{black_box, Criterion};
+use queue::bound::BoundQueue;
+use queue::Queue;
+
+fn churn(q: &mut BoundQueue<u64>, n: u64) {
+    for i in 0..n {
+        q.push(black_box(i));
+        q.push(black_box(i));
+        black_box(q.pop());
+        black_box(q.pop());
+    }
+}
+
+fn fill_drain(q: &mut BoundQueue<u64>) {
+    for i in 0..1024 {
+        q.push(black_box(i));
+    }
+    while let Some(v) = q.pop() {
+        black_box(v);
+    }
+}
+
+fn bench_wrap(c: &mut Criterion) {
+    c.bench_function("churn/branch", |b| {
+        let mut q = BoundQueue::new(1024);
+        b.iter(|| churn(&mut q, 4096))
+    });
+    c.bench_function("churn/mask", |b| {
+        let mut q = BoundQueue::new_pow2(10);
+        b.iter(|| churn(&mut q, 4096))
+    });
+    c.bench_function("fill_drain/branch", |b| {
+        let mut q = BoundQueue::new(1024);
+        b.iter(|| fill_drain(&mut q))
+    });
+    c.bench_function("fill_drain/mask", |b| {
+        let mut q = BoundQueue::new_pow2(10);
+        b.iter(|| fill_drain(&mut q))
+    });
+}
+
+criterion_group!(benches, bench_wrap);
+criterion_main!(benches);
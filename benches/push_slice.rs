@@ -0,0 +1,40 @@
+#[macro_use]
+extern crate criterion;
+extern crate queue;
+
+use criterion::{black_box, Criterion};
+use queue::bound::BoundQueue;
+use queue::Queue;
+
+const CHUNK: usize = 4096;
+
+fn one_by_one(q: &mut BoundQueue<u8>, data: &[u8], out: &mut [u8]) {
+    for &b in data {
+        q.push(black_box(b));
+    }
+    for slot in out.iter_mut() {
+        *slot = q.pop().unwrap();
+    }
+}
+
+fn bulk(q: &mut BoundQueue<u8>, data: &[u8], out: &mut [u8]) {
+    q.push_slice(black_box(data));
+    q.pop_into(out);
+}
+
+fn bench_push_slice(c: &mut Criterion) {
+    let data: Vec<u8> = (0..CHUNK).map(|i| i as u8).collect();
+    let mut out = vec![0; CHUNK];
+    // a capacity that isn't a multiple of the chunk, so the copies wrap
+    c.bench_function("u8/push+pop", |b| {
+        let mut q = BoundQueue::new(CHUNK + 100);
+        b.iter(|| one_by_one(&mut q, &data, &mut out))
+    });
+    c.bench_function("u8/push_slice+pop_into", |b| {
+        let mut q = BoundQueue::new(CHUNK + 100);
+        b.iter(|| bulk(&mut q, &data, &mut out))
+    });
+}
+
+criterion_group!(benches, bench_push_slice);
+criterion_main!(benches);
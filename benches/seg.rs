@@ -0,0 +1,35 @@
+#[macro_use]
+extern crate criterion;
+extern crate queue;
+
+use criterion::{black_box, Criterion};
+use queue::bound::BoundQueue;
+use queue::seg::SegQueue;
+use queue::unbound::UnboundQueue;
+use queue::Queue;
+
+const LEN: usize = 1000;
+
+// Keeps LEN elements queued and measures one push + pop at that level.
+fn churn<Q: Queue<u64>>(c: &mut Criterion, name: &str, mut q: Q) {
+    for i in 0..LEN as u64 {
+        q.push(i);
+    }
+    let mut i = 0u64;
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            q.push(black_box(i));
+            i += 1;
+            black_box(q.pop())
+        })
+    });
+}
+
+fn bench_churn(c: &mut Criterion) {
+    churn(c, "churn/bound", BoundQueue::new(LEN + 1));
+    churn(c, "churn/unbound", UnboundQueue::new());
+    churn(c, "churn/seg", SegQueue::new());
+}
+
+criterion_group!(benches, bench_churn);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+// Uses the queues from a `no_std` crate. Built as a library, so it needs
+// no panic handler or entry point of its own:
+//
+//     cargo build --example no_std --no-default-features
+//
+// The dev-dependencies need std, so on a bare-metal target build the
+// library alone:
+//
+//     cargo build --lib --no-default-features --target thumbv7em-none-eabihf
+
+#![no_std]
+
+extern crate alloc;
+extern crate queue;
+
+use queue::bound::BoundQueue;
+use queue::unbound::UnboundQueue;
+use queue::Queue;
+
+/// Moves everything `samples` accepts into a bounded queue, then drains
+/// it through an unbounded one; returns how many samples were turned away
+/// and the sum of the rest.
+pub fn relay(samples: &[u32], cap: usize) -> (usize, u64) {
+    let mut bound = BoundQueue::new(cap);
+    let mut rejected = 0;
+    for &s in samples {
+        if bound.try_push(s).is_err() {
+            rejected += 1;
+        }
+    }
+    let mut unbound: UnboundQueue<u32> = bound.drain().collect();
+    let mut sum = 0;
+    while let Some(s) = Queue::pop(&mut unbound) {
+        sum += u64::from(s);
+    }
+    (rejected, sum)
+}
@@ -0,0 +1,275 @@
+//! Combinators over the `Queue` trait. Each adapter is a `Queue` itself,
+//! so they nest:
+//!
+//! ```
+//! use queue::adapters::{chain, filter, map};
+//! use queue::bound::BoundQueue;
+//! use queue::unbound::UnboundQueue;
+//! use queue::Queue;
+//!
+//! // the backlog first, then whatever is pushed into the bounded stage;
+//! // only pushes are mapped
+//! let stages = chain(UnboundQueue::from_slice(&[10, 20, 30]), BoundQueue::new(4));
+//! let mut q = filter(map(stages, |x: i32| x * 10), |x: &i32| *x != 20);
+//! q.push(4);
+//! assert_eq!(q.pop(), Some(10));
+//! assert_eq!(q.pop(), Some(30));
+//! assert_eq!(q.pop(), Some(40));
+//! assert!(q.is_empty());
+//! ```
+
+use super::{Queue, TryQueue};
+use core::marker::PhantomData;
+use error::PushError;
+
+/// Pops from `a` until it is empty, then from `b`; pushes go to `b`.
+pub fn chain<A, B>(a: A, b: B) -> Chained<A, B> {
+    Chained { a, b }
+}
+
+/// Runs every pushed item through `f` on its way into `q`.
+pub fn map<Q, F, U>(q: Q, f: F) -> Mapped<Q, F, U> {
+    Mapped {
+        inner: q,
+        f,
+        marker: PhantomData,
+    }
+}
+
+/// Pops only the items `keep` accepts; the rest are dropped as they come
+/// up.
+pub fn filter<Q, P>(q: Q, keep: P) -> Filtered<Q, P> {
+    Filtered { inner: q, keep }
+}
+
+/// A two-stage queue; see `chain`.
+pub struct Chained<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Chained<A, B> {
+    pub fn first(&self) -> &A {
+        &self.a
+    }
+
+    pub fn second(&self) -> &B {
+        &self.b
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<T, A: Queue<T>, B: Queue<T>> Queue<T> for Chained<A, B> {
+    fn push(&mut self, item: T) {
+        self.b.push(item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.a.pop().or_else(|| self.b.pop())
+    }
+
+    fn len(&self) -> usize {
+        self.a.len() + self.b.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.a.is_empty() && self.b.is_empty()
+    }
+}
+
+impl<T, A: Queue<T>, B: TryQueue<T>> TryQueue<T> for Chained<A, B> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        self.b.try_push(item)
+    }
+}
+
+/// A queue whose pushes go through a function; see `map`.
+///
+/// Its own `push` takes whatever `f` takes. It is a `Queue` when `f` maps
+/// the queue's item type to itself, so the items can come back out.
+pub struct Mapped<Q, F, U> {
+    inner: Q,
+    f: F,
+    marker: PhantomData<fn(U)>,
+}
+
+impl<Q, F, U> Mapped<Q, F, U> {
+    pub fn get_ref(&self) -> &Q {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> Q {
+        self.inner
+    }
+}
+
+impl<T, Q: Queue<T>, F: Fn(U) -> T, U> Mapped<Q, F, U> {
+    pub fn push(&mut self, item: U) {
+        self.inner.push((self.f)(item));
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.inner.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+impl<T, Q: Queue<T>, F: Fn(T) -> T> Queue<T> for Mapped<Q, F, T> {
+    fn push(&mut self, item: T) {
+        Mapped::push(self, item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.inner.pop()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+/// Hands back the mapped item if `inner` turns it away.
+impl<T, Q: TryQueue<T>, F: Fn(T) -> T> TryQueue<T> for Mapped<Q, F, T> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        self.inner.try_push((self.f)(item))
+    }
+}
+
+/// A queue that drops the items a predicate rejects; see `filter`.
+///
+/// Items are only checked as they reach the front, so `len` counts the
+/// ones not looked at yet too and is an upper bound: `pop` may return
+/// None while `len` is not 0.
+pub struct Filtered<Q, P> {
+    inner: Q,
+    keep: P,
+}
+
+impl<Q, P> Filtered<Q, P> {
+    pub fn get_ref(&self) -> &Q {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> Q {
+        self.inner
+    }
+}
+
+impl<T, Q: Queue<T>, P: FnMut(&T) -> bool> Queue<T> for Filtered<Q, P> {
+    fn push(&mut self, item: T) {
+        self.inner.push(item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        loop {
+            let item = self.inner.pop()?;
+            if (self.keep)(&item) {
+                return Some(item);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+impl<T, Q: TryQueue<T>, P: FnMut(&T) -> bool> TryQueue<T> for Filtered<Q, P> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        self.inner.try_push(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bound::BoundQueue;
+    use std::cell::Cell;
+    use unbound::UnboundQueue;
+
+    #[test]
+    fn chained() {
+        let mut q = chain(BoundQueue::from_slice(&[1]), UnboundQueue::new());
+        assert!(!q.is_empty());
+        q.push(2);
+        assert_eq!((q.first().len(), q.second().len()), (1, 1));
+        assert_eq!(q.pop(), Some(1));
+        // empty only once both stages are
+        assert!(!q.is_empty());
+        assert_eq!(q.len(), 1);
+        assert_eq!(q.pop(), Some(2));
+        assert!(q.is_empty());
+        assert_eq!(q.pop(), None);
+
+        let mut q = chain(UnboundQueue::new(), BoundQueue::new(1));
+        assert_eq!(q.try_push('a'), Ok(()));
+        assert_eq!(q.try_push('b'), Err(PushError::Full('b')));
+        let (a, b) = q.into_inner();
+        assert_eq!((a.len(), b.len()), (0, 1));
+    }
+
+    #[test]
+    fn mapped() {
+        let mut q = map(UnboundQueue::new(), |s: &str| s.len());
+        q.push("abc");
+        q.push("");
+        assert_eq!(q.len(), 2);
+        assert_eq!(q.pop(), Some(3));
+        assert_eq!(q.pop(), Some(0));
+        assert!(q.is_empty());
+
+        let mut q = map(BoundQueue::new(1), |x: u8| x.saturating_add(200));
+        assert_eq!(q.try_push(100), Ok(()));
+        assert_eq!(q.try_push(1), Err(PushError::Full(201)));
+        assert_eq!(Queue::pop(&mut q), Some(255));
+    }
+
+    struct Counted<'a>(&'a Cell<usize>, i32);
+
+    impl<'a> Drop for Counted<'a> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn filtered_drops_once() {
+        let drops = Cell::new(0);
+        {
+            let mut q = filter(BoundQueue::new(8), |c: &Counted| c.1 % 3 == 0);
+            for i in 0..8 {
+                q.push(Counted(&drops, i));
+            }
+            assert_eq!(q.pop().map(|c| c.1), Some(0));
+            assert_eq!(drops.get(), 1);
+            // 1 and 2 are skipped and dropped on the way to 3
+            assert_eq!(q.pop().map(|c| c.1), Some(3));
+            assert_eq!(drops.get(), 4);
+            assert_eq!(q.len(), 4);
+            assert_eq!(q.pop().map(|c| c.1), Some(6));
+            assert_eq!(q.pop().map(|c| c.1), None);
+            assert!(q.is_empty());
+            assert_eq!(drops.get(), 8);
+            q.push(Counted(&drops, 9));
+        }
+        assert_eq!(drops.get(), 9);
+    }
+}
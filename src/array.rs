@@ -0,0 +1,316 @@
+use super::{Queue, TryQueue};
+use core::mem::MaybeUninit;
+use core::ptr;
+use error::PushError;
+
+/// A ring of `N` slots stored inline, for when even one allocation is too
+/// many. It holds no pointers, so it can be built in a `const` or `static`
+/// and moved freely. All `N` slots are used: the length is kept alongside
+/// the head instead of leaving one slot free.
+///
+/// ```
+/// use queue::array::ArrayQueue;
+/// use queue::Queue;
+/// use std::sync::Mutex;
+///
+/// static EVENTS: Mutex<ArrayQueue<u32, 4>> = Mutex::new(ArrayQueue::new());
+///
+/// EVENTS.lock().unwrap().push(7);
+/// assert_eq!(EVENTS.lock().unwrap().pop(), Some(7));
+/// ```
+pub struct ArrayQueue<T, const N: usize> {
+    data: [MaybeUninit<T>; N],
+    head: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> ArrayQueue<T, N> {
+    pub const fn new() -> Self {
+        ArrayQueue {
+            data: [const { MaybeUninit::uninit() }; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub const fn cap(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Like `push`, but hands `item` back if the queue is full.
+    pub fn try_push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+        let tail = self.phys(self.len);
+        self.data[tail] = MaybeUninit::new(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        if self.is_empty() {
+            return None;
+        }
+        unsafe { Some(&*self.data[self.head].as_ptr()) }
+    }
+
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        if self.is_empty() {
+            return None;
+        }
+        unsafe { Some(&mut *self.data[self.head].as_mut_ptr()) }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            pos: self.head,
+            len: self.len,
+            data: &self.data,
+        }
+    }
+
+    // slot of the element `idx` places behind head; only called with
+    // idx < N, so never for N == 0
+    fn phys(&self, idx: usize) -> usize {
+        let p = self.head + idx;
+        if p >= N {
+            p - N
+        } else {
+            p
+        }
+    }
+}
+
+impl<T, const N: usize> Default for ArrayQueue<T, N> {
+    fn default() -> Self {
+        ArrayQueue::new()
+    }
+}
+
+impl<T, const N: usize> Queue<T> for ArrayQueue<T, N> {
+    fn push(&mut self, item: T) {
+        if self.try_push(item).is_err() {
+            trace!(warn, cap = N, "push rejected, queue full");
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let v = unsafe { ptr::read(self.data[self.head].as_ptr()) };
+        self.head = self.phys(1);
+        self.len -= 1;
+        Some(v)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T, const N: usize> TryQueue<T> for ArrayQueue<T, N> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        ArrayQueue::try_push(self, item).map_err(PushError::Full)
+    }
+}
+
+impl<T, const N: usize> Drop for ArrayQueue<T, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+pub struct Iter<'a, T: 'a> {
+    pos: usize,
+    len: usize,
+    data: &'a [MaybeUninit<T>],
+}
+
+pub struct IntoIter<T, const N: usize>(ArrayQueue<T, N>);
+
+impl<T, const N: usize> IntoIterator for ArrayQueue<T, N> {
+    type Item = T;
+    type IntoIter = IntoIter<T, N>;
+    fn into_iter(self) -> IntoIter<T, N> {
+        IntoIter(self)
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a ArrayQueue<T, N> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let c = self.pos;
+        self.pos = if c + 1 == self.data.len() { 0 } else { c + 1 };
+        self.len -= 1;
+        unsafe { Some(&*self.data[c].as_ptr()) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::mem;
+
+    #[test]
+    fn no_slots() {
+        let mut q = ArrayQueue::<String, 0>::new();
+        assert!(q.is_empty() && q.is_full());
+        assert_eq!(q.try_push("a".to_string()), Err("a".to_string()));
+        q.push("b".to_string());
+        assert_eq!(q.len(), 0);
+        assert_eq!(q.pop(), None);
+        assert_eq!(q.peek(), None);
+        assert_eq!(q.iter().next(), None);
+    }
+
+    #[test]
+    fn one_slot() {
+        let mut q = ArrayQueue::<_, 1>::new();
+        assert_eq!(q.cap(), 1);
+        for i in 0..5 {
+            assert_eq!(q.try_push(i), Ok(()));
+            assert!(q.is_full());
+            assert_eq!(q.try_push(10), Err(10));
+            *q.peek_mut().unwrap() += 100;
+            assert_eq!(q.iter().collect::<Vec<_>>(), [&(i + 100)]);
+            assert_eq!(q.pop(), Some(i + 100));
+            assert!(q.is_empty());
+        }
+    }
+
+    #[test]
+    fn fill_drain_fill() {
+        let mut q = ArrayQueue::<String, 5>::new();
+        let mut next = 0;
+        // each round starts the ring at a different slot
+        for round in 0..7 {
+            for _ in 0..5 {
+                q.push(next.to_string());
+                next += 1;
+            }
+            q.push("dropped".to_string());
+            assert_eq!(q.len(), 5);
+            let want: Vec<_> = (next - 5..next).map(|i| i.to_string()).collect();
+            assert_eq!(q.iter().cloned().collect::<Vec<_>>(), want);
+            let mut got = vec![];
+            for _ in 0..5 {
+                got.push(q.pop().unwrap());
+            }
+            assert_eq!(got, want);
+            assert_eq!(q.pop(), None);
+
+            // leave `round % 3` behind so the next fill wraps further on
+            for _ in 0..round % 3 {
+                q.push(String::new());
+            }
+            while !q.is_empty() {
+                q.pop();
+            }
+        }
+    }
+
+    #[test]
+    fn const_and_static() {
+        const EMPTY: ArrayQueue<u8, 3> = ArrayQueue::new();
+        let mut q = EMPTY;
+        q.push(1);
+        assert_eq!(EMPTY.len(), 0);
+        assert_eq!(q.len(), 1);
+
+        static mut Q: ArrayQueue<u32, 8> = ArrayQueue::new();
+        let q = unsafe { &mut *ptr::addr_of_mut!(Q) };
+        for i in 0..10 {
+            q.push(i);
+        }
+        assert_eq!(q.iter().sum::<u32>(), 28);
+        assert_eq!(mem::take(q).into_iter().count(), 8);
+        assert!(q.is_empty());
+    }
+
+    struct Logged<'a>(&'a RefCell<Vec<i32>>, i32);
+
+    impl<'a> Drop for Logged<'a> {
+        fn drop(&mut self) {
+            self.0.borrow_mut().push(self.1);
+        }
+    }
+
+    #[test]
+    fn drops_live_elements() {
+        let log = RefCell::new(vec![]);
+        {
+            let mut q = ArrayQueue::<_, 4>::new();
+            for i in 0..6 {
+                q.push(Logged(&log, i));
+            }
+            // 4 and 5 didn't fit
+            assert_eq!(*log.borrow(), [4, 5]);
+            drop(q.pop());
+            drop(q.pop());
+            q.push(Logged(&log, 6));
+            q.push(Logged(&log, 7));
+        }
+        // the rest go front to back, across the wrap
+        assert_eq!(*log.borrow(), [4, 5, 0, 1, 2, 3, 6, 7]);
+
+        // and so do the ones an owning iterator didn't get to
+        let mut q = ArrayQueue::<_, 3>::new();
+        for i in 0..3 {
+            q.push(Logged(&log, i));
+        }
+        let mut it = q.into_iter();
+        assert_eq!(it.len(), 3);
+        assert_eq!(it.next().map(|l| l.1), Some(0));
+        drop(it);
+        assert_eq!(log.borrow()[8..], [0, 1, 2]);
+    }
+}
@@ -0,0 +1,261 @@
+use super::{Queue, TryQueue};
+use alloc::vec::Vec;
+use error::{CapacityError, PushError};
+
+/// A bounded FIFO of bools packed 64 to a word. Bit `i` of the ring lives
+/// in bit `i % 64` of word `i / 64`, and the ring wraps after `capacity`
+/// bits, which need not be a multiple of 64.
+pub struct BitQueue {
+    words: Vec<u64>,
+    cap: usize,
+    head: usize,
+    len: usize,
+}
+
+impl BitQueue {
+    pub fn new(cap: usize) -> Self {
+        BitQueue {
+            words: vec![0; cap.div_ceil(64)],
+            cap,
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Like `new`, but allocation failure comes back as an error instead
+    /// of an abort.
+    pub fn try_new(cap: usize) -> Result<Self, CapacityError> {
+        let n = cap.div_ceil(64);
+        let mut words = Vec::new();
+        words.try_reserve_exact(n)?;
+        words.resize(n, 0);
+        Ok(BitQueue {
+            words,
+            cap,
+            head: 0,
+            len: 0,
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.cap
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { q: self, pos: 0 }
+    }
+
+    /// Pushes bits in order until the queue fills, returning how many
+    /// were taken.
+    pub fn push_bits(&mut self, bits: &[bool]) -> usize {
+        let n = bits.len().min(self.cap - self.len);
+        for &b in &bits[..n] {
+            self.push(b);
+        }
+        n
+    }
+
+    /// Pushes the 8 bits of `byte`, least significant first, if all of
+    /// them fit.
+    pub fn push_byte(&mut self, byte: u8) -> bool {
+        if self.cap - self.len < 8 {
+            return false;
+        }
+        for i in 0..8 {
+            self.push(byte >> i & 1 == 1);
+        }
+        true
+    }
+
+    /// Pops 8 bits into a byte, the first popped bit as the least
+    /// significant one. `None` if fewer than 8 bits are queued.
+    pub fn pop_byte(&mut self) -> Option<u8> {
+        if self.len < 8 {
+            return None;
+        }
+        let mut byte = 0;
+        for i in 0..8 {
+            if self.pop() == Some(true) {
+                byte |= 1 << i;
+            }
+        }
+        Some(byte)
+    }
+
+    // ring position of the bit `idx` places behind head
+    fn pos(&self, idx: usize) -> usize {
+        let p = self.head + idx;
+        if p >= self.cap {
+            p - self.cap
+        } else {
+            p
+        }
+    }
+
+    fn get(&self, pos: usize) -> bool {
+        self.words[pos / 64] >> (pos % 64) & 1 == 1
+    }
+}
+
+impl Queue<bool> for BitQueue {
+    fn push(&mut self, item: bool) {
+        if self.is_full() {
+            trace!(warn, cap = self.cap, "push rejected, queue full");
+            return;
+        }
+        let pos = self.pos(self.len);
+        let mask = 1 << (pos % 64);
+        if item {
+            self.words[pos / 64] |= mask;
+        } else {
+            self.words[pos / 64] &= !mask;
+        }
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<bool> {
+        if self.len == 0 {
+            return None;
+        }
+        let v = self.get(self.head);
+        self.head = self.pos(1);
+        self.len -= 1;
+        Some(v)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl TryQueue<bool> for BitQueue {
+    fn try_push(&mut self, item: bool) -> Result<(), PushError<bool>> {
+        if self.is_full() {
+            return Err(PushError::Full(item));
+        }
+        self.push(item);
+        Ok(())
+    }
+}
+
+pub struct Iter<'a> {
+    q: &'a BitQueue,
+    pos: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = bool;
+    fn next(&mut self) -> Option<bool> {
+        if self.pos == self.q.len {
+            return None;
+        }
+        let v = self.q.get(self.q.pos(self.pos));
+        self.pos += 1;
+        Some(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    fn rng(mut x: u64) -> impl FnMut() -> u64 {
+        move || {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        }
+    }
+
+    #[test]
+    fn word_boundaries() {
+        let mut q = BitQueue::new(130);
+        for round in 0..5 {
+            for i in 0..130 {
+                q.push((i + round) % 3 == 0);
+            }
+            assert!(q.is_full());
+            q.push(true);
+            assert_eq!(q.len(), 130);
+            // drain part way so the next round starts mid-word
+            for i in 0..70 {
+                assert_eq!(q.pop(), Some((i + round) % 3 == 0));
+            }
+            for i in 70..130 {
+                assert_eq!(q.pop(), Some((i + round) % 3 == 0));
+            }
+            assert!(q.is_empty());
+            for _ in 0..(37 * (round + 1)) {
+                q.push(false);
+                q.pop();
+            }
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        let mut q = BitQueue::new(20);
+        assert!(q.push_byte(0b1010_0110));
+        assert!(q.push_byte(0xff));
+        assert!(!q.push_byte(0));
+        assert_eq!(q.push_bits(&[true, false, true, true, true]), 4);
+        assert_eq!(q.pop_byte(), Some(0b1010_0110));
+        assert_eq!(q.pop_byte(), Some(0xff));
+        assert_eq!(q.pop_byte(), None);
+        assert_eq!(q.iter().collect::<Vec<_>>(), vec![true, false, true, true]);
+    }
+
+    #[test]
+    fn try_new() {
+        let mut q = BitQueue::try_new(65).unwrap();
+        q.push_bits(&[true; 70]);
+        assert!(q.is_full());
+        assert_eq!(BitQueue::try_new(0).unwrap().pop(), None);
+        #[cfg(not(miri))]
+        assert!(BitQueue::try_new(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn model() {
+        let mut next = rng(0x9e37_79b9_7f4a_7c15);
+        for &cap in &[1, 63, 64, 65, 128, 200] {
+            let mut q = BitQueue::new(cap);
+            let mut m = VecDeque::new();
+            for _ in 0..5000 {
+                match next() % 4 {
+                    0 | 1 => {
+                        let b = next() & 1 == 0;
+                        q.push(b);
+                        if m.len() < cap {
+                            m.push_back(b);
+                        }
+                    }
+                    2 => assert_eq!(q.pop(), m.pop_front()),
+                    _ => {
+                        let n = (next() % 70) as usize;
+                        let bits: Vec<bool> = (0..n).map(|_| next() & 1 == 0).collect();
+                        let took = q.push_bits(&bits);
+                        assert_eq!(took, n.min(cap - m.len()));
+                        m.extend(&bits[..took]);
+                    }
+                }
+                assert_eq!(q.len(), m.len());
+            }
+            assert!(q.iter().eq(m.iter().cloned()));
+        }
+    }
+}
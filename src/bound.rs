@@ -1,231 +1,4740 @@
-use super::Queue;
-use alloc::raw_vec::RawVec;
-use std::ptr;
-use std::slice;
+use super::{Queue, TryQueue};
+use alloc::alloc;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+#[cfg(feature = "serde")]
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::FromIterator;
+#[cfg(feature = "serde")]
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::ops::{Bound, Range, RangeBounds};
+use core::ops::{Deref, DerefMut, Index, IndexMut};
+use core::ptr;
+use core::slice;
+use error::{CapacityError, PushError};
+use merge::{Refs, Walk};
+use raw::RawBuf;
+#[cfg(feature = "serde")]
+use serde::de::{DeserializeSeed, Deserializer, Error, MapAccess, SeqAccess, Visitor};
+#[cfg(feature = "serde")]
+use serde::ser::{SerializeStruct, Serializer};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use spsc::{self, Consumer, Producer};
+#[cfg(feature = "std")]
+use std::io::{self, IoSlice, IoSliceMut};
+use unbound::UnboundQueue;
 
-pub struct BoundQueue<T> {
-    data: RawVec<T>,
-    head: usize,
-    tail: usize,
+/// A fixed-capacity FIFO ring buffer.
+///
+/// Once a queue exists, `push`, `pop`, `get` and iteration never panic: a
+/// push onto a full queue is dropped, and a shrink that can't allocate is
+/// skipped. The constructors without `try_` panic on capacity overflow and
+/// abort if the allocation fails, as `Vec` does.
+///
+/// `I` is the integer type head and tail are kept in; see `Idx`.
+///
+/// A queue is `Send` and `Sync` when its elements are, so one holding `Rc`s
+/// can't leave its thread:
+///
+/// ```compile_fail
+/// use queue::bound::BoundQueue;
+/// use std::rc::Rc;
+/// use std::thread;
+///
+/// let q = BoundQueue::from_slice(&[Rc::new(1)]);
+/// thread::spawn(move || drop(q));
+/// ```
+pub struct BoundQueue<T, I: Idx = usize> {
+    data: RawBuf<T>,
+    head: I,
+    tail: I,
+    // set by new_pow2: head/tail are free-running counters wrapped by masking
+    mask: Option<usize>,
+    shrink: ShrinkPolicy,
+    stats: Counters,
+}
+
+// The queue owns its elements outright, as a Vec does, so sending it sends
+// them, and a shared queue only ever hands out `&T`. The iterators borrow
+// through slice iterators and pick up the right bounds from those.
+unsafe impl<T: Send, I: Idx> Send for BoundQueue<T, I> {}
+unsafe impl<T: Sync, I: Idx> Sync for BoundQueue<T, I> {}
+
+/// When `pop` should give memory back; see `BoundQueue::maybe_shrink`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ShrinkPolicy {
+    #[default]
+    Never,
+    /// Shrink once fewer than `fraction` of the slots are in use, but never
+    /// below `min_capacity`. `fraction` should stay under 0.5 so a shrink
+    /// isn't followed by another one right away.
+    WhenBelow { fraction: f64, min_capacity: usize },
+}
+
+/// What a queue has seen since it was created or `reset_stats` was last
+/// called; see `BoundQueue::stats`.
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueueStats {
+    /// The longest the queue has been.
+    pub high_water_mark: usize,
+    pub pushed: u64,
+    /// Elements taken out from either end or the middle, drains and
+    /// evictions included. Elements dropped in place by `truncate`,
+    /// `clear` or `retain` are not counted.
+    pub popped: u64,
+    /// Elements turned away because the queue was full.
+    pub rejected: u64,
+}
+
+// Without the `stats` feature the counters are a zero-sized no-op, so
+// the hot paths compile to what they were.
+#[cfg(feature = "stats")]
+type Counters = QueueStats;
+
+#[cfg(not(feature = "stats"))]
+#[derive(Debug, Clone, Copy)]
+struct Counters;
+
+#[cfg(feature = "stats")]
+impl QueueStats {
+    // for a queue that starts out holding `len` elements
+    fn new(len: usize) -> Self {
+        QueueStats {
+            high_water_mark: len,
+            ..QueueStats::default()
+        }
+    }
+
+    fn pushed(&mut self, n: usize, len: usize) {
+        self.pushed += n as u64;
+        self.saw(len);
+    }
+
+    fn saw(&mut self, len: usize) {
+        self.high_water_mark = cmp::max(self.high_water_mark, len);
+    }
+
+    fn popped(&mut self, n: usize) {
+        self.popped += n as u64;
+    }
+
+    fn rejected(&mut self, n: usize) {
+        self.rejected += n as u64;
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+impl Counters {
+    fn new(_: usize) -> Self {
+        Counters
+    }
+
+    #[inline(always)]
+    fn pushed(&mut self, _: usize, _: usize) {}
+
+    #[inline(always)]
+    fn saw(&mut self, _: usize) {}
+
+    #[inline(always)]
+    fn popped(&mut self, _: usize) {}
+
+    #[inline(always)]
+    fn rejected(&mut self, _: usize) {}
+}
+
+/// An index type for `BoundQueue`: `u8`, `u16`, `u32` or `usize`. A
+/// narrower index makes the queue itself smaller but limits its capacity to
+/// `MAX` elements, or half of `MAX + 1` for the power-of-two layout.
+pub trait Idx: Copy + private::Sealed {
+    const MAX: usize;
+    // truncates, so counters wrap at the type's width
+    fn from_usize(i: usize) -> Self;
+    fn to_usize(self) -> usize;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! idx {
+    ($($t:ident)*) => {$(
+        impl private::Sealed for $t {}
+
+        impl Idx for $t {
+            const MAX: usize = $t::MAX as usize;
+
+            fn from_usize(i: usize) -> Self {
+                i as $t
+            }
+
+            fn to_usize(self) -> usize {
+                self as usize
+            }
+        }
+    )*};
+}
+
+idx!(u8 u16 u32 usize);
+
+// slot count for new_pow2, None if it doesn't fit a Layout
+fn pow2_size(cap_log2: u32) -> Option<usize> {
+    1usize
+        .checked_shl(cap_log2)
+        .filter(|&n| n <= isize::MAX as usize)
+}
+
+// panics on overflow and aborts on allocation failure, naming the call
+fn capacity_failed(e: CapacityError, method: &str, arg: usize) -> ! {
+    match e.alloc_layout() {
+        Some(layout) => alloc::handle_alloc_error(layout),
+        None => panic!("BoundQueue::{}({}): {}", method, arg, e),
+    }
 }
 
 impl<T> BoundQueue<T> {
+    /// Panics if `size` elements of T can't be laid out in one buffer, and
+    /// aborts if the allocation fails; see `try_new`.
     pub fn new(size: usize) -> Self {
-        let mut buf = RawVec::new();
-        buf.reserve(0, size + 1);
+        match BoundQueue::try_new(size) {
+            Ok(q) => q,
+            Err(e) => capacity_failed(e, "new", size),
+        }
+    }
 
-        BoundQueue {
-            head: 0,
-            tail: 0,
-            data: buf,
+    /// Same as `new`, named like the std collections.
+    pub fn with_capacity(size: usize) -> Self {
+        BoundQueue::new(size)
+    }
+
+    /// Like `new`, but capacity overflow and allocation failure come back as
+    /// an error instead of a panic or an abort.
+    pub fn try_new(size: usize) -> Result<Self, CapacityError> {
+        BoundQueue::try_new_indexed(size)
+    }
+
+    pub fn new_pow2(cap_log2: u32) -> Self {
+        let size = pow2_size(cap_log2).expect("capacity overflow");
+        BoundQueue::from_buf(RawBuf::with_capacity(size), Some(size - 1))
+    }
+
+    pub fn try_new_pow2(cap_log2: u32) -> Result<Self, CapacityError> {
+        BoundQueue::try_new_pow2_indexed(cap_log2)
+    }
+
+    /// `new_pow2` with room for at least `size` elements: the capacity is
+    /// `size` rounded up to a power of two (and at least 1), so wrapping
+    /// is a mask instead of a compare.
+    pub fn with_pow2_capacity(size: usize) -> Self {
+        match BoundQueue::try_with_pow2_capacity(size) {
+            Ok(q) => q,
+            Err(e) => capacity_failed(e, "with_pow2_capacity", size),
         }
     }
 
-    pub fn cap(&self) -> usize {
-        self.data.cap()
+    pub fn try_with_pow2_capacity(size: usize) -> Result<Self, CapacityError> {
+        let size = size
+            .checked_next_power_of_two()
+            .ok_or_else(CapacityError::overflow)?;
+        BoundQueue::try_new_pow2(size.trailing_zeros())
     }
 
-    pub fn is_full(&self) -> bool {
-        return self.tail + 1 == self.head;
+    /// Like `new`, but the buffer is allocated with at least `align`-byte
+    /// alignment, e.g. for aligned SIMD loads over `as_slices`. Fails if
+    /// `align` is not a power of two; alignments below `align_of::<T>()`
+    /// are raised to it.
+    pub fn new_aligned(size: usize, align: usize) -> Result<Self, CapacityError> {
+        let slots = size.checked_add(1).ok_or_else(CapacityError::overflow)?;
+        let buf = RawBuf::with_align(slots, align)?;
+        Ok(BoundQueue::from_buf(buf, None))
+    }
+
+    /// A full queue of clones of `s`, front first, cloned straight into
+    /// the buffer.
+    pub fn from_slice(s: &[T]) -> Self
+    where
+        T: Clone,
+    {
+        let mut q = BoundQueue::new(s.len());
+        let p: *mut T = q.data.ptr();
+        for (i, x) in s.iter().enumerate() {
+            unsafe { ptr::write(p.add(i), x.clone()) };
+            // a panicking clone leaves a queue of the ones written so far
+            q.tail = i + 1;
+        }
+        q.stats.saw(s.len());
+        q
+    }
+
+    /// Collects the last `cap` items of `iter`, front first, by evicting
+    /// the oldest element whenever the queue is full. Also returns how many
+    /// items were evicted.
+    pub fn from_iter_keep_last<I: IntoIterator<Item = T>>(cap: usize, iter: I) -> (Self, usize) {
+        let mut q = BoundQueue::new(cap);
+        let mut evicted = 0;
+        for x in iter {
+            if q.len() == cap {
+                evicted += 1;
+                if cap == 0 {
+                    continue;
+                }
+                q.pop();
+            }
+            q.push(x);
+        }
+        (q, evicted)
+    }
+
+    /// Collects the first `cap` items of `iter` and stops there. The rest of
+    /// the iterator is handed back untouched; count it to know how many
+    /// items didn't fit.
+    pub fn from_iter_keep_first<I: IntoIterator<Item = T>>(
+        cap: usize,
+        iter: I,
+    ) -> (Self, I::IntoIter) {
+        let mut q = BoundQueue::new(cap);
+        let mut iter = iter.into_iter();
+        while q.len() < cap {
+            match iter.next() {
+                Some(x) => q.push(x),
+                None => break,
+            }
+        }
+        (q, iter)
+    }
+
+    /// Adopts the Vec's allocation without moving any element: the Vec's
+    /// contents become the queue (front first) and its spare capacity the
+    /// free slots, less one slot that stays reserved as the ring's
+    /// sentinel, so `cap()` is `v.capacity() - 1`. A Vec with no spare
+    /// capacity is grown by one slot first, which may reallocate.
+    pub fn from_vec_in_place(mut v: Vec<T>) -> Self {
+        if v.len() == v.capacity() {
+            v.reserve_exact(1);
+        }
+        let mut v = ManuallyDrop::new(v);
+        let (ptr, len, cap) = (v.as_mut_ptr(), v.len(), v.capacity());
+        let q = BoundQueue {
+            head: 0,
+            tail: len,
+            data: unsafe { RawBuf::from_raw_parts(ptr, cap) },
+            mask: None,
+            shrink: ShrinkPolicy::Never,
+            stats: Counters::new(len),
+        };
+        trace!(debug, cap = q.usable(), "bound queue created");
+        q
     }
 }
 
-impl<T> Queue<T> for BoundQueue<T> {
-    fn push(&mut self, item: T) {
-        let mut next = self.tail + 1;
-        if next >= self.cap() {
-            next = 0
+impl<T, I: Idx> BoundQueue<T, I> {
+    /// `try_new` for a queue with a narrower index type, which also fails
+    /// if `size` doesn't fit it.
+    pub fn try_new_indexed(size: usize) -> Result<Self, CapacityError> {
+        if size > I::MAX {
+            return Err(CapacityError::overflow());
         }
-        if next == self.head {
-            return;
+        let slots = size.checked_add(1).ok_or_else(CapacityError::overflow)?;
+        let buf = RawBuf::with_align(slots, mem::align_of::<T>())?;
+        Ok(BoundQueue::from_buf(buf, None))
+    }
+
+    /// `try_new_pow2` for a queue with a narrower index type; `cap_log2`
+    /// may be at most the type's width minus one.
+    pub fn try_new_pow2_indexed(cap_log2: u32) -> Result<Self, CapacityError> {
+        let size = pow2_size(cap_log2).ok_or_else(CapacityError::overflow)?;
+        // the free-running counters wrap at the index width, and a full
+        // queue must not look empty
+        if size > I::MAX / 2 + 1 {
+            return Err(CapacityError::overflow());
         }
-        let tail = self.tail;
-        unsafe {
-            self.write(tail, item);
+        let buf = RawBuf::with_align(size, mem::align_of::<T>())?;
+        Ok(BoundQueue::from_buf(buf, Some(size - 1)))
+    }
+
+    fn from_buf(data: RawBuf<T>, mask: Option<usize>) -> Self {
+        let q = BoundQueue {
+            head: I::from_usize(0),
+            tail: I::from_usize(0),
+            data,
+            mask,
+            shrink: ShrinkPolicy::Never,
+            stats: Counters::new(0),
+        };
+        trace!(debug, cap = q.usable(), "bound queue created");
+        q
+    }
+
+    /// The element `idx` places behind the front, or None past the end.
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        if idx < self.len() {
+            Some(unsafe { self.elem(idx) })
+        } else {
+            None
         }
-        self.tail = next;
     }
 
-    fn pop(&mut self) -> Option<T> {
-        if self.head == self.tail {
-            return None;
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        if idx < self.len() {
+            Some(unsafe { self.elem_mut(idx) })
+        } else {
+            None
         }
-        let mut next = self.head + 1;
-        if next >= self.cap() {
-            next = 0
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        if self.is_empty() {
+            return None;
         }
-        let head = self.head;
-        let v = unsafe { self.read(head) };
-        self.head = next;
-        return Some(v);
+        Some(unsafe { self.elem_mut(0) })
     }
 
-    fn is_empty(&self) -> bool {
-        self.head == self.tail
+    /// The most recently pushed element.
+    pub fn back(&self) -> Option<&T> {
+        self.len().checked_sub(1).and_then(|i| self.get(i))
     }
-}
 
-impl<T> BoundQueue<T> {
-    unsafe fn as_slice(&self) -> &[T] {
-        slice::from_raw_parts(self.data.ptr(), self.data.cap())
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        let i = self.len().checked_sub(1)?;
+        Some(unsafe { self.elem_mut(i) })
     }
 
-    unsafe fn as_slice_mut(&self) -> &mut [T] {
-        slice::from_raw_parts_mut(self.data.ptr(), self.data.cap())
+    /// Puts `item` in front of every queued element, so `pop` returns it
+    /// next. Like `push`, drops `item` if the queue is full.
+    pub fn push_front(&mut self, item: T) {
+        if self.is_full() {
+            trace!(warn, cap = self.usable(), "push rejected, queue full");
+            self.stats.rejected(1);
+            return;
+        }
+        self.retreat_head();
+        let head = self.phys(0);
+        unsafe {
+            self.write_at(head, item);
+        }
+        self.stats.pushed(1, self.len());
     }
 
-    unsafe fn read(&mut self, off: usize) -> T {
-        ptr::read(self.data.ptr().add(off))
+    /// Removes the most recently pushed element. Applies the shrink policy
+    /// as `pop` does.
+    pub fn pop_back(&mut self) -> Option<T> {
+        let len = self.len().checked_sub(1)?;
+        let off = self.phys(len);
+        self.set_len(len);
+        let v = unsafe { self.read_at(off) };
+        self.stats.popped(1);
+        self.maybe_shrink(self.shrink);
+        Some(v)
     }
 
-    unsafe fn write(&mut self, off: usize, item: T) {
-        ptr::write(self.data.ptr().add(off), item);
+    /// Moves the first `n` elements to the back, keeping their order, as
+    /// `VecDeque::rotate_left` does. Only `min(n, len() - n)` elements move
+    /// in memory. Panics if `n > len()`.
+    pub fn rotate_left(&mut self, n: usize) {
+        let len = self.len();
+        assert!(n <= len, "rotate by {} in a queue of length {}", n, len);
+        if n <= len - n {
+            self.front_to_back(n);
+        } else {
+            self.back_to_front(len - n);
+        }
     }
 
-    pub fn into_iter(self) -> IntoIter<T> {
-        IntoIter(self)
+    /// Moves the last `n` elements to the front, keeping their order, as
+    /// `VecDeque::rotate_right` does. Panics if `n > len()`.
+    pub fn rotate_right(&mut self, n: usize) {
+        let len = self.len();
+        assert!(n <= len, "rotate by {} in a queue of length {}", n, len);
+        if n <= len - n {
+            self.back_to_front(n);
+        } else {
+            self.front_to_back(len - n);
+        }
     }
 
-    pub fn iter(&self) -> Iter<T> {
-        Iter {
-            pos: self.head,
-            tail: self.tail,
-            data: unsafe { self.as_slice() },
+    // The slot an element leaves is always free by the time one is written
+    // on the other side, even in a full power-of-two queue.
+    fn front_to_back(&mut self, n: usize) {
+        for _ in 0..n {
+            unsafe {
+                let v = self.read_at(self.phys(0));
+                self.advance_head(1);
+                let len = self.len();
+                self.write_at(self.phys(len), v);
+                self.set_len(len + 1);
+            }
         }
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<T> {
-        IterMut {
-            pos: self.head,
-            tail: self.tail,
-            data: unsafe { self.as_slice_mut() },
+    fn back_to_front(&mut self, n: usize) {
+        for _ in 0..n {
+            unsafe {
+                let len = self.len() - 1;
+                let v = self.read_at(self.phys(len));
+                self.set_len(len);
+                self.retreat_head();
+                self.write_at(self.phys(0), v);
+            }
         }
     }
-}
 
-pub struct Iter<'a, T: 'a> {
-    pos: usize,
-    tail: usize,
-    data: &'a [T],
-}
+    /// Swaps the elements `i` and `j` places behind the front. Panics if
+    /// either is out of bounds.
+    pub fn swap(&mut self, i: usize, j: usize) {
+        let len = self.len();
+        for &idx in &[i, j] {
+            if idx >= len {
+                out_of_bounds(idx, len);
+            }
+        }
+        let p = self.data.ptr();
+        unsafe { ptr::swap(p.add(self.phys(i)), p.add(self.phys(j))) };
+    }
 
-pub struct IterMut<'a, T: 'a> {
-    pos: usize,
-    tail: usize,
-    data: &'a mut [T],
-}
+    /// Borrows the first `min(n, len())` elements without popping them, as
+    /// the run up to the end of the buffer followed by the run that wrapped
+    /// around to its start. The second slice is empty unless they straddle
+    /// the wrap.
+    pub fn peek_n(&self, n: usize) -> (&[T], &[T]) {
+        let n = cmp::min(n, self.len());
+        if n == 0 {
+            return (&[], &[]);
+        }
+        let start = self.phys(0);
+        let first = cmp::min(n, self.data.cap() - start);
+        let p = self.data.ptr();
+        unsafe {
+            (
+                slice::from_raw_parts(p.add(start), first),
+                slice::from_raw_parts(p, n - first),
+            )
+        }
+    }
 
-pub struct IntoIter<T>(BoundQueue<T>);
+    /// Every element, as `peek_n(len())` would give them.
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        self.peek_n(self.len())
+    }
 
-impl<T> Iterator for IntoIter<T> {
-    type Item = T;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.pop()
+    pub fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+        let (a, b) = self.ranges();
+        unsafe { (&mut *self.slice_ptr(a), &mut *self.slice_ptr(b)) }
     }
-}
 
-impl<T> Drop for BoundQueue<T> {
-    fn drop(&mut self) {
-        for e in self.iter_mut() {
+    /// Moves the elements so they sit in one run and returns it. Nothing
+    /// is allocated; a queue that is already contiguous is left alone.
+    pub fn make_contiguous(&mut self) -> &mut [T] {
+        let (a, b) = self.ranges();
+        if !b.is_empty() {
+            let len = self.len();
             unsafe {
-                ptr::drop_in_place(e as *mut _);
+                // spare slots are rotated along, initialized or not
+                let slots = self.slots();
+                let p = self.data.ptr() as *mut MaybeUninit<T>;
+                slice::from_raw_parts_mut(p, slots).rotate_left(a.start);
             }
+            self.set_head(0);
+            self.set_len(len);
         }
+        self.as_mut_slices().0
     }
-}
 
-impl<'a, T> Iterator for Iter<'a, T> {
-    type Item = &'a T;
-    fn next(&mut self) -> Option<Self::Item> {
-        let c = self.pos;
-        if c == self.tail {
-            return None;
+    pub fn buffer_align(&self) -> usize {
+        self.data.align()
+    }
+
+    /// How many elements the queue can hold.
+    pub fn cap(&self) -> usize {
+        self.usable()
+    }
+
+    /// Makes every `pop` apply `policy` afterwards.
+    pub fn set_shrink_policy(&mut self, policy: ShrinkPolicy) {
+        self.shrink = policy;
+    }
+
+    pub fn shrink_policy(&self) -> ShrinkPolicy {
+        self.shrink
+    }
+
+    /// Moves the elements into a smaller buffer if `policy` says the queue
+    /// is sparse enough, returning whether it did. If the new buffer can't
+    /// be allocated the queue is left as it is. The new buffer holds
+    /// twice the current length, or `min_capacity` if that is more, rounded
+    /// up to a power of two for `new_pow2` queues. The queue's bound drops
+    /// with its capacity.
+    pub fn maybe_shrink(&mut self, policy: ShrinkPolicy) -> bool {
+        let (fraction, min_capacity) = match policy {
+            ShrinkPolicy::Never => return false,
+            ShrinkPolicy::WhenBelow {
+                fraction,
+                min_capacity,
+            } => (fraction, min_capacity),
+        };
+        let (len, cap) = (self.len(), self.usable());
+        if mem::size_of::<T>() == 0 || len as f64 >= fraction * cap as f64 {
+            return false;
         }
-        if c == self.data.len() - 1 {
-            self.pos = 0;
+        let mut target = cmp::max(min_capacity, len * 2);
+        if self.mask.is_some() {
+            target = target.next_power_of_two();
+        }
+        if target >= cap {
+            return false;
+        }
+        trace!(debug, from = cap, to = target, "bound queue shrunk");
+        self.relocate(target).is_ok()
+    }
+
+    /// Makes room for at least `additional` more elements. Like `Vec`, the
+    /// capacity at least doubles whenever it has to grow, so repeated small
+    /// reserves stay cheap. Panics or aborts as `grow_to` does.
+    pub fn reserve(&mut self, additional: usize) {
+        if let Err(e) = self.try_reserve(additional) {
+            capacity_failed(e, "reserve", additional);
+        }
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), CapacityError> {
+        let want = self
+            .len()
+            .checked_add(additional)
+            .ok_or_else(CapacityError::overflow)?;
+        if want <= self.usable() {
+            return Ok(());
+        }
+        self.try_grow_to(cmp::max(want, self.usable().saturating_mul(2)))
+    }
+
+    /// Moves the elements, in order, into a buffer that holds at least
+    /// `new_cap` of them, rounded up to a power of two for `new_pow2`
+    /// queues. Does nothing if the queue can already hold that many. Panics
+    /// if the capacity overflows and aborts if the allocation fails; see
+    /// `try_grow_to`.
+    pub fn grow_to(&mut self, new_cap: usize) {
+        if let Err(e) = self.try_grow_to(new_cap) {
+            capacity_failed(e, "grow_to", new_cap);
+        }
+    }
+
+    /// Like `grow_to`, but failure comes back as an error and leaves the
+    /// queue as it was.
+    pub fn try_grow_to(&mut self, new_cap: usize) -> Result<(), CapacityError> {
+        let cap = self.usable();
+        if new_cap <= cap {
+            return Ok(());
+        }
+        let target = if self.mask.is_some() {
+            // same limit as try_new_pow2_indexed
+            new_cap
+                .checked_next_power_of_two()
+                .filter(|&n| n <= I::MAX / 2 + 1)
+                .ok_or_else(CapacityError::overflow)?
+        } else if new_cap > I::MAX {
+            return Err(CapacityError::overflow());
+        } else {
+            new_cap
+        };
+        trace!(debug, from = cap, to = target, "bound queue grown");
+        self.relocate(target)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head() == self.tail()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.usable()
+    }
+
+    pub fn len(&self) -> usize {
+        let (head, tail) = (self.head(), self.tail());
+        if self.mask.is_some() {
+            tail.wrapping_sub(head) & I::MAX
+        } else if tail >= head {
+            tail - head
         } else {
-            self.pos += 1;
+            self.slots() - head + tail
         }
-        unsafe { Some(self.data.get_unchecked(c)) }
+    }
+
+    fn head(&self) -> usize {
+        self.head.to_usize()
+    }
+
+    fn tail(&self) -> usize {
+        self.tail.to_usize()
+    }
+
+    fn set_head(&mut self, head: usize) {
+        self.head = I::from_usize(head);
+    }
+
+    fn set_tail(&mut self, tail: usize) {
+        self.tail = I::from_usize(tail);
     }
 }
 
-impl<'a, T> Iterator for IterMut<'a, T> {
-    type Item = &'a mut T;
-    fn next(&mut self) -> Option<Self::Item> {
-        let c = self.pos;
-        if c == self.tail {
-            return None;
+impl<T, I: Idx> Queue<T> for BoundQueue<T, I> {
+    fn push(&mut self, item: T) {
+        if let Some(mask) = self.mask {
+            let tail = self.tail();
+            if tail.wrapping_sub(self.head()) & I::MAX > mask {
+                trace!(warn, cap = self.usable(), "push rejected, queue full");
+                self.stats.rejected(1);
+                return;
+            }
+            unsafe {
+                self.write_at(tail & mask, item);
+            }
+            self.set_tail(tail.wrapping_add(1));
+            self.stats.pushed(1, self.len());
+            return;
         }
-        if c == self.data.len() - 1 {
-            self.pos = 0;
-        } else {
-            self.pos += 1;
+        let mut next = self.tail() + 1;
+        if next >= self.slots() {
+            next = 0
+        }
+        if next == self.head() {
+            trace!(warn, cap = self.usable(), "push rejected, queue full");
+            self.stats.rejected(1);
+            return;
         }
+        let tail = self.tail();
         unsafe {
-            let item = self.data.get_unchecked_mut(c);
-            Some(&mut *(item as *mut _))
+            self.write_at(tail, item);
+        }
+        self.set_tail(next);
+        self.stats.pushed(1, self.len());
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
         }
+        if let Some(mask) = self.mask {
+            let head = self.head();
+            let v = unsafe { self.read_at(head & mask) };
+            self.set_head(head.wrapping_add(1));
+            self.stats.popped(1);
+            self.maybe_shrink(self.shrink);
+            return Some(v);
+        }
+        let mut next = self.head() + 1;
+        if next >= self.slots() {
+            next = 0
+        }
+        let head = self.head();
+        let v = unsafe { self.read_at(head) };
+        self.set_head(next);
+        self.stats.popped(1);
+        self.maybe_shrink(self.shrink);
+        Some(v)
+    }
+
+    fn len(&self) -> usize {
+        BoundQueue::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        BoundQueue::is_empty(self)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl<T, I: Idx> TryQueue<T> for BoundQueue<T, I> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        BoundQueue::try_push(self, item).map_err(PushError::Full)
+    }
+}
 
-    #[test]
-    fn is_empty() {
-        let q = BoundQueue::<i32>::new(10);
-        assert_eq!(q.is_empty(), true);
+impl<T, I: Idx> Walk<T> for BoundQueue<T, I> {
+    fn peek(&self) -> Option<&T> {
+        BoundQueue::peek(self)
     }
 
-    #[test]
-    fn test_push_pop() {
-        let mut q = BoundQueue::<i32>::new(10);
-        for i in 1..=10 {
-            q.push(i);
-            assert_eq!(q.pop(), Some(i));
+    fn walk<'a>(&'a self) -> Refs<'a, T> {
+        Box::new(self.iter())
+    }
+}
+
+impl<T, I: Idx> BoundQueue<T, I> {
+    // physical slot of the element `idx` places behind head
+    fn phys(&self, idx: usize) -> usize {
+        if let Some(mask) = self.mask {
+            return self.head().wrapping_add(idx) & mask;
+        }
+        let p = self.head() + idx;
+        if p >= self.slots() {
+            p - self.slots()
+        } else {
+            p
         }
     }
-    #[test]
-    fn into_iter() {
-        let mut q = BoundQueue::<i32>::new(10);
-        q.push(1);
-        q.push(2);
-        q.push(3);
 
-        let mut iter = q.into_iter();
-        assert_eq!(iter.next(), Some(1));
-        assert_eq!(iter.next(), Some(2));
-        assert_eq!(iter.next(), Some(3));
-        assert_eq!(iter.next(), None);
+    // number of elements the buffer can hold at once
+    fn usable(&self) -> usize {
+        match self.mask {
+            Some(mask) => mask + 1,
+            None => self.slots() - 1,
+        }
     }
 
-    #[test]
-    fn iter() {
-        let mut q = BoundQueue::<String>::new(10);
-        q.push(1.to_string());
-        q.push(2.to_string());
-        q.push(3.to_string());
+    // the buffer's size, sentinel slot included
+    fn slots(&self) -> usize {
+        self.data.cap()
+    }
 
-        let mut iter = q.iter();
-        assert_eq!(iter.next(), Some(&"1".to_string()));
-        assert_eq!(iter.next(), Some(&"2".to_string()));
-        assert_eq!(iter.next(), Some(&"3".to_string()));
-        assert_eq!(iter.next(), None);
+    fn advance_head(&mut self, n: usize) {
+        let len = self.len() - n;
+        let head = if self.mask.is_some() {
+            self.head().wrapping_add(n)
+        } else {
+            self.phys(n)
+        };
+        self.set_head(head);
+        self.set_len(len);
     }
 
-    #[test]
-    fn iter_mut() {
-        let mut q = BoundQueue::<i32>::new(10);
-        q.push(1);
-        q.push(2);
-        q.push(3);
+    // makes room for one more element in front of the current head
+    fn retreat_head(&mut self) {
+        let head = if self.mask.is_some() {
+            self.head().wrapping_sub(1)
+        } else if self.head() == 0 {
+            self.slots() - 1
+        } else {
+            self.head() - 1
+        };
+        self.set_head(head);
+    }
 
-        let mut iter = q.iter_mut();
-        assert_eq!(iter.next(), Some(&mut 1));
-        assert_eq!(iter.next(), Some(&mut 2));
+    fn set_len(&mut self, len: usize) {
+        let tail = if self.mask.is_some() {
+            self.head().wrapping_add(len)
+        } else {
+            self.phys(len)
+        };
+        self.set_tail(tail);
+    }
+
+    // moves the elements, in order, to the start of a new buffer that
+    // holds `usable` of them; on failure the queue is left as it was
+    fn relocate(&mut self, usable: usize) -> Result<(), CapacityError> {
+        let slots = if self.mask.is_some() {
+            usable
+        } else {
+            usable.checked_add(1).ok_or_else(CapacityError::overflow)?
+        };
+        let data = RawBuf::with_align(slots, self.data.align())?;
+        let (a, b) = self.ranges();
+        let len = self.len();
+        unsafe {
+            let src = self.data.ptr();
+            ptr::copy_nonoverlapping(src.add(a.start), data.ptr(), a.len());
+            ptr::copy_nonoverlapping(src.add(b.start), data.ptr().add(a.len()), b.len());
+        }
+        // the old buffer is freed without dropping anything
+        self.data = data;
+        self.set_head(0);
+        self.set_tail(len);
+        if self.mask.is_some() {
+            self.mask = Some(usable - 1);
+        }
+        Ok(())
+    }
+
+    // physical slot ranges of the live elements, front run first
+    fn ranges(&self) -> (Range<usize>, Range<usize>) {
+        self.span(0, self.len())
+    }
+
+    // physical slot ranges of the free space, starting at tail
+    fn free_ranges(&self) -> (Range<usize>, Range<usize>) {
+        let len = self.len();
+        self.span(len, self.usable() - len)
+    }
+
+    // physical slot ranges of the `n` slots from `idx` places behind head
+    fn span(&self, idx: usize, n: usize) -> (Range<usize>, Range<usize>) {
+        let start = self.phys(idx);
+        let slots = self.data.cap();
+        if n <= slots - start {
+            (start..start + n, 0..0)
+        } else {
+            (start..slots, 0..n - (slots - start))
+        }
+    }
+
+    unsafe fn slice_ptr(&self, r: Range<usize>) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(self.data.ptr().add(r.start), r.end - r.start)
+    }
+
+    // If a drop panics the rest are still dropped, as Vec does: a slice's
+    // drop glue keeps going on its own, and the guard covers the second
+    // run when the first one panics.
+    unsafe fn drop_elements(&mut self) {
+        struct Dropper<T>(*mut [T]);
+        impl<T> Drop for Dropper<T> {
+            fn drop(&mut self) {
+                unsafe { ptr::drop_in_place(self.0) };
+            }
+        }
+        let (a, b) = self.ranges();
+        let _back = Dropper(self.slice_ptr(b));
+        ptr::drop_in_place(self.slice_ptr(a));
+    }
+
+    // caller guarantees idx < len
+    unsafe fn elem(&self, idx: usize) -> &T {
+        &*self.data.ptr().add(self.phys(idx))
+    }
+
+    // caller guarantees idx < len
+    unsafe fn elem_mut(&mut self, idx: usize) -> &mut T {
+        &mut *self.data.ptr().add(self.phys(idx))
+    }
+
+    unsafe fn read_at(&mut self, off: usize) -> T {
+        ptr::read(self.data.ptr().add(off))
+    }
+
+    unsafe fn write_at(&mut self, off: usize, item: T) {
+        ptr::write(self.data.ptr().add(off), item);
+    }
+
+    /// Drops every element in place; the buffer is kept.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Drops elements from the back until at most `len` are left. If one of
+    /// those drops panics, the rest are still dropped.
+    pub fn truncate(&mut self, len: usize) {
+        let old = self.len();
+        if len >= old {
+            return;
+        }
+        let (a, b) = self.span(len, old - len);
+        // shorten first, so nothing dropped is ever still queued
+        self.set_len(len);
+        unsafe {
+            let _back = DropGuard(self.slice_ptr(b));
+            ptr::drop_in_place(self.slice_ptr(a));
+        }
+    }
+
+    pub fn contains(&self, x: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter().any(|e| e == x)
+    }
+
+    /// Index from the front of the first element `f` accepts.
+    pub fn position<F: Fn(&T) -> bool>(&self, f: F) -> Option<usize> {
+        self.iter().position(f)
+    }
+
+    /// Like `retain_mut`, for predicates that only need to look.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.retain_mut(|e| f(e))
+    }
+
+    /// Keeps the elements for which `f` returns true, closing the gaps
+    /// toward head so order is preserved. If `f` panics, the element it was
+    /// given and everything after it stay in the queue; nothing is dropped
+    /// twice or leaked.
+    pub fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        let len = self.len();
+        let mut g = RetainGuard {
+            q: self,
+            len,
+            processed: 0,
+            kept: 0,
+        };
+        while g.processed < g.len {
+            let cur = g.q.phys(g.processed);
+            let p = unsafe { g.q.data.ptr().add(cur) };
+            let keep = f(unsafe { &mut *p });
+            g.processed += 1;
+            if keep {
+                if g.kept + 1 != g.processed {
+                    let dst = g.q.phys(g.kept);
+                    unsafe { ptr::copy_nonoverlapping(p, g.q.data.ptr().add(dst), 1) };
+                }
+                g.kept += 1;
+            } else {
+                unsafe { ptr::drop_in_place(p) };
+            }
+        }
+    }
+
+    /// Copies as many of `items` as fit onto the back, with at most two
+    /// memcpys, and returns how many that was.
+    pub fn push_slice(&mut self, items: &[T]) -> usize
+    where
+        T: Copy,
+    {
+        let (a, b) = self.free_ranges();
+        let n1 = cmp::min(items.len(), a.len());
+        let n2 = cmp::min(items.len() - n1, b.len());
+        unsafe {
+            let p = self.data.ptr();
+            ptr::copy_nonoverlapping(items.as_ptr(), p.add(a.start), n1);
+            ptr::copy_nonoverlapping(items.as_ptr().add(n1), p.add(b.start), n2);
+        }
+        let len = self.len() + n1 + n2;
+        self.set_len(len);
+        self.stats.pushed(n1 + n2, len);
+        self.stats.rejected(items.len() - n1 - n2);
+        n1 + n2
+    }
+
+    /// Moves elements from the front into `buf` until it is full or the
+    /// queue is empty, with at most two memcpys, and returns how many were
+    /// moved.
+    pub fn pop_into(&mut self, buf: &mut [T]) -> usize
+    where
+        T: Copy,
+    {
+        let (a, b) = self.ranges();
+        let n1 = cmp::min(buf.len(), a.len());
+        let n2 = cmp::min(buf.len() - n1, b.len());
+        unsafe {
+            let p = self.data.ptr();
+            ptr::copy_nonoverlapping(p.add(a.start), buf.as_mut_ptr(), n1);
+            ptr::copy_nonoverlapping(p.add(b.start), buf.as_mut_ptr().add(n1), n2);
+        }
+        self.advance_head(n1 + n2);
+        self.stats.popped(n1 + n2);
+        n1 + n2
+    }
+
+    /// Logical index of the first occurrence of `needle`, which may
+    /// straddle the point where the ring wraps.
+    pub fn find(&self, needle: &[T]) -> Option<usize>
+    where
+        T: PartialEq,
+    {
+        let len = self.len();
+        if needle.len() > len {
+            return None;
+        }
+        (0..=len - needle.len()).find(|&start| {
+            needle
+                .iter()
+                .enumerate()
+                .all(|(j, x)| unsafe { self.elem(start + j) } == x)
+        })
+    }
+
+    pub fn starts_with(&self, prefix: &[T]) -> bool
+    where
+        T: PartialEq,
+    {
+        prefix.len() <= self.len()
+            && prefix
+                .iter()
+                .enumerate()
+                .all(|(j, x)| unsafe { self.elem(j) } == x)
+    }
+
+    /// Inserts `item` after every element that is less than or equal to
+    /// it, shifting whichever side of the insertion point is shorter. Hands
+    /// the item back in `PushError::Full` if the queue is full. Keeps an already sorted queue
+    /// sorted, with equal elements in insertion order.
+    pub fn insert_sorted(&mut self, item: T) -> Result<(), PushError<T>>
+    where
+        T: Ord,
+    {
+        self.insert_sorted_by(item, |a, b| a.cmp(b))
+    }
+
+    pub fn insert_sorted_by_key<K, F>(&mut self, item: T, mut f: F) -> Result<(), PushError<T>>
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.insert_sorted_by(item, |a, b| f(a).cmp(&f(b)))
+    }
+
+    fn insert_sorted_by<F>(&mut self, item: T, mut cmp: F) -> Result<(), PushError<T>>
+    where
+        F: FnMut(&T, &T) -> cmp::Ordering,
+    {
+        let len = self.len();
+        if len == self.usable() {
+            self.stats.rejected(1);
+            return Err(PushError::Full(item));
+        }
+        // first index whose element is greater than item
+        let (mut lo, mut hi) = (0, len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if cmp(unsafe { self.elem(mid) }, &item) == cmp::Ordering::Greater {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        self.insert_at(lo, item);
+        Ok(())
+    }
+
+    /// Binary-searches a queue sorted front to back, as
+    /// `slice::binary_search` does, over logical indices: `Ok` with the
+    /// index of a match (any one, if there are several), or `Err` with the
+    /// index `x` could be inserted at to keep the queue sorted.
+    pub fn binary_search(&self, x: &T) -> Result<usize, usize>
+    where
+        T: Ord,
+    {
+        self.binary_search_by(|e| e.cmp(x))
+    }
+
+    /// `binary_search` with `f` telling how each element compares to the
+    /// one looked for.
+    pub fn binary_search_by<F>(&self, mut f: F) -> Result<usize, usize>
+    where
+        F: FnMut(&T) -> cmp::Ordering,
+    {
+        // the run that wrapped holds the larger elements; search just the
+        // run the target is in
+        let (a, b) = self.as_slices();
+        match b.first().map(&mut f) {
+            Some(cmp::Ordering::Equal) => Ok(a.len()),
+            Some(cmp::Ordering::Less) => match b[1..].binary_search_by(f) {
+                Ok(i) => Ok(a.len() + 1 + i),
+                Err(i) => Err(a.len() + 1 + i),
+            },
+            _ => a.binary_search_by(f),
+        }
+    }
+
+    pub fn binary_search_by_key<K, F>(&self, key: &K, mut f: F) -> Result<usize, usize>
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.binary_search_by(|e| f(e).cmp(key))
+    }
+
+    // caller guarantees idx <= len < usable
+    fn insert_at(&mut self, idx: usize, item: T) {
+        let len = self.len();
+        let p = self.data.ptr();
+        unsafe {
+            if idx < len - idx {
+                self.retreat_head();
+                for i in 0..idx {
+                    ptr::copy_nonoverlapping(p.add(self.phys(i + 1)), p.add(self.phys(i)), 1);
+                }
+                ptr::write(p.add(self.phys(idx)), item);
+            } else {
+                for i in (idx..len).rev() {
+                    ptr::copy_nonoverlapping(p.add(self.phys(i)), p.add(self.phys(i + 1)), 1);
+                }
+                ptr::write(p.add(self.phys(idx)), item);
+                self.set_len(len + 1);
+            }
+        }
+        self.stats.pushed(1, len + 1);
+    }
+
+    /// Takes out the element `idx` places behind the front, moving
+    /// whichever side of it is shorter to close the gap. None past the end.
+    pub fn remove(&mut self, idx: usize) -> Option<T> {
+        let len = self.len();
+        if idx >= len {
+            return None;
+        }
+        let p = self.data.ptr();
+        unsafe {
+            let item = ptr::read(p.add(self.phys(idx)));
+            if idx < len - 1 - idx {
+                for i in (0..idx).rev() {
+                    ptr::copy_nonoverlapping(p.add(self.phys(i)), p.add(self.phys(i + 1)), 1);
+                }
+                self.advance_head(1);
+            } else {
+                for i in idx + 1..len {
+                    ptr::copy_nonoverlapping(p.add(self.phys(i)), p.add(self.phys(i - 1)), 1);
+                }
+                self.set_len(len - 1);
+            }
+            self.stats.popped(1);
+            Some(item)
+        }
+    }
+
+    /// Takes out the element at `idx`, putting the front element in its
+    /// place. O(1), but reorders the queue.
+    pub fn swap_remove_front(&mut self, idx: usize) -> Option<T> {
+        if idx >= self.len() {
+            return None;
+        }
+        let (front, at) = (self.phys(0), self.phys(idx));
+        unsafe {
+            ptr::swap(self.data.ptr().add(front), self.data.ptr().add(at));
+            let item = self.read_at(front);
+            self.advance_head(1);
+            self.stats.popped(1);
+            Some(item)
+        }
+    }
+
+    /// Takes out the element at `idx`, putting the back element in its
+    /// place. O(1), but reorders the queue.
+    pub fn swap_remove_back(&mut self, idx: usize) -> Option<T> {
+        let len = self.len();
+        if idx >= len {
+            return None;
+        }
+        let (back, at) = (self.phys(len - 1), self.phys(idx));
+        unsafe {
+            ptr::swap(self.data.ptr().add(back), self.data.ptr().add(at));
+            self.set_len(len - 1);
+            self.stats.popped(1);
+            Some(self.read_at(back))
+        }
+    }
+
+    /// Runs `f` on the front element where it lies, then drops it there
+    /// and removes it, so the element is never moved out of the buffer.
+    /// The element is removed and dropped exactly once even if `f` panics.
+    pub fn pop_with<R, F: FnOnce(&mut T) -> R>(&mut self, f: F) -> Option<R> {
+        if self.is_empty() {
+            return None;
+        }
+        let p = unsafe { self.data.ptr().add(self.phys(0)) };
+        self.advance_head(1);
+        self.stats.popped(1);
+        // the slot is now outside the live run; the guard drops its element
+        let guard = DropGuard(p);
+        Some(f(unsafe { &mut *guard.0 }))
+    }
+
+    /// Removes every element, yielding them front to back; see
+    /// `drain_first`.
+    pub fn drain(&mut self) -> Drain<'_, T, I> {
+        let len = self.len();
+        self.drain_first(len)
+    }
+
+    /// Removes the first `n` elements, or all of them if there are fewer,
+    /// yielding them front to back. Whatever is left when the iterator is
+    /// dropped gets dropped too, even if one of those drops panics. The
+    /// buffer stays as it is.
+    pub fn drain_first(&mut self, n: usize) -> Drain<'_, T, I> {
+        let left = cmp::min(n, self.len());
+        Drain { q: self, left }
+    }
+
+    /// Like `push`, but hands `item` back if the queue is full.
+    pub fn try_push(&mut self, item: T) -> Result<(), T> {
+        match self.push_slot() {
+            Some(slot) => {
+                slot.write(item);
+                Ok(())
+            }
+            None => {
+                self.stats.rejected(1);
+                Err(item)
+            }
+        }
+    }
+
+    /// Pushes `item`, first making room by removing the front element if
+    /// the queue is full, and returns what was removed. A queue that holds
+    /// nothing at all hands `item` straight back.
+    pub fn force_push(&mut self, item: T) -> Option<T> {
+        let mut evicted = None;
+        if self.len() == self.usable() {
+            if self.usable() == 0 {
+                return Some(item);
+            }
+            let head = self.phys(0);
+            evicted = Some(unsafe { self.read_at(head) });
+            self.advance_head(1);
+            self.stats.popped(1);
+            trace!(debug, cap = self.usable(), "evicted front element");
+        }
+        if self.try_push(item).is_err() {
+            unreachable!("BoundQueue has room after evicting");
+        }
+        evicted
+    }
+
+    /// Reserves the back slot so an element can be built in place. The
+    /// element only becomes part of the queue once the guard is committed;
+    /// dropping the guard leaves the queue as it was (and forgets whatever
+    /// was written to the slot). `None` if the queue is full.
+    pub fn push_slot(&mut self) -> Option<SlotGuard<'_, T, I>> {
+        let len = self.len();
+        if len == self.usable() {
+            return None;
+        }
+        let slot = self.phys(len);
+        Some(SlotGuard { q: self, slot })
+    }
+
+    /// Calls `f` and pushes its result only if there is room, so a full
+    /// queue never pays for building the element. Returns whether the
+    /// element was pushed.
+    pub fn push_with<F: FnOnce() -> T>(&mut self, f: F) -> bool {
+        match self.push_slot() {
+            Some(slot) => {
+                slot.write(f());
+                true
+            }
+            None => {
+                self.stats.rejected(1);
+                false
+            }
+        }
+    }
+
+    /// Offers `item` to `merge` along with the back element; if `merge`
+    /// hands it back (or the queue is empty, in which case `merge` isn't
+    /// called) it is pushed as usual. `Err` returns the item when it was
+    /// neither merged nor pushed because the queue is full.
+    pub fn push_or_merge<F>(&mut self, item: T, merge: F) -> Result<(), T>
+    where
+        F: FnOnce(&mut T, T) -> Option<T>,
+    {
+        let item = match self.back_mut() {
+            None => item,
+            Some(back) => match merge(back, item) {
+                Some(item) => item,
+                None => return Ok(()),
+            },
+        };
+        self.try_push(item)
+    }
+
+    /// Appends copies of the elements in the logical range `src`, returning
+    /// how many were appended. As with an LZ77 back-reference, `src` may run
+    /// past the current end: the copy proceeds element by element, so
+    /// `len - 1..len + 3` repeats the last element four times. Either the
+    /// whole range fits or nothing is copied.
+    ///
+    /// Panics if the range is decreasing or a non-empty range starts at or
+    /// past `len()`.
+    pub fn extend_from_within<R: RangeBounds<usize>>(
+        &mut self,
+        src: R,
+    ) -> Result<usize, CapacityError>
+    where
+        T: Copy,
+    {
+        let len = self.len();
+        let start = match src.start_bound() {
+            Bound::Included(&s) => s,
+            Bound::Excluded(&s) => s.checked_add(1).expect("range start overflow"),
+            Bound::Unbounded => 0,
+        };
+        let end = match src.end_bound() {
+            Bound::Included(&e) => e.checked_add(1).expect("range end overflow"),
+            Bound::Excluded(&e) => e,
+            Bound::Unbounded => len,
+        };
+        assert!(
+            start <= end,
+            "range start {} is past its end {}",
+            start,
+            end
+        );
+        let count = end - start;
+        if count == 0 {
+            return Ok(0);
+        }
+        assert!(
+            start < len,
+            "range starts at {} but the queue has {} elements",
+            start,
+            len
+        );
+        let free = self.usable() - len;
+        if count > free {
+            self.stats.rejected(count);
+            return Err(CapacityError::full(count, free));
+        }
+        if mem::size_of::<T>() != 0 {
+            // never read past what has been written, so copy at most
+            // `dist` elements at a time, split where either side wraps
+            let (dist, slots, p) = (len - start, self.data.cap(), self.data.ptr());
+            let mut k = 0;
+            while k < count {
+                let (s, d) = (self.phys(start + k), self.phys(len + k));
+                let run = cmp::min(cmp::min(count - k, dist), cmp::min(slots - s, slots - d));
+                unsafe { ptr::copy_nonoverlapping(p.add(s), p.add(d), run) };
+                k += run;
+            }
+        }
+        self.set_len(len + count);
+        self.stats.pushed(count, len + count);
+        Ok(count)
+    }
+
+    /// Hands over everything queued, buffer included, in O(1) and leaves
+    /// an empty queue in its place with a fresh buffer of the same capacity,
+    /// alignment and shrink policy. Unlike `mem::take`, the capacity is kept.
+    pub fn take(&mut self) -> Self {
+        let data = RawBuf::with_capacity_aligned(self.data.cap(), self.data.align());
+        let mut fresh = BoundQueue::from_buf(data, self.mask);
+        fresh.shrink = self.shrink;
+        fresh.stats = self.stats;
+        let mut taken = mem::replace(self, fresh);
+        taken.stats = Counters::new(taken.len());
+        taken
+    }
+
+    /// Exchanges the two queues, buffers and all, in O(1). Each capacity
+    /// goes with its elements.
+    pub fn swap_with(&mut self, other: &mut Self) {
+        mem::swap(self, other);
+    }
+
+    /// Turns the queue into the two halves of an `spsc::channel` with the
+    /// same capacity and elements. The buffer is handed over as it is,
+    /// except that a `new_pow2` queue is first copied into a plain one.
+    pub fn split(mut self) -> (Producer<T>, Consumer<T>) {
+        if self.mask.is_some() {
+            let mut plain = BoundQueue::new(self.usable());
+            plain.extend(self.drain());
+            return plain.split();
+        }
+        let (head, tail) = (self.head(), self.tail());
+        let q = ManuallyDrop::new(self);
+        // the queue is forgotten, so the buffer has one owner again
+        let data = unsafe { ptr::read(&q.data) };
+        spsc::from_buf(data, head, tail)
+    }
+
+    /// Copies the queue with at most two memcpys, into a buffer of the same
+    /// capacity and alignment. The copy starts unwrapped at slot 0.
+    pub fn duplicate(&self) -> Self
+    where
+        T: Copy,
+    {
+        let data = RawBuf::with_capacity_aligned(self.data.cap(), self.data.align());
+        let (a, b) = self.ranges();
+        let len = self.len();
+        unsafe {
+            let src = self.data.ptr();
+            ptr::copy_nonoverlapping(src.add(a.start), data.ptr(), a.len());
+            ptr::copy_nonoverlapping(src.add(b.start), data.ptr().add(a.len()), b.len());
+        }
+        BoundQueue {
+            head: I::from_usize(0),
+            tail: I::from_usize(len),
+            data,
+            mask: self.mask,
+            shrink: self.shrink,
+            stats: Counters::new(len),
+        }
+    }
+
+    /// The elements front first, moved out with at most two memcpys.
+    pub fn into_vec(mut self) -> Vec<T> {
+        let (a, b) = self.ranges();
+        let len = a.len() + b.len();
+        let mut v = Vec::with_capacity(len);
+        unsafe {
+            let (src, dst) = (self.data.ptr(), v.as_mut_ptr());
+            ptr::copy_nonoverlapping(src.add(a.start), dst, a.len());
+            ptr::copy_nonoverlapping(src.add(b.start), dst.add(a.len()), b.len());
+            // the elements belong to `v` now
+            self.set_len(0);
+            v.set_len(len);
+        }
+        v
+    }
+
+    /// Moves the elements into an `UnboundQueue`, front first.
+    pub fn into_unbound(self) -> UnboundQueue<T> {
+        self.into_iter().collect()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let (a, b) = self.ranges();
+        unsafe {
+            Iter {
+                front: (*self.slice_ptr(a)).iter(),
+                back: (*self.slice_ptr(b)).iter(),
+            }
+        }
+    }
+
+    /// Borrows the queue mutably for as long as the iterator lives, so two
+    /// of them can't overlap:
+    ///
+    /// ```compile_fail
+    /// use queue::bound::BoundQueue;
+    ///
+    /// let mut q = BoundQueue::from_slice(&[1, 2]);
+    /// let mut a = q.iter_mut();
+    /// let mut b = q.iter_mut();
+    /// *a.next().unwrap() += *b.next().unwrap();
+    /// ```
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let (a, b) = self.ranges();
+        // the two ranges never overlap
+        unsafe {
+            IterMut {
+                front: (*self.slice_ptr(a)).iter_mut(),
+                back: (*self.slice_ptr(b)).iter_mut(),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "stats")]
+impl<T, I: Idx> BoundQueue<T, I> {
+    /// The counters since the queue was created or last reset. Elements a
+    /// queue starts out with (or holds at a reset) count toward the high
+    /// water mark only.
+    pub fn stats(&self) -> QueueStats {
+        self.stats
+    }
+
+    /// Zeroes the counters; the high water mark restarts from the current
+    /// length.
+    pub fn reset_stats(&mut self) {
+        self.stats = QueueStats::new(self.len());
+    }
+
+    /// The longest the queue has been.
+    pub fn high_water_mark(&self) -> usize {
+        self.stats.high_water_mark
+    }
+
+    pub fn total_pushed(&self) -> u64 {
+        self.stats.pushed
+    }
+
+    pub fn total_popped(&self) -> u64 {
+        self.stats.popped
+    }
+
+    /// How many elements were turned away because the queue was full.
+    pub fn rejected(&self) -> u64 {
+        self.stats.rejected
+    }
+}
+
+#[cfg(feature = "std")]
+impl<I: Idx> BoundQueue<u8, I> {
+    /// Reads from `r` straight into the free space, with at most one read
+    /// call per free segment. Stops early on a short read. An error after
+    /// some bytes were read is dropped and the bytes read so far returned.
+    /// A reader claiming more bytes than it was given fails with
+    /// `InvalidData`, and none of that read's bytes are queued.
+    pub fn read_from<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
+        let (a, b) = self.free_ranges();
+        let mut total = 0;
+        for seg in [a, b].iter().cloned() {
+            if seg.start == seg.end {
+                break;
+            }
+            let want = seg.end - seg.start;
+            // zero the slots first so the reader only ever sees initialized bytes
+            let buf = unsafe {
+                let p = self.data.ptr().add(seg.start);
+                ptr::write_bytes(p, 0, want);
+                slice::from_raw_parts_mut(p, want)
+            };
+            let n = match r.read(buf).and_then(|n| checked_count(n, want)) {
+                Ok(n) => n,
+                Err(_) if total > 0 => break,
+                Err(e) => return Err(e),
+            };
+            let len = self.len() + n;
+            self.set_len(len);
+            self.stats.pushed(n, len);
+            total += n;
+            if n < want {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Writes the queued bytes to `w`, with at most one write call per
+    /// occupied segment, and drops from the front whatever `w` accepted.
+    /// Errors are handled as in `read_from`, including a writer claiming
+    /// more bytes than it was given.
+    pub fn write_to<W: io::Write>(&mut self, w: &mut W) -> io::Result<usize> {
+        let (a, b) = self.ranges();
+        let mut total = 0;
+        for seg in [a, b].iter().cloned() {
+            if seg.start == seg.end {
+                break;
+            }
+            let want = seg.end - seg.start;
+            let buf = unsafe { slice::from_raw_parts(self.data.ptr().add(seg.start), want) };
+            let n = match w.write(buf).and_then(|n| checked_count(n, want)) {
+                Ok(n) => n,
+                Err(_) if total > 0 => break,
+                Err(e) => return Err(e),
+            };
+            self.advance_head(n);
+            self.stats.popped(n);
+            total += n;
+            if n < want {
+                break;
+            }
+        }
+        Ok(total)
+    }
+}
+
+// a count from a Read or Write impl, which safe code may get wrong
+#[cfg(feature = "std")]
+fn checked_count(n: usize, len: usize) -> io::Result<usize> {
+    if n > len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "reader or writer claimed more bytes than the buffer holds",
+        ));
+    }
+    Ok(n)
+}
+
+#[cfg(feature = "serde")]
+impl<T> BoundQueue<T> {
+    /// Deserializes a sequence straight into a queue of capacity `cap`,
+    /// failing as soon as the sequence turns out to be longer.
+    pub fn deserialize_into<'de, D>(cap: usize, d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        d.deserialize_seq(BoundedVisitor {
+            cap,
+            marker: PhantomData,
+        })
+    }
+}
+
+/// Written as a struct of the capacity and the elements front to back, so
+/// a queue comes back with the capacity it had. A `new_pow2` queue comes
+/// back as a plain one of the same capacity.
+#[cfg(feature = "serde")]
+impl<T: Serialize, I: Idx> Serialize for BoundQueue<T, I> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        struct Items<'a, T: 'a, I: Idx + 'a>(&'a BoundQueue<T, I>);
+        impl<'a, T: Serialize, I: Idx> Serialize for Items<'a, T, I> {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                s.collect_seq(self.0.iter())
+            }
+        }
+
+        let mut st = s.serialize_struct("BoundQueue", 2)?;
+        st.serialize_field("cap", &self.cap())?;
+        st.serialize_field("items", &Items(self))?;
+        st.end()
+    }
+}
+
+/// Fails if there are more elements than the capacity.
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for BoundQueue<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_struct("BoundQueue", &["cap", "items"], QueueVisitor(PhantomData))
+    }
+}
+
+#[cfg(feature = "serde")]
+struct QueueVisitor<T>(PhantomData<T>);
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Visitor<'de> for QueueVisitor<T> {
+    type Value = BoundQueue<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a BoundQueue capacity and elements")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let cap = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        seq.next_element_seed(BoundedVisitor {
+            cap,
+            marker: PhantomData,
+        })?
+        .ok_or_else(|| A::Error::invalid_length(1, &self))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut cap = None;
+        let mut q = None;
+        // elements that come before the capacity wait in a Vec
+        let mut early: Option<Vec<T>> = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match &key[..] {
+                "cap" if cap.is_some() => return Err(A::Error::duplicate_field("cap")),
+                "cap" => cap = Some(map.next_value()?),
+                "items" if q.is_some() || early.is_some() => {
+                    return Err(A::Error::duplicate_field("items"))
+                }
+                "items" => match cap {
+                    Some(cap) => {
+                        q = Some(map.next_value_seed(BoundedVisitor {
+                            cap,
+                            marker: PhantomData,
+                        })?)
+                    }
+                    None => early = Some(map.next_value()?),
+                },
+                other => return Err(A::Error::unknown_field(other, &["cap", "items"])),
+            }
+        }
+        let cap = cap.ok_or_else(|| A::Error::missing_field("cap"))?;
+        match (q, early) {
+            (Some(q), _) => Ok(q),
+            (None, Some(items)) if items.len() > cap => Err(too_long(cap, items.len())),
+            (None, Some(items)) => {
+                let mut q = with_capacity_from_input(cap)?;
+                q.extend(items);
+                Ok(q)
+            }
+            (None, None) => Err(A::Error::missing_field("items")),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn too_long<E: Error>(cap: usize, seen: usize) -> E {
+    E::custom(format_args!(
+        "sequence does not fit in a queue of capacity {} ({} elements seen)",
+        cap, seen
+    ))
+}
+
+// the capacity comes from the input, so a bad one is an error, not a
+// panic or an abort
+#[cfg(feature = "serde")]
+fn with_capacity_from_input<T, E: Error>(cap: usize) -> Result<BoundQueue<T>, E> {
+    BoundQueue::try_new(cap).map_err(E::custom)
+}
+
+#[cfg(feature = "serde")]
+struct BoundedVisitor<T> {
+    cap: usize,
+    marker: PhantomData<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> DeserializeSeed<'de> for BoundedVisitor<T> {
+    type Value = BoundQueue<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_seq(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Visitor<'de> for BoundedVisitor<T> {
+    type Value = BoundQueue<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a sequence of at most {} elements", self.cap)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut q = with_capacity_from_input(self.cap)?;
+        let mut seen = 0;
+        while let Some(v) = seq.next_element()? {
+            seen += 1;
+            if seen > self.cap {
+                return Err(too_long(self.cap, seen));
+            }
+            q.push(v);
+        }
+        Ok(q)
+    }
+}
+
+struct DropGuard<T: ?Sized>(*mut T);
+
+impl<T: ?Sized> Drop for DropGuard<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.0) }
+    }
+}
+
+pub struct SlotGuard<'a, T: 'a, I: Idx + 'a = usize> {
+    q: &'a mut BoundQueue<T, I>,
+    slot: usize,
+}
+
+impl<'a, T, I: Idx> SlotGuard<'a, T, I> {
+    /// Appends the slot's contents to the queue.
+    ///
+    /// # Safety
+    ///
+    /// The slot must have been fully initialized through the guard.
+    pub unsafe fn commit(self) {
+        let len = self.q.len() + 1;
+        self.q.set_len(len);
+        self.q.stats.pushed(1, len);
+    }
+
+    pub fn write(mut self, item: T) {
+        *self = MaybeUninit::new(item);
+        unsafe { self.commit() }
+    }
+}
+
+impl<'a, T, I: Idx> Deref for SlotGuard<'a, T, I> {
+    type Target = MaybeUninit<T>;
+    fn deref(&self) -> &MaybeUninit<T> {
+        unsafe { &*(self.q.data.ptr().add(self.slot) as *const MaybeUninit<T>) }
+    }
+}
+
+impl<'a, T, I: Idx> DerefMut for SlotGuard<'a, T, I> {
+    fn deref_mut(&mut self) -> &mut MaybeUninit<T> {
+        unsafe { &mut *(self.q.data.ptr().add(self.slot) as *mut MaybeUninit<T>) }
+    }
+}
+
+/// Writes append as many bytes as fit and never block; a full queue accepts
+/// nothing and returns `Ok(0)`.
+#[cfg(feature = "std")]
+impl<I: Idx> io::Write for BoundQueue<u8, I> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.push_slice(buf))
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        let mut n = 0;
+        for buf in bufs {
+            let w = self.push_slice(buf);
+            n += w;
+            if w < buf.len() {
+                break;
+            }
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads consume bytes from the front; an empty queue reads as `Ok(0)`.
+#[cfg(feature = "std")]
+impl<I: Idx> io::Read for BoundQueue<u8, I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.pop_into(buf))
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        let mut n = 0;
+        for buf in bufs {
+            let r = self.pop_into(buf);
+            n += r;
+            if r < buf.len() {
+                break;
+            }
+        }
+        Ok(n)
+    }
+}
+
+// Logical [0, kept) are survivors, [kept, processed) are holes and
+// [processed, len) are still to be examined. Dropping the guard slides the
+// unexamined run down over the holes, whether or not the closure panicked.
+struct RetainGuard<'a, T: 'a, I: Idx + 'a> {
+    q: &'a mut BoundQueue<T, I>,
+    len: usize,
+    processed: usize,
+    kept: usize,
+}
+
+impl<'a, T, I: Idx> Drop for RetainGuard<'a, T, I> {
+    fn drop(&mut self) {
+        let gap = self.processed - self.kept;
+        if gap > 0 {
+            let p = self.q.data.ptr();
+            for i in self.processed..self.len {
+                let (src, dst) = (self.q.phys(i), self.q.phys(i - gap));
+                unsafe { ptr::copy_nonoverlapping(p.add(src), p.add(dst), 1) };
+            }
+        }
+        self.q.set_len(self.len - gap);
+    }
+}
+
+// The iterators only ever see the two runs of live elements, so they never
+// form a reference to a slot that holds nothing.
+pub struct Iter<'a, T: 'a> {
+    front: slice::Iter<'a, T>,
+    back: slice::Iter<'a, T>,
+}
+
+pub struct IterMut<'a, T: 'a> {
+    front: slice::IterMut<'a, T>,
+    back: slice::IterMut<'a, T>,
+}
+
+pub struct IntoIter<T, I: Idx = usize>(BoundQueue<T, I>);
+
+pub struct Drain<'a, T: 'a, I: Idx + 'a = usize> {
+    q: &'a mut BoundQueue<T, I>,
+    // elements still to be removed
+    left: usize,
+}
+
+impl<'a, T, I: Idx> Iterator for Drain<'a, T, I> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        if self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        let head = self.q.phys(0);
+        let v = unsafe { self.q.read_at(head) };
+        self.q.advance_head(1);
+        self.q.stats.popped(1);
+        Some(v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<'a, T, I: Idx> ExactSizeIterator for Drain<'a, T, I> {}
+
+impl<'a, T, I: Idx> Drop for Drain<'a, T, I> {
+    fn drop(&mut self) {
+        // keeps going if dropping an element panics
+        struct Guard<'r, 'a: 'r, T: 'a, I: Idx + 'a>(&'r mut Drain<'a, T, I>);
+        impl<'r, 'a, T, I: Idx> Drop for Guard<'r, 'a, T, I> {
+            fn drop(&mut self) {
+                self.0.for_each(drop);
+            }
+        }
+        while let Some(item) = self.next() {
+            let guard = Guard(self);
+            drop(item);
+            mem::forget(guard);
+        }
+    }
+}
+
+impl<T, I: Idx> IntoIterator for BoundQueue<T, I> {
+    type Item = T;
+    type IntoIter = IntoIter<T, I>;
+    fn into_iter(self) -> IntoIter<T, I> {
+        IntoIter(self)
+    }
+}
+
+impl<'a, T, I: Idx> IntoIterator for &'a BoundQueue<T, I> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T, I: Idx> IntoIterator for &'a mut BoundQueue<T, I> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+/// Sizes the queue from the iterator's lower bound, as Vec does, and
+/// grows it if more items turn up than that. The upper bound is ignored:
+/// it can be far past what the iterator yields.
+impl<T> FromIterator<T> for BoundQueue<T> {
+    fn from_iter<It: IntoIterator<Item = T>>(iter: It) -> Self {
+        let iter = iter.into_iter();
+        let mut q = BoundQueue::new(iter.size_hint().0);
+        for item in iter {
+            q.reserve(1);
+            Queue::push(&mut q, item);
+        }
+        q
+    }
+}
+
+/// A full queue of the Vec's elements, front first, moved with one memcpy.
+impl<T> From<Vec<T>> for BoundQueue<T> {
+    fn from(mut v: Vec<T>) -> Self {
+        let len = v.len();
+        let mut q = BoundQueue::new(len);
+        unsafe {
+            ptr::copy_nonoverlapping(v.as_ptr(), q.data.ptr(), len);
+            v.set_len(0);
+        }
+        q.set_len(len);
+        q.stats.saw(len);
+        q
+    }
+}
+
+impl<T> From<VecDeque<T>> for BoundQueue<T> {
+    fn from(d: VecDeque<T>) -> Self {
+        BoundQueue::from(Vec::from(d))
+    }
+}
+
+impl<T, I: Idx> From<BoundQueue<T, I>> for Vec<T> {
+    fn from(q: BoundQueue<T, I>) -> Self {
+        q.into_vec()
+    }
+}
+
+impl<T, I: Idx> From<BoundQueue<T, I>> for VecDeque<T> {
+    fn from(q: BoundQueue<T, I>) -> Self {
+        VecDeque::from(q.into_vec())
+    }
+}
+
+/// Pushes items until the queue is full. Nothing is pulled from the
+/// iterator after that, so the rest is left to the caller.
+impl<T, I: Idx> Extend<T> for BoundQueue<T, I> {
+    fn extend<It: IntoIterator<Item = T>>(&mut self, iter: It) {
+        let free = self.usable() - self.len();
+        for item in iter.into_iter().take(free) {
+            Queue::push(self, item);
+        }
+    }
+}
+
+impl<T, I: Idx> Iterator for IntoIter<T, I> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.0.len();
+        (len, Some(len))
+    }
+}
+
+impl<T, I: Idx> DoubleEndedIterator for IntoIter<T, I> {
+    fn next_back(&mut self) -> Option<T> {
+        let len = self.0.len().checked_sub(1)?;
+        let slot = self.0.phys(len);
+        let v = unsafe { self.0.read_at(slot) };
+        self.0.set_len(len);
+        Some(v)
+    }
+}
+
+impl<T, I: Idx> ExactSizeIterator for IntoIter<T, I> {}
+
+/// Copies into a buffer of the same capacity, alignment and kind, starting
+/// unwrapped at slot 0. If a `clone` panics, the copies made so far are
+/// dropped.
+impl<T: Clone, I: Idx> Clone for BoundQueue<T, I> {
+    fn clone(&self) -> Self {
+        let data = RawBuf::with_capacity_aligned(self.data.cap(), self.data.align());
+        let mut q = BoundQueue::from_buf(data, self.mask);
+        q.shrink = self.shrink;
+        for (i, item) in self.iter().enumerate() {
+            unsafe {
+                q.write_at(i, item.clone());
+            }
+            q.set_len(i + 1);
+        }
+        q.stats.saw(self.len());
+        q
+    }
+}
+
+/// Lists the elements front to back.
+impl<T: fmt::Debug, I: Idx> fmt::Debug for BoundQueue<T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Queues are equal when they hold equal elements in the same order;
+/// capacity and where the elements sit in the buffer don't matter.
+impl<T: PartialEq, I: Idx> PartialEq for BoundQueue<T, I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq, I: Idx> Eq for BoundQueue<T, I> {}
+
+impl<T: Hash, I: Idx> Hash for BoundQueue<T, I> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for item in self.iter() {
+            item.hash(state);
+        }
+    }
+}
+
+impl<T, I: Idx> Index<usize> for BoundQueue<T, I> {
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &T {
+        let len = self.len();
+        self.get(idx).unwrap_or_else(|| out_of_bounds(idx, len))
+    }
+}
+
+impl<T, I: Idx> IndexMut<usize> for BoundQueue<T, I> {
+    fn index_mut(&mut self, idx: usize) -> &mut T {
+        let len = self.len();
+        self.get_mut(idx).unwrap_or_else(|| out_of_bounds(idx, len))
+    }
+}
+
+#[cold]
+fn out_of_bounds(idx: usize, len: usize) -> ! {
+    panic!("index {} out of bounds for a queue of length {}", idx, len)
+}
+
+impl<T, I: Idx> Drop for BoundQueue<T, I> {
+    fn drop(&mut self) {
+        trace!(debug, cap = self.usable(), len = self.len(), "bound queue dropped");
+        unsafe {
+            self.drop_elements();
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.front.next().or_else(|| self.back.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.front.len() + self.back.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.back.next_back().or_else(|| self.front.next_back())
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.front.next().or_else(|| self.back.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.front.len() + self.back.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.back.next_back().or_else(|| self.front.next_back())
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl<T: Clone, I: Idx> BoundQueue<T, I> {
+        fn as_vec(&self) -> Vec<T> {
+            self.iter().cloned().collect()
+        }
+    }
+
+    impl<T> BoundQueue<T> {
+        // a with_pow2_capacity queue whose counters wrap past usize::MAX
+        // after `pushes` pushes
+        fn near_wrap(size: usize, pushes: usize) -> Self {
+            let mut q = BoundQueue::with_pow2_capacity(size);
+            q.head = 0usize.wrapping_sub(pushes);
+            q.tail = q.head;
+            q
+        }
+    }
+
+    #[test]
+    fn peek_n() {
+        let mut q = BoundQueue::new(6);
+        assert_eq!(q.peek_n(3), (&[][..], &[][..]));
+        for i in 0..4 {
+            q.push(i);
+        }
+        assert_eq!(q.peek_n(0), (&[][..], &[][..]));
+        assert_eq!(q.peek_n(2), (&[0, 1][..], &[][..]));
+        assert_eq!(q.peek_n(10), (&[0, 1, 2, 3][..], &[][..]));
+
+        // head at slot 5 of 7: the front straddles the end of the buffer
+        for _ in 0..4 {
+            q.pop();
+        }
+        q.push(-1);
+        q.pop();
+        for i in 0..6 {
+            q.push(i);
+        }
+        let (a, b) = q.peek_n(16);
+        assert_eq!((a, b), (&[0, 1][..], &[2, 3, 4, 5][..]));
+        assert_eq!(q.peek_n(2), (&[0, 1][..], &[][..]));
+        assert_eq!(q.peek_n(3), (&[0, 1][..], &[2][..]));
+        // still borrowed while reading the queue
+        assert_eq!(q.get(0), a.first());
+        for i in 0..6 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(q.pop(), None);
+
+        let mut q = BoundQueue::new_pow2(2);
+        for i in 0..7 {
+            q.push(i);
+            if i < 3 {
+                q.pop();
+            }
+        }
+        assert_eq!(q.peek_n(4), (&[3][..], &[4, 5, 6][..]));
+    }
+
+    #[test]
+    fn is_empty() {
+        let q = BoundQueue::<i32>::new(10);
+        assert!(q.is_empty());
+    }
+
+    // pushes and pops in uneven runs through the trait, checking len after
+    // every step against a counter; `cap` is None for an unbounded queue
+    fn churn_len<Q: Queue<usize>>(mut q: Q, cap: Option<usize>) {
+        let mut len = 0;
+        for step in 0..300 {
+            if step % 7 < 4 || (step / 40) % 2 == 0 {
+                q.push(step);
+                len = cap.map_or(len + 1, |cap| cmp::min(len + 1, cap));
+            } else if q.pop().is_some() {
+                len -= 1;
+            }
+            assert_eq!(q.len(), len);
+            assert_eq!(q.is_empty(), len == 0);
+        }
+    }
+
+    #[test]
+    fn peek_and_back() {
+        let mut q = BoundQueue::new(3);
+        assert_eq!((q.peek(), q.back()), (None, None));
+        assert!(q.peek_mut().is_none() && q.back_mut().is_none());
+        q.push(1);
+        assert_eq!((q.peek(), q.back()), (Some(&1), Some(&1)));
+        for i in 2..7 {
+            q.push(i);
+            q.pop();
+        }
+        // head and tail have wrapped around the buffer
+        assert_eq!((q.peek(), q.back()), (Some(&6), Some(&6)));
+        q.push(7);
+        q.push(8);
+        *q.peek_mut().unwrap() *= 10;
+        *q.back_mut().unwrap() += 100;
+        assert_eq!(q.as_vec(), vec![60, 7, 108]);
+        assert_eq!(q.pop(), Some(60));
+        assert_eq!(q.peek(), Some(&7));
+    }
+
+    #[test]
+    fn trait_len() {
+        churn_len(BoundQueue::new(5), Some(5));
+        churn_len(BoundQueue::new_pow2(3), Some(8));
+        churn_len(BoundQueue::<_, u8>::try_new_indexed(20).unwrap(), Some(20));
+        churn_len(UnboundQueue::new(), None);
+        assert_eq!(BoundQueue::<u8>::new(5).cap(), 5);
+        assert_eq!(BoundQueue::<u8>::new_pow2(3).cap(), 8);
+    }
+
+    #[test]
+    fn len_full_wraparound() {
+        for size in 1..6 {
+            let mut q = BoundQueue::new(size);
+            let mut len = 0;
+            let mut next = 0;
+            // fill and drain in uneven steps so every head/tail pair comes up
+            for step in 0..12 * size {
+                let push = (step / size) % 2 == 0 || step % 3 == 0;
+                if push {
+                    q.push(next);
+                    next += 1;
+                    len = cmp::min(len + 1, size);
+                } else if q.pop().is_some() {
+                    len -= 1;
+                }
+                assert_eq!(q.len(), len);
+                assert_eq!(q.is_empty(), len == 0);
+                assert_eq!(q.is_full(), len == size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut q = BoundQueue::<i32>::new(10);
+        for i in 1..=10 {
+            q.push(i);
+            assert_eq!(q.pop(), Some(i));
+        }
+    }
+    #[test]
+    fn into_iter() {
+        let mut q = BoundQueue::<i32>::new(10);
+        q.push(1);
+        q.push(2);
+        q.push(3);
+
+        let mut iter = q.into_iter();
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter() {
+        let mut q = BoundQueue::<String>::new(10);
+        q.push(1.to_string());
+        q.push(2.to_string());
+        q.push(3.to_string());
+
+        let mut iter = q.iter();
+        assert_eq!(iter.next(), Some(&"1".to_string()));
+        assert_eq!(iter.next(), Some(&"2".to_string()));
+        assert_eq!(iter.next(), Some(&"3".to_string()));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut q = BoundQueue::<i32>::new(10);
+        q.push(1);
+        q.push(2);
+        q.push(3);
+
+        let mut iter = q.iter_mut();
+        assert_eq!(iter.next(), Some(&mut 1));
+        assert_eq!(iter.next(), Some(&mut 2));
         assert_eq!(iter.next(), Some(&mut 3));
         assert_eq!(iter.next(), None);
     }
+
+    #[test]
+    fn iter_both_ends() {
+        let mut q = BoundQueue::new(5);
+        for i in 0..8 {
+            q.push(i);
+            if i < 3 {
+                q.pop();
+            }
+        }
+        // 3..8, wrapped around the end of the buffer
+        let mut iter = q.iter();
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.next_back(), Some(&7));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.rev().cloned().collect::<Vec<_>>(), [6, 5, 4]);
+
+        for x in &mut q {
+            *x *= 10;
+        }
+        let mut iter = q.iter_mut();
+        *iter.next_back().unwrap() += 1;
+        assert_eq!(iter.size_hint(), (4, Some(4)));
+        assert_eq!(
+            (&q).into_iter().collect::<Vec<_>>(),
+            [&30, &40, &50, &60, &71]
+        );
+
+        let mut iter = q.into_iter();
+        assert_eq!(iter.next_back(), Some(71));
+        assert_eq!(iter.next(), Some(30));
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.rev().collect::<Vec<_>>(), [60, 50, 40]);
+    }
+
+    fn hash_of<H: Hash>(x: &H) -> u64 {
+        let mut h = ::std::collections::hash_map::DefaultHasher::new();
+        x.hash(&mut h);
+        h.finish()
+    }
+
+    #[test]
+    fn std_traits() {
+        let mut q = BoundQueue::new(4);
+        for i in 0..6 {
+            q.push(i.to_string());
+            if i < 3 {
+                q.pop();
+            }
+        }
+        // elements wrap around the end of the buffer
+        assert_eq!(format!("{:?}", q), r#"["3", "4", "5"]"#);
+
+        let c = q.clone();
+        assert_eq!(c.cap(), 4);
+        assert_eq!(c, q);
+        assert_eq!(hash_of(&c), hash_of(&q));
+
+        let mut other = BoundQueue::new_pow2(3);
+        for i in 3..6 {
+            other.push(i.to_string());
+        }
+        assert_eq!(other, c);
+        assert_eq!(hash_of(&other), hash_of(&c));
+        other.pop();
+        assert_ne!(other, c);
+        drop(q);
+        assert_eq!(c.as_vec(), ["3", "4", "5"]);
+    }
+
+    #[test]
+    fn clone_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        struct Bomb<'a>(&'a Cell<usize>, bool);
+        impl<'a> Clone for Bomb<'a> {
+            fn clone(&self) -> Self {
+                assert!(!self.1, "boom");
+                Bomb(self.0, false)
+            }
+        }
+        impl<'a> Drop for Bomb<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let mut q = BoundQueue::new(4);
+        for i in 0..4 {
+            q.push(Bomb(&drops, i == 2));
+        }
+        let r = catch_unwind(AssertUnwindSafe(|| q.clone()));
+        assert!(r.is_err());
+        // the two copies made before the panic, originals untouched
+        assert_eq!(drops.get(), 2);
+        assert_eq!(q.len(), 4);
+    }
+
+    #[test]
+    fn from_iter_extend() {
+        let q: BoundQueue<_> = (0..6).collect();
+        assert_eq!(q.cap(), 6);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), [0, 1, 2, 3, 4, 5]);
+
+        // a lower bound of 0, so the queue grows as items arrive
+        let q: BoundQueue<_> = (0..100).filter(|x| x % 3 == 0).collect();
+        assert_eq!(q.len(), 34);
+        assert!(q.iter().cloned().eq((0..100).step_by(3)));
+
+        // an upper bound far past what can be allocated isn't a problem
+        let huge = (0..1u64 << 40).take_while(|x| *x < 3);
+        assert_eq!(huge.size_hint(), (0, Some(1 << 40)));
+        let q: BoundQueue<_> = huge.collect();
+        assert_eq!(q.as_vec(), [0, 1, 2]);
+        let q: BoundQueue<u8> = (0..usize::MAX).take_while(|_| false).map(|_| 1).collect();
+        assert!(q.is_empty());
+
+        let mut q = BoundQueue::new(4);
+        q.push(-1);
+        let mut src = 0..10;
+        q.extend(&mut src);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), [-1, 0, 1, 2]);
+        // extend stopped pulling once the queue was full
+        assert_eq!(src.next(), Some(3));
+        q.extend(src);
+        assert_eq!(q.len(), 4);
+    }
+
+    use std::cell::Cell;
+    use std::collections::VecDeque;
+
+    struct Counted<'a>(&'a Cell<usize>);
+
+    impl<'a> Drop for Counted<'a> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn drop_wrapped() {
+        let drops = Cell::new(0);
+        {
+            let mut q = BoundQueue::new(10);
+            for _ in 0..8 {
+                q.push(Counted(&drops));
+            }
+            for _ in 0..6 {
+                q.pop();
+            }
+            for _ in 0..7 {
+                q.push(Counted(&drops));
+            }
+            assert_eq!(drops.get(), 6);
+        }
+        assert_eq!(drops.get(), 15);
+
+        drops.set(0);
+        {
+            let mut q = BoundQueue::new_pow2(3);
+            for _ in 0..6 {
+                q.push(Counted(&drops));
+            }
+            for _ in 0..5 {
+                q.pop();
+            }
+            for _ in 0..7 {
+                q.push(Counted(&drops));
+            }
+        }
+        assert_eq!(drops.get(), 13);
+    }
+
+    // every head offset and length of a small ring, both kinds, with
+    // elements that own heap memory so Miri catches stray reads
+    #[test]
+    fn iter_every_offset() {
+        for &pow2 in &[false, true] {
+            for offset in 0..5 {
+                for len in 0..5 {
+                    let mut q = if pow2 {
+                        BoundQueue::new_pow2(2)
+                    } else {
+                        BoundQueue::new(4)
+                    };
+                    // fill, drain, refill across the wrap point
+                    for i in 0..offset {
+                        q.push(i.to_string());
+                        q.pop();
+                    }
+                    for i in 0..len {
+                        q.push(i.to_string());
+                    }
+                    let want: Vec<_> = (0..len).map(|i| i.to_string()).collect();
+                    assert_eq!(q.iter().cloned().collect::<Vec<_>>(), want);
+                    let rev: Vec<_> = q.iter().rev().cloned().collect();
+                    assert!(rev.iter().eq(want.iter().rev()));
+                    assert_eq!(q.iter_mut().len(), len);
+                    for s in q.iter_mut().rev() {
+                        s.push('!');
+                    }
+                    assert!(q.iter().all(|s| s.ends_with('!')));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn drop_every_offset() {
+        let drops = Cell::new(0);
+        for offset in 0..6 {
+            for len in 0..6 {
+                drops.set(0);
+                {
+                    let mut q = BoundQueue::new(5);
+                    for _ in 0..offset {
+                        q.push(Counted(&drops));
+                        q.pop();
+                    }
+                    for _ in 0..len {
+                        q.push(Counted(&drops));
+                    }
+                }
+                assert_eq!(drops.get(), offset + len);
+            }
+        }
+    }
+
+    // counts constructions as well as drops, so a leak and a double drop
+    // both show up as a nonzero balance
+    struct Tracked<'a> {
+        live: &'a Cell<isize>,
+        _s: String,
+    }
+
+    impl<'a> Tracked<'a> {
+        fn new(live: &'a Cell<isize>, i: usize) -> Self {
+            live.set(live.get() + 1);
+            Tracked {
+                live,
+                _s: i.to_string(),
+            }
+        }
+    }
+
+    impl<'a> Drop for Tracked<'a> {
+        fn drop(&mut self) {
+            self.live.set(self.live.get() - 1);
+        }
+    }
+
+    #[test]
+    fn drop_after_wrap_cycles() {
+        let live = Cell::new(0);
+        for &pow2 in &[false, true] {
+            for cycles in 0..4 {
+                for last in 0..=4 {
+                    let mut q = if pow2 {
+                        BoundQueue::new_pow2(2)
+                    } else {
+                        BoundQueue::new(4)
+                    };
+                    // fill, drain most of it, fill again, so head and tail
+                    // go round the buffer several times
+                    for c in 0..cycles {
+                        for i in 0..4 {
+                            q.push(Tracked::new(&live, i));
+                        }
+                        for _ in 0..3 - c % 2 {
+                            q.pop();
+                        }
+                    }
+                    for i in 0..last {
+                        q.push(Tracked::new(&live, i));
+                    }
+                    assert_eq!(live.get(), q.len() as isize);
+                    drop(q);
+                    assert_eq!(live.get(), 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn drain() {
+        let mut q = BoundQueue::new(5);
+        for i in 0..7 {
+            q.push(i.to_string());
+            if i < 3 {
+                q.pop();
+            }
+        }
+        let buf = q.data.ptr();
+        let mut d = q.drain_first(3);
+        assert_eq!(d.len(), 3);
+        assert_eq!(d.next().as_deref(), Some("3"));
+        drop(d);
+        assert_eq!(q.as_vec(), ["6"]);
+        q.extend((7..10).map(|i| i.to_string()));
+        assert_eq!(q.drain().collect::<Vec<_>>(), ["6", "7", "8", "9"]);
+        assert!(q.is_empty());
+        assert_eq!(q.data.ptr(), buf);
+        assert_eq!(q.drain_first(2).count(), 0);
+
+        // forgetting the iterator leaves the rest queued
+        q.extend((0..4).map(|i| i.to_string()));
+        let mut d = q.drain();
+        d.next();
+        mem::forget(d);
+        assert_eq!(q.as_vec(), ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn drain_drop_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        struct Bomb<'a>(&'a Cell<usize>, bool);
+        impl<'a> Drop for Bomb<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+                assert!(!self.1, "boom");
+            }
+        }
+
+        let drops = Cell::new(0);
+        let mut q = BoundQueue::new(6);
+        for i in 0..6 {
+            q.push(Bomb(&drops, i == 2));
+        }
+        let r = catch_unwind(AssertUnwindSafe(|| {
+            let mut d = q.drain_first(5);
+            d.next();
+        }));
+        assert!(r.is_err());
+        // all five were dropped and the sixth is still queued
+        assert_eq!(drops.get(), 5);
+        assert_eq!(q.len(), 1);
+        drop(q);
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[test]
+    fn conversions() {
+        for &pow2 in &[false, true] {
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(3)
+            } else {
+                BoundQueue::new(8)
+            };
+            for i in 0..13 {
+                q.push(i.to_string());
+                if i % 2 == 0 {
+                    q.pop();
+                }
+            }
+            let (a, b) = q.as_slices();
+            assert!(!a.is_empty() && !b.is_empty());
+            let want: Vec<_> = (0..13).skip(7).map(|i| i.to_string()).collect();
+            let front = q.peek().unwrap().as_ptr();
+
+            let v = Vec::from(q);
+            assert_eq!(v, want);
+            // moved, not cloned
+            assert_eq!(v[0].as_ptr(), front);
+            let d = VecDeque::from(BoundQueue::from(v));
+            assert!(d.iter().eq(&want));
+            assert_eq!(d[0].as_ptr(), front);
+            let q = BoundQueue::from(d);
+            assert_eq!((q.cap(), q.len()), (6, 6));
+            let u = q.into_unbound();
+            assert!(u.iter().eq(&want));
+            assert_eq!(u.peek().unwrap().as_ptr(), front);
+        }
+        assert!(BoundQueue::<String>::from(vec![]).is_full());
+        assert_eq!(BoundQueue::<()>::from(vec![(); 3]).into_vec().len(), 3);
+    }
+
+    #[test]
+    fn rotate_swap() {
+        let mut q = BoundQueue::new(6);
+        q.extend(vec!["a", "b", "c", "d", "e", "f"]);
+        q.rotate_left(2);
+        assert_eq!(q.as_vec(), ["c", "d", "e", "f", "a", "b"]);
+        q.rotate_left(5);
+        assert_eq!(q.as_vec(), ["b", "c", "d", "e", "f", "a"]);
+        q.rotate_right(1);
+        q.rotate_left(0);
+        q.rotate_right(6);
+        assert_eq!(q.as_vec(), ["a", "b", "c", "d", "e", "f"]);
+        q.swap(0, 5);
+        q.swap(2, 2);
+        assert_eq!(q.as_vec(), ["f", "b", "c", "d", "e", "a"]);
+
+        // full with no spare slot: the freed slot is the one written
+        let mut q = BoundQueue::new_pow2(2);
+        q.extend(0..4);
+        q.rotate_right(1);
+        assert_eq!(q.as_vec(), [3, 0, 1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "rotate by 3 in a queue of length 2")]
+    fn rotate_past_len() {
+        let mut q = BoundQueue::from_slice(&[1, 2]);
+        q.rotate_left(3);
+    }
+
+    #[test]
+    #[should_panic(expected = "index 2 out of bounds for a queue of length 2")]
+    fn swap_past_len() {
+        let mut q = BoundQueue::from_slice(&[1, 2]);
+        q.swap(0, 2);
+    }
+
+    #[test]
+    fn drop_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        struct Bomb<'a>(&'a Cell<usize>, bool);
+        impl<'a> Drop for Bomb<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+                assert!(!self.1, "boom");
+            }
+        }
+
+        for &pow2 in &[false, true] {
+            let drops = Cell::new(0);
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(3)
+            } else {
+                BoundQueue::new(7)
+            };
+            for i in 0..6 {
+                q.push(Bomb(&drops, false));
+                q.push(Bomb(&drops, i == 3));
+                drop(q.pop());
+            }
+            // the bomb is in the run before the wrap, with more after it
+            assert_eq!(q.as_slices().0.len(), 2);
+            assert_eq!(drops.get(), 6);
+            let r = catch_unwind(AssertUnwindSafe(|| drop(q)));
+            assert!(r.is_err());
+            assert_eq!(drops.get(), 12);
+        }
+    }
+
+    #[test]
+    fn push_slice_pop_into() {
+        for &pow2 in &[false, true] {
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(3)
+            } else {
+                BoundQueue::new(8)
+            };
+            assert_eq!(q.push_slice(&[]), 0);
+            assert_eq!(q.pop_into(&mut []), 0);
+            assert_eq!(q.pop_into(&mut [0; 4]), 0);
+            // exact fit
+            assert_eq!(q.push_slice(&[0, 1, 2, 3, 4, 5, 6, 7]), 8);
+            assert_eq!(q.push_slice(&[8]), 0);
+            let mut buf = [0u8; 5];
+            assert_eq!(q.pop_into(&mut buf), 5);
+            assert_eq!(buf, [0, 1, 2, 3, 4]);
+            // more than the free space; the copy wraps past the end
+            assert_eq!(q.push_slice(&[8, 9, 10, 11, 12, 13, 14]), 5);
+            assert!(q.is_full());
+            assert_eq!(q.as_vec(), [5, 6, 7, 8, 9, 10, 11, 12]);
+            // the read wraps too, and the buffer is bigger than the queue
+            let mut buf = [0u8; 10];
+            assert_eq!(q.pop_into(&mut buf), 8);
+            assert_eq!(buf[..8], [5, 6, 7, 8, 9, 10, 11, 12]);
+            assert!(q.is_empty());
+            assert_eq!(q.push_slice(&[1, 2]), 2);
+            assert_eq!(q.pop_into(&mut buf[..2]), 2);
+            assert_eq!(buf[..2], [1, 2]);
+        }
+    }
+
+    fn send_sync<T: Send + Sync>() {}
+
+    // these only compile if the types are covariant in T
+    #[allow(dead_code)]
+    fn shorten<'a>(q: BoundQueue<&'static str>) -> BoundQueue<&'a str> {
+        q
+    }
+
+    #[allow(dead_code)]
+    fn shorten_iter<'i, 'a>(it: Iter<'i, &'static str>) -> Iter<'i, &'a str> {
+        it
+    }
+
+    #[test]
+    fn auto_traits() {
+        send_sync::<BoundQueue<i32>>();
+        send_sync::<BoundQueue<i32, u8>>();
+        send_sync::<Iter<'_, i32>>();
+        send_sync::<IterMut<'_, i32>>();
+        send_sync::<IntoIter<i32>>();
+        send_sync::<Drain<'_, i32>>();
+
+        let mut q = BoundQueue::new(100);
+        q.extend((0..100).map(|i| i.to_string()));
+        let q = ::std::thread::spawn(move || {
+            q.pop();
+            q
+        })
+        .join()
+        .unwrap();
+        assert_eq!(q.len(), 99);
+        assert_eq!(q.peek().map(|s| &s[..]), Some("1"));
+    }
+
+    #[test]
+    fn truncate_clear() {
+        let drops = Cell::new(0);
+        for &pow2 in &[false, true] {
+            for offset in 0..8 {
+                for keep in 0..8 {
+                    drops.set(0);
+                    let mut q = if pow2 {
+                        BoundQueue::new_pow2(3)
+                    } else {
+                        BoundQueue::new(8)
+                    };
+                    for _ in 0..offset {
+                        q.push(Counted(&drops));
+                        q.pop();
+                    }
+                    q.extend((0..6).map(|_| Counted(&drops)));
+                    q.truncate(keep);
+                    assert_eq!(q.len(), cmp::min(keep, 6));
+                    let kept = q.len();
+                    assert_eq!(drops.get(), offset + 6 - kept);
+                    // the freed slots take new elements; extend stops once full
+                    q.extend((0..8).map(|_| Counted(&drops)));
+                    assert!(q.is_full());
+                    let buf = q.data.ptr();
+                    q.clear();
+                    assert!(q.is_empty());
+                    assert_eq!(q.data.ptr(), buf);
+                    assert_eq!(drops.get(), offset + 6 + 8 - kept);
+                }
+            }
+        }
+
+        let mut q = BoundQueue::new(5);
+        for i in 0..8 {
+            q.push(i);
+            if i < 3 {
+                q.pop();
+            }
+        }
+        q.truncate(3);
+        q.push(9);
+        assert_eq!(q.as_vec(), [3, 4, 5, 9]);
+    }
+
+    #[test]
+    fn from_vec_in_place() {
+        let mut v = Vec::with_capacity(8);
+        for i in 0..5 {
+            v.push(i.to_string());
+        }
+        let addrs: Vec<*const String> = v.iter().map(|s| s as *const _).collect();
+
+        let mut q = BoundQueue::from_vec_in_place(v);
+        assert_eq!(q.cap(), 7);
+        let moved: Vec<*const String> = q.iter().map(|s| s as *const _).collect();
+        assert_eq!(addrs, moved);
+
+        q.push("5".to_string());
+        q.push("6".to_string());
+        q.push("7".to_string());
+        assert_eq!(q.pop(), Some("0".to_string()));
+        q.push("8".to_string());
+        let v: Vec<_> = q.into_iter().collect();
+        assert_eq!(v, vec!["1", "2", "3", "4", "5", "6", "8"]);
+    }
+
+    #[test]
+    fn from_vec_in_place_no_spare() {
+        let v = vec![1, 2, 3];
+        let mut q = BoundQueue::from_vec_in_place(v);
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn index() {
+        for &pow2 in &[false, true] {
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(2)
+            } else {
+                BoundQueue::new(4)
+            };
+            for i in 0..3 {
+                q.push(i);
+                q.pop();
+            }
+            for i in 0..3 {
+                q.push(i * 10);
+            }
+            assert_eq!(q.get(2), Some(&20));
+            // slot behind tail exists, but holds nothing
+            assert_eq!(q.get(3), None);
+            assert_eq!(q.get_mut(3), None);
+            *q.get_mut(1).unwrap() += 1;
+            q[2] += 2;
+            assert_eq!((q[0], q[1], q[2]), (0, 11, 22));
+            q.pop();
+            assert_eq!(q[0], 11);
+            assert_eq!(q.get(2), None);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "index 2 out of bounds for a queue of length 2")]
+    fn index_past_len() {
+        let mut q = BoundQueue::new(4);
+        q.push(1);
+        q.push(2);
+        q[2] = 3;
+    }
+
+    #[test]
+    fn slices() {
+        for &pow2 in &[false, true] {
+            for offset in 0..8 {
+                for len in 0..9 {
+                    let mut q = if pow2 {
+                        BoundQueue::new_pow2(3)
+                    } else {
+                        BoundQueue::new(8)
+                    };
+                    for i in 0..offset {
+                        q.push(i);
+                        q.pop();
+                    }
+                    q.extend(0..len);
+                    let want: Vec<_> = q.iter().cloned().collect();
+                    let (a, b) = q.as_slices();
+                    assert_eq!([a, b].concat(), want);
+
+                    let (a, b) = q.as_mut_slices();
+                    for x in a.iter_mut().chain(b) {
+                        *x += 1;
+                    }
+                    let want: Vec<_> = want.iter().map(|x| x + 1).collect();
+                    assert_eq!(q.make_contiguous(), &want[..]);
+                    assert_eq!(q.as_slices(), (&want[..], &[][..]));
+                    q.pop();
+                    q.push(100);
+                    assert_eq!(q.back(), Some(&100));
+                }
+            }
+        }
+    }
+
+    // how many of 0..n went in before the first rejection
+    fn fill<Q: TryQueue<u32>>(q: &mut Q, n: u32) -> u32 {
+        for i in 0..n {
+            if let Err(e) = q.try_push(i) {
+                assert_eq!(e, PushError::Full(i));
+                return i;
+            }
+        }
+        n
+    }
+
+    #[test]
+    fn try_queue() {
+        assert_eq!(fill(&mut BoundQueue::new(5), 10), 5);
+        assert_eq!(fill(&mut BoundQueue::new_pow2(3), 10), 8);
+        assert_eq!(fill(&mut UnboundQueue::new(), 10), 10);
+
+        let mut q = BoundQueue::new(2);
+        fill(&mut q, 2);
+        q.pop();
+        assert_eq!(TryQueue::try_push(&mut q, 7), Ok(()));
+        assert_eq!(q.as_vec(), [1, 7]);
+    }
+
+    #[test]
+    fn deque_ends() {
+        for &pow2 in &[false, true] {
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(2)
+            } else {
+                BoundQueue::new(4)
+            };
+            assert_eq!(q.pop_back(), None);
+            // head starts at slot 0, so this wraps it backwards
+            q.push_front(2);
+            q.push_front(1);
+            q.push(3);
+            q.push_front(0);
+            assert!(q.is_full());
+            q.push_front(-1);
+            assert_eq!(q.len(), 4);
+            assert_eq!(q.iter().cloned().collect::<Vec<_>>(), [0, 1, 2, 3]);
+            assert!(q.iter().rev().eq(&[3, 2, 1, 0]));
+            assert_eq!((q[0], q[3]), (0, 3));
+            assert_eq!(q.pop_back(), Some(3));
+            assert_eq!(q.back(), Some(&2));
+            assert_eq!(q.pop(), Some(0));
+            assert_eq!(q.pop_back(), Some(2));
+            assert_eq!(q.pop_back(), Some(1));
+            assert_eq!(q.pop_back(), None);
+            assert!(q.is_empty());
+            q.push(5);
+            assert_eq!(q.peek(), Some(&5));
+        }
+    }
+
+    #[test]
+    fn remove_at() {
+        // a queue holding 0..len as strings, with head `offset` slots in
+        let make = |pow2, offset, len| {
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(3)
+            } else {
+                BoundQueue::new(8)
+            };
+            for _ in 0..offset {
+                q.push(String::new());
+                q.pop();
+            }
+            q.extend((0..len).map(|i: usize| i.to_string()));
+            let model: VecDeque<_> = q.iter().cloned().collect();
+            (q, model)
+        };
+        for &pow2 in &[false, true] {
+            for offset in 0..8 {
+                for len in 0..=8 {
+                    for idx in 0..=len {
+                        let (mut q, mut model) = make(pow2, offset, len);
+                        assert_eq!(q.remove(idx), model.remove(idx));
+                        assert_eq!(q.len(), model.len());
+                        assert!(q.iter().eq(model.iter()));
+                        assert!(q.iter().rev().eq(model.iter().rev()));
+                        if idx < len {
+                            q.push("x".to_string());
+                            assert_eq!(q.back().map(|s| &s[..]), Some("x"));
+                        }
+
+                        let (mut q, mut model) = make(pow2, offset, len);
+                        assert_eq!(q.swap_remove_front(idx), model.swap_remove_front(idx));
+                        assert!(q.iter().eq(model.iter()));
+
+                        let (mut q, mut model) = make(pow2, offset, len);
+                        assert_eq!(q.swap_remove_back(idx), model.swap_remove_back(idx));
+                        assert!(q.iter().eq(model.iter()));
+                        assert_eq!(q.is_empty(), model.is_empty());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn contains_retain() {
+        let mut q = BoundQueue::new(6);
+        for i in 0..4 {
+            q.push(i);
+            q.pop();
+        }
+        // wraps: 4 and 5 at the end of the buffer, the rest from slot 0
+        for i in 4..10 {
+            q.push(i);
+        }
+        assert!(q.contains(&9) && q.contains(&4) && !q.contains(&3));
+        assert_eq!(q.position(|&x| x > 6), Some(3));
+        assert_eq!(q.position(|&x| x > 9), None);
+
+        q.retain(|&x| x != 9);
+        assert_eq!(q.as_vec(), [4, 5, 6, 7, 8]);
+        q.push(10);
+        assert_eq!(q.back(), Some(&10));
+        q.retain(|&x| x % 2 == 0);
+        assert_eq!(q.as_vec(), [4, 6, 8, 10]);
+        q.retain(|_| false);
+        assert!(q.is_empty());
+        q.push(11);
+        assert_eq!(q.pop(), Some(11));
+    }
+
+    #[test]
+    fn retain_mut_wrapped() {
+        let drops = Cell::new(0);
+        let mut q = BoundQueue::new(8);
+        for _ in 0..6 {
+            q.push(Counted(&drops));
+        }
+        for _ in 0..6 {
+            q.pop();
+        }
+        drops.set(0);
+        // live run now starts at slot 6 of 9 and wraps
+        let mut n = 0;
+        for _ in 0..8 {
+            q.push(Counted(&drops));
+        }
+        q.retain_mut(|_| {
+            n += 1;
+            n % 2 == 0
+        });
+        assert_eq!(drops.get(), 4);
+        assert_eq!(q.len(), 4);
+
+        let mut q = BoundQueue::new(8);
+        for i in 0..5 {
+            q.push(i);
+            q.pop();
+        }
+        for i in 0..8 {
+            q.push(i);
+        }
+        q.retain_mut(|x| {
+            *x *= 10;
+            *x % 30 != 0
+        });
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), vec![10, 20, 40, 50, 70]);
+        q.push(80);
+        assert_eq!(q.pop(), Some(10));
+        assert_eq!(q.iter().next_back(), Some(&80));
+    }
+
+    #[test]
+    fn retain_mut_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let drops = Cell::new(0);
+        {
+            let mut q = BoundQueue::new(8);
+            for _ in 0..3 {
+                q.push(Counted(&drops));
+                q.pop();
+            }
+            drops.set(0);
+            for _ in 0..8 {
+                q.push(Counted(&drops));
+            }
+            let mut n = 0;
+            let r = catch_unwind(AssertUnwindSafe(|| {
+                q.retain_mut(|_| {
+                    n += 1;
+                    if n == 5 {
+                        panic!("boom");
+                    }
+                    n % 2 == 0
+                })
+            }));
+            assert!(r.is_err());
+            assert_eq!(drops.get(), 2);
+            assert_eq!(q.len(), 6);
+        }
+        assert_eq!(drops.get(), 8);
+    }
+
+    #[test]
+    fn io_vectored() {
+        use std::io::{Read, Write};
+
+        let mut q = BoundQueue::<u8>::new(10);
+        q.write_all(b"abcdefg").unwrap();
+        let mut buf = [0; 6];
+        q.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"abcdef");
+
+        // free space now runs from slot 7 around to slot 4
+        let bufs = [IoSlice::new(b"hij"), IoSlice::new(b""), IoSlice::new(b"klmnop")];
+        assert_eq!(q.write_vectored(&bufs).unwrap(), 9);
+        assert_eq!(q.len(), 10);
+        assert_eq!(q.write(b"z").unwrap(), 0);
+
+        let (mut a, mut b, mut c) = ([0; 2], [0; 4], [0; 8]);
+        {
+            let mut bufs = [
+                IoSliceMut::new(&mut a),
+                IoSliceMut::new(&mut b),
+                IoSliceMut::new(&mut c),
+            ];
+            assert_eq!(q.read_vectored(&mut bufs).unwrap(), 10);
+        }
+        assert_eq!(&a, b"gh");
+        assert_eq!(&b, b"ijkl");
+        assert_eq!(&c[..4], b"mnop");
+        assert_eq!(q.read(&mut a).unwrap(), 0);
+    }
+
+    #[test]
+    fn io_vectored_partial() {
+        use std::io::Write;
+
+        let mut q = BoundQueue::<u8>::new(8);
+        q.write_all(b"12345").unwrap();
+        let bufs = [IoSlice::new(b"ab"), IoSlice::new(b"cd"), IoSlice::new(b"ef")];
+        assert_eq!(q.write_vectored(&bufs).unwrap(), 3);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), b"12345abc".to_vec());
+    }
+
+    #[test]
+    fn io_vectored_matches_sequential() {
+        use std::io::{Read, Write};
+
+        let mut a = BoundQueue::<u8>::new_pow2(4);
+        let mut b = BoundQueue::<u8>::new_pow2(4);
+        let mut out_a = vec![];
+        let mut out_b = vec![];
+        for round in 0..20u8 {
+            let x = [round; 3];
+            let y = [round + 100; 5];
+            let na = a.write_vectored(&[IoSlice::new(&x), IoSlice::new(&y)]).unwrap();
+            let mut nb = b.write(&x).unwrap();
+            if nb == x.len() {
+                nb += b.write(&y).unwrap();
+            }
+            assert_eq!(na, nb);
+            let mut buf = [0; 7];
+            let n = a.read(&mut buf).unwrap();
+            out_a.extend_from_slice(&buf[..n]);
+            let n = b.read(&mut buf).unwrap();
+            out_b.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(out_a, out_b);
+        assert_eq!(a.iter().collect::<Vec<_>>(), b.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn find() {
+        use std::io::{Read, Write};
+
+        let mut q = BoundQueue::<u8>::new(16);
+        q.write_all(b"0123456789ab").unwrap();
+        q.read_exact(&mut [0; 10]).unwrap();
+        // "abGET /" fills slots 10..17, the rest wraps to the front
+        q.write_all(b"GET /\r\n\r\nxy").unwrap();
+        assert_eq!(q.as_vec(), b"abGET /\r\n\r\nxy".to_vec());
+
+        assert_eq!(q.find(b"ab"), Some(0));
+        assert_eq!(q.find(b"bGE"), Some(1));
+        assert_eq!(q.find(b"/\r\n"), Some(6));
+        assert_eq!(q.find(b"\r\n\r\n"), Some(7));
+        assert_eq!(q.find(b"xy"), Some(11));
+        assert_eq!(q.find(b""), Some(0));
+        assert_eq!(q.find(b"yz"), None);
+        assert_eq!(q.find(b"abGET /\r\n\r\nxyz"), None);
+
+        assert!(q.starts_with(b"abG"));
+        assert!(q.starts_with(b""));
+        assert!(!q.starts_with(b"b"));
+        assert!(!q.starts_with(b"abGET /\r\n\r\nxyz"));
+    }
+
+    // hands out at most `step` bytes per call, cycling through 1..=step
+    struct Trickle<T> {
+        inner: T,
+        step: usize,
+        calls: usize,
+    }
+
+    impl<T: io::Read> io::Read for Trickle<T> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.calls += 1;
+            let n = cmp::min(buf.len(), self.calls % self.step + 1);
+            self.inner.read(&mut buf[..n])
+        }
+    }
+
+    impl<T: io::Write> io::Write for Trickle<T> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            let n = cmp::min(buf.len(), self.calls % self.step + 1);
+            self.inner.write(&buf[..n])
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_from_write_to() {
+        use std::io::Cursor;
+
+        let input: Vec<u8> = (0..5000u32).map(|i| (i * 7 % 251) as u8).collect();
+        for &(cap, step) in &[(7, 3), (16, 5), (100, 64)] {
+            let mut r = Trickle {
+                inner: Cursor::new(input.clone()),
+                step,
+                calls: 0,
+            };
+            let mut w = Trickle {
+                inner: Vec::new(),
+                step: step + 1,
+                calls: 0,
+            };
+            let mut q = BoundQueue::<u8>::new(cap);
+            loop {
+                let n = q.read_from(&mut r).unwrap();
+                assert!(q.len() <= cap);
+                q.write_to(&mut w).unwrap();
+                if n == 0 && q.is_empty() {
+                    break;
+                }
+            }
+            assert_eq!(w.inner, input);
+        }
+    }
+
+    #[test]
+    fn read_from_full_and_eof() {
+        use std::io::Cursor;
+
+        let mut q = BoundQueue::<u8>::new(4);
+        let mut r = Cursor::new(b"abcdef".to_vec());
+        assert_eq!(q.read_from(&mut r).unwrap(), 4);
+        assert_eq!(q.read_from(&mut r).unwrap(), 0);
+        let mut out = vec![];
+        assert_eq!(q.write_to(&mut out).unwrap(), 4);
+        assert_eq!(q.read_from(&mut r).unwrap(), 2);
+        assert_eq!(q.read_from(&mut r).unwrap(), 0);
+        q.write_to(&mut out).unwrap();
+        assert_eq!(out, b"abcdef".to_vec());
+    }
+
+    // after `honest` truthful calls, claims to have handled `extra` more
+    // bytes than it was given
+    struct Liar {
+        honest: usize,
+        extra: usize,
+    }
+
+    impl Liar {
+        fn count(&mut self, len: usize) -> io::Result<usize> {
+            if self.honest > 0 {
+                self.honest -= 1;
+                return Ok(len);
+            }
+            Ok(len + self.extra)
+        }
+    }
+
+    impl io::Read for Liar {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.count(buf.len())
+        }
+    }
+
+    impl io::Write for Liar {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.count(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_from_write_to_overclaim() {
+        let liar = |honest, extra| Liar { honest, extra };
+        let mut q = BoundQueue::<u8>::new(4);
+        q.push_slice(b"xyz");
+        q.pop();
+        q.pop();
+        // free space runs from slot 3 around to slot 0
+        let err = q.read_from(&mut liar(0, 5)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(q.len(), 1);
+        // a lie after the first segment is dropped like any late error
+        assert_eq!(q.read_from(&mut liar(1, 5)).unwrap(), 2);
+        assert_eq!(q.len(), 3);
+
+        let err = q.write_to(&mut liar(0, 1)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(q.len(), 3);
+        q.push(b'w');
+        assert_eq!(q.write_to(&mut liar(1, 1)).unwrap(), 3);
+        assert_eq!(q.as_vec(), b"w");
+    }
+
+    #[test]
+    fn new_aligned() {
+        for &align in &[32, 64, 4096] {
+            let mut q = BoundQueue::<f32>::new_aligned(10, align).unwrap();
+            assert_eq!(q.buffer_align(), align);
+            assert_eq!(q.data.ptr() as usize % align, 0);
+            for i in 0..30 {
+                q.push(i as f32);
+                assert_eq!(q.pop(), Some(i as f32));
+            }
+        }
+        let q = BoundQueue::<String>::new_aligned(10, 64).unwrap();
+        drop(q);
+        assert_eq!(BoundQueue::<u64>::new_aligned(4, 1).unwrap().buffer_align(), 8);
+        assert!(BoundQueue::<u8>::new_aligned(4, 48).is_err());
+
+        // nothing is allocated, but the alignment still holds
+        let mut q = BoundQueue::<()>::new_aligned(4, 64).unwrap();
+        assert_eq!(q.buffer_align(), 64);
+        assert_eq!(q.data.ptr() as usize % 64, 0);
+        q.push(());
+        assert_eq!(q.pop(), Some(()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_into() {
+        let mut de = serde_json::Deserializer::from_str("[1, 2, 3]");
+        let q = BoundQueue::<u32>::deserialize_into(5, &mut de).unwrap();
+        assert_eq!(q.as_vec(), vec![1, 2, 3]);
+
+        let mut de = serde_json::Deserializer::from_str("[1, 2, 3]");
+        let q = BoundQueue::<u32>::deserialize_into(3, &mut de).unwrap();
+        assert_eq!(q.as_vec(), vec![1, 2, 3]);
+
+        let mut de = serde_json::Deserializer::from_str("[1, 2, 3]");
+        let err = BoundQueue::<u32>::deserialize_into(2, &mut de).err().unwrap();
+        assert!(err.to_string().contains("capacity 2 (3 elements seen)"));
+
+        let mut de = serde_json::Deserializer::from_str("[]");
+        let q = BoundQueue::<u32>::deserialize_into(0, &mut de).unwrap();
+        assert!(q.is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_into_bincode() {
+        use bincode::Options;
+
+        let opts = bincode::DefaultOptions::new();
+        let words = vec!["a".to_string(), "bc".to_string()];
+        let bytes = opts.serialize(&words).unwrap();
+
+        let mut de = bincode::Deserializer::from_slice(&bytes, opts);
+        let q = BoundQueue::<String>::deserialize_into(2, &mut de).unwrap();
+        assert_eq!(q.as_vec(), words);
+
+        let mut de = bincode::Deserializer::from_slice(&bytes, opts);
+        assert!(BoundQueue::<String>::deserialize_into(1, &mut de).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use bincode::Options;
+
+        let opts = bincode::DefaultOptions::new();
+        let mut wrapped = BoundQueue::new(4);
+        for i in 0..7 {
+            wrapped.push(i.to_string());
+            if i < 3 {
+                wrapped.pop();
+            }
+        }
+        let mut pow2 = BoundQueue::new_pow2(3);
+        pow2.extend(["x".to_string(), "y".to_string()]);
+        for q in [BoundQueue::new(0), BoundQueue::new(5), wrapped, pow2] {
+            let json = serde_json::to_string(&q).unwrap();
+            let back: BoundQueue<String> = serde_json::from_str(&json).unwrap();
+            assert_eq!((back.cap(), &back), (q.cap(), &q));
+
+            let bytes = opts.serialize(&q).unwrap();
+            let back: BoundQueue<String> = opts.deserialize(&bytes).unwrap();
+            assert_eq!((back.cap(), &back), (q.cap(), &q));
+        }
+
+        let mut q = BoundQueue::new(3);
+        q.extend([1, 2]);
+        assert_eq!(
+            serde_json::to_string(&q).unwrap(),
+            r#"{"cap":3,"items":[1,2]}"#
+        );
+        let back: BoundQueue<u32> = serde_json::from_str(r#"{"items":[1,2],"cap":3}"#).unwrap();
+        assert_eq!((back.cap(), back.as_vec()), (3, vec![1, 2]));
+
+        for json in &[r#"{"cap":1,"items":[1,2]}"#, r#"{"items":[1,2],"cap":1}"#] {
+            let err = serde_json::from_str::<BoundQueue<u32>>(json).err().unwrap();
+            assert!(err.to_string().contains("capacity 1 (2 elements seen)"));
+        }
+        assert!(serde_json::from_str::<BoundQueue<u32>>(r#"{"cap":1}"#).is_err());
+        assert!(serde_json::from_str::<BoundQueue<u32>>(r#"{"cap":1,"cap":1}"#).is_err());
+        let bytes = opts.serialize(&(1usize, vec![1u32, 2])).unwrap();
+        assert!(opts.deserialize::<BoundQueue<u32>>(&bytes).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_hostile_cap() {
+        let huge = format!("{}", usize::MAX);
+        // more than can be allocated, without overflowing the layout
+        let unallocatable = "1000000000000000";
+        for cap in &[&huge[..], unallocatable] {
+            for json in &[
+                format!(r#"{{"cap":{},"items":[]}}"#, cap),
+                format!(r#"{{"items":[],"cap":{}}}"#, cap),
+            ] {
+                assert!(serde_json::from_str::<BoundQueue<u64>>(json).is_err());
+            }
+        }
+        let json = format!(r#"{{"cap":{},"items":[]}}"#, huge);
+        let err = serde_json::from_str::<BoundQueue<u64>>(&json)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("capacity overflow"));
+
+        let mut d = serde_json::Deserializer::from_str("[1, 2]");
+        assert!(BoundQueue::<u64>::deserialize_into(usize::MAX, &mut d).is_err());
+    }
+
+    fn rng(mut x: u64) -> impl FnMut() -> u64 {
+        move || {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        }
+    }
+
+    #[test]
+    fn insert_sorted() {
+        let mut next = rng(0x2545_f491_4f6c_dd1d);
+        for round in 0..200 {
+            let cap = 1 + round % 17;
+            let mut q = BoundQueue::new(cap);
+            // start from a random offset so the run wraps
+            for _ in 0..next() % (cap as u64 + 1) {
+                q.push(0);
+                q.pop();
+            }
+            let mut model = vec![];
+            for _ in 0..cap {
+                let v = next() % 8;
+                assert!(q.insert_sorted(v).is_ok());
+                let at = model.iter().take_while(|&&x| x <= v).count();
+                model.insert(at, v);
+                assert_eq!(q.as_vec(), model);
+            }
+            assert_eq!(q.insert_sorted(3), Err(PushError::Full(3)));
+        }
+    }
+
+    #[test]
+    fn insert_sorted_stable() {
+        let mut q = BoundQueue::new_pow2(3);
+        for &(k, tag) in &[(2, 'a'), (1, 'b'), (2, 'c'), (0, 'd'), (2, 'e'), (1, 'f')] {
+            q.insert_sorted_by_key((k, tag), |e| e.0).unwrap();
+        }
+        let tags: String = q.iter().map(|e| e.1).collect();
+        assert_eq!(tags, "dbface");
+    }
+
+    #[test]
+    fn insert_sorted_wrapped_segment() {
+        let mut q = BoundQueue::new(6);
+        for i in 0..5 {
+            q.push(i);
+            q.pop();
+        }
+        // head at slot 5 of 7: the run 10, 20, 30, 40 wraps after 20
+        for &v in &[10, 20, 30, 40] {
+            q.push(v);
+        }
+        q.insert_sorted(35).unwrap();
+        q.insert_sorted(5).unwrap();
+        assert_eq!(q.as_vec(), vec![5, 10, 20, 30, 35, 40]);
+        assert_eq!(q.insert_sorted(1), Err(PushError::Full(1)));
+    }
+
+    #[test]
+    fn sorted_pop_order() {
+        let mut next = rng(0x9e37_79b9_7f4a_7c15);
+        for round in 0..100 {
+            let cap = 1 + round % 23;
+            let mut q = if round % 2 == 0 {
+                BoundQueue::new(cap)
+            } else {
+                BoundQueue::with_pow2_capacity(cap)
+            };
+            for _ in 0..next() % (q.cap() as u64 + 1) {
+                q.push((0, 0));
+                q.pop();
+            }
+            // shuffled keys with repeats, tagged in arrival order
+            let mut keys: Vec<u64> = (0..q.cap() as u64).map(|k| k / 2).collect();
+            for i in (1..keys.len()).rev() {
+                keys.swap(i, next() as usize % (i + 1));
+            }
+            for (tag, &k) in keys.iter().enumerate() {
+                q.insert_sorted_by_key((k, tag), |e| e.0).unwrap();
+            }
+            for &k in &keys {
+                let i = q.binary_search_by_key(&k, |e| e.0).unwrap();
+                assert_eq!(q[i].0, k);
+            }
+            let max = keys.iter().max().unwrap();
+            assert_eq!(q.binary_search_by_key(&(max + 1), |e| e.0), Err(q.len()));
+
+            let out: Vec<_> = q.into_iter().collect();
+            let mut want = out.clone();
+            // sorted by key, and equal keys in arrival order
+            want.sort_by_key(|e| (e.0, e.1));
+            assert_eq!(out, want);
+        }
+
+        let mut q = BoundQueue::new(4);
+        q.push(0);
+        q.pop();
+        q.push(0);
+        q.pop();
+        for &v in &[10, 20, 30, 40] {
+            q.push(v);
+        }
+        assert!(!q.as_slices().1.is_empty());
+        assert_eq!(q.binary_search(&30), Ok(2));
+        assert_eq!(q.binary_search(&25), Err(2));
+        assert_eq!(q.binary_search(&5), Err(0));
+        assert_eq!(q.binary_search(&45), Err(4));
+        let at = q.as_slices().0.len();
+        assert_eq!(q.binary_search(&q[at]), Ok(at));
+    }
+
+    #[test]
+    fn push_slot() {
+        let mut q = BoundQueue::<[u64; 64]>::new(3);
+        for i in 0..2 {
+            let mut slot = q.push_slot().unwrap();
+            unsafe {
+                let p = slot.as_mut_ptr() as *mut u64;
+                for j in 0..64 {
+                    p.add(j).write(i * 100 + j as u64);
+                }
+                slot.commit();
+            }
+        }
+        q.push_slot().unwrap().write([7; 64]);
+        assert!(q.push_slot().is_none());
+        assert_eq!(q.pop().unwrap()[63], 63);
+        assert_eq!(q.pop().unwrap()[1], 101);
+        assert_eq!(q.pop().unwrap()[0], 7);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn push_slot_abandoned() {
+        let mut q = BoundQueue::<String>::new(4);
+        q.push("a".to_string());
+        {
+            let _slot = q.push_slot().unwrap();
+        }
+        {
+            let mut slot = q.push_slot().unwrap();
+            // written but never committed: forgotten, not queued
+            // (an empty String owns no heap memory, so nothing leaks)
+            *slot = MaybeUninit::new(String::new());
+        }
+        assert_eq!(q.len(), 1);
+        q.push_slot().unwrap().write("b".to_string());
+        assert_eq!(q.as_vec(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn pop_with() {
+        let mut q = BoundQueue::new(3);
+        for i in 0..3 {
+            q.push([i as u8; 4096]);
+        }
+        let addr = q.iter().next().unwrap() as *const _ as usize;
+        assert_eq!(q.pop_with(|x| (x as *mut _ as usize, x[0])), Some((addr, 0)));
+        assert_eq!(q.pop_with(|x| x[4095]), Some(1));
+        assert_eq!(q.len(), 1);
+        q.pop();
+        assert_eq!(q.pop_with(|x| x[0]), None);
+    }
+
+    #[test]
+    fn pop_with_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let drops = Cell::new(0);
+        {
+            let mut q = BoundQueue::new(4);
+            for _ in 0..3 {
+                q.push(Counted(&drops));
+            }
+            let r = catch_unwind(AssertUnwindSafe(|| q.pop_with(|_| panic!("boom"))));
+            assert!(r.is_err());
+            assert_eq!(drops.get(), 1);
+            assert_eq!(q.len(), 2);
+            assert_eq!(q.pop_with(|_| 5), Some(5));
+            assert_eq!(drops.get(), 2);
+        }
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn push_with() {
+        let calls = Cell::new(0);
+        let make = |i: i32| {
+            calls.set(calls.get() + 1);
+            format!("line {}", i)
+        };
+        let mut q = BoundQueue::new(3);
+        for i in 0..5 {
+            let pushed = q.push_with(|| make(i));
+            assert_eq!(pushed, i < 3);
+        }
+        assert_eq!(calls.get(), 3);
+        assert_eq!(q.as_vec(), vec!["line 0", "line 1", "line 2"]);
+
+        q.pop();
+        assert!(q.push_with(|| make(5)));
+        assert_eq!(calls.get(), 4);
+        assert_eq!(q.as_vec(), vec!["line 1", "line 2", "line 5"]);
+    }
+
+    #[test]
+    fn try_push_force_push() {
+        for &pow2 in &[false, true] {
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(2)
+            } else {
+                BoundQueue::new(4)
+            };
+            for i in 0..4 {
+                assert_eq!(q.try_push(i), Ok(()));
+            }
+            q.pop();
+            q.pop();
+            assert_eq!(q.try_push(4), Ok(()));
+            assert_eq!(q.try_push(5), Ok(()));
+            // tail has wrapped to just behind head
+            assert_eq!(q.try_push(6), Err(6));
+            assert_eq!(q.as_vec(), vec![2, 3, 4, 5]);
+
+            for i in 6..15 {
+                assert_eq!(q.force_push(i), Some(i - 4));
+                assert_eq!(q.as_vec(), (i - 3..i + 1).collect::<Vec<_>>());
+            }
+            q.pop();
+            assert_eq!(q.force_push(15), None);
+            assert_eq!(q.as_vec(), vec![12, 13, 14, 15]);
+        }
+
+        let mut q = BoundQueue::new(0);
+        assert_eq!(q.try_push(1), Err(1));
+        assert_eq!(q.force_push(1), Some(1));
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn push_or_merge() {
+        let calls = Cell::new(0);
+        // consecutive notifications for the same page add up
+        let same_page = |back: &mut (u32, u32), item: (u32, u32)| {
+            calls.set(calls.get() + 1);
+            if back.0 == item.0 {
+                back.1 += item.1;
+                None
+            } else {
+                Some(item)
+            }
+        };
+        let mut q = BoundQueue::new(2);
+        assert_eq!(q.push_or_merge((1, 1), same_page), Ok(()));
+        assert_eq!(calls.get(), 0);
+        assert_eq!(q.push_or_merge((1, 2), same_page), Ok(()));
+        assert_eq!(q.push_or_merge((2, 1), same_page), Ok(()));
+        assert_eq!(calls.get(), 2);
+        assert_eq!(q.as_vec(), vec![(1, 3), (2, 1)]);
+
+        // full, but merging needs no slot
+        assert_eq!(q.push_or_merge((2, 4), same_page), Ok(()));
+        assert_eq!(q.push_or_merge((3, 1), same_page), Err((3, 1)));
+        assert_eq!(calls.get(), 4);
+        assert_eq!(q.as_vec(), vec![(1, 3), (2, 5)]);
+
+        // the back element is found across the wrap
+        q.pop();
+        assert_eq!(q.push_or_merge((3, 1), same_page), Ok(()));
+        q.pop();
+        assert_eq!(q.push_or_merge((3, 1), same_page), Ok(()));
+        assert_eq!(q.as_vec(), vec![(3, 2)]);
+    }
+
+    #[test]
+    fn duplicate() {
+        let mut q = BoundQueue::<f32>::new(8);
+        for i in 0..6 {
+            q.push(i as f32);
+        }
+        for _ in 0..5 {
+            q.pop();
+        }
+        for i in 6..13 {
+            q.push(i as f32);
+        }
+        let mut d = q.duplicate();
+        assert_eq!(d.as_vec(), q.as_vec());
+        assert_eq!(d.cap(), q.cap());
+
+        for x in d.iter_mut() {
+            *x = -*x;
+        }
+        d.pop();
+        assert_eq!(q.as_vec(), (5..13).map(|i| i as f32).collect::<Vec<_>>());
+        assert_eq!(d.as_vec(), (6..13).map(|i| -i as f32).collect::<Vec<_>>());
+        d.push(1.0);
+        d.push(2.0);
+        assert_eq!(d.len(), 8);
+
+        let mut p = BoundQueue::<u8>::new_pow2(2);
+        for i in 0..7 {
+            p.push(i);
+            if i % 2 == 0 {
+                p.pop();
+            }
+        }
+        assert_eq!(p.duplicate().as_vec(), p.as_vec());
+    }
+
+    #[test]
+    fn from_slice() {
+        let src = ["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut q = BoundQueue::from_slice(&src);
+        assert_eq!(q.cap(), 3);
+        assert_eq!(q.len(), 3);
+        q.push("d".to_string());
+        q.iter_mut().for_each(|s| s.push('!'));
+        assert_eq!(q.as_vec(), vec!["a!", "b!", "c!"]);
+        assert_eq!(src, ["a", "b", "c"]);
+        assert_eq!(q.pop().as_deref(), Some("a!"));
+
+        let q = BoundQueue::<String>::from_slice(&[]);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn from_slice_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        struct Bomb<'a>(&'a Cell<usize>, bool);
+        impl<'a> Clone for Bomb<'a> {
+            fn clone(&self) -> Self {
+                assert!(!self.1, "boom");
+                Bomb(self.0, false)
+            }
+        }
+        impl<'a> Drop for Bomb<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        {
+            let src = [
+                Bomb(&drops, false),
+                Bomb(&drops, false),
+                Bomb(&drops, true),
+            ];
+            let r = catch_unwind(AssertUnwindSafe(|| BoundQueue::from_slice(&src)));
+            assert!(r.is_err());
+            // the two clones made before the panic
+            assert_eq!(drops.get(), 2);
+        }
+        assert_eq!(drops.get(), 5);
+    }
+
+    // the byte-by-byte definition extend_from_within has to match
+    fn naive_extend(v: &mut Vec<u8>, start: usize, end: usize) {
+        for i in start..end {
+            let x = v[i];
+            v.push(x);
+        }
+    }
+
+    #[test]
+    fn extend_from_within() {
+        let mut q = BoundQueue::new(16);
+        for _ in 0..15 {
+            q.push(0);
+            q.pop();
+        }
+        // "89" fills the last two of the 17 slots, "ab" wraps to the front
+        for &b in b"89ab" {
+            q.push(b);
+        }
+        assert_eq!(q.extend_from_within(..), Ok(4));
+        assert_eq!(q.as_vec(), b"89ab89ab");
+        // source range inside the wrapped segment
+        assert_eq!(q.extend_from_within(3..6), Ok(3));
+        assert_eq!(q.as_vec(), b"89ab89abb89");
+        // repeat semantics: the source overlaps what is being written
+        assert_eq!(q.extend_from_within(9..=12), Ok(4));
+        assert_eq!(q.as_vec(), b"89ab89abb898989");
+        assert_eq!(q.extend_from_within(2..2), Ok(0));
+
+        // all or nothing
+        let e = q.extend_from_within(0..2).unwrap_err();
+        assert_eq!(e.to_string(), "no room for 2 more elements, only 1 free");
+        assert_eq!(q.as_vec(), b"89ab89abb898989");
+        assert_eq!(q.extend_from_within(14..15), Ok(1));
+        assert_eq!(q.len(), 16);
+
+        // destination wraps
+        let mut q = BoundQueue::new(16);
+        for _ in 0..14 {
+            q.push(0);
+            q.pop();
+        }
+        for &b in b"xyz" {
+            q.push(b);
+        }
+        assert_eq!(q.extend_from_within(0..3), Ok(3));
+        assert_eq!(q.extend_from_within(1..=4), Ok(4));
+        assert_eq!(q.as_vec(), b"xyzxyzyzxy");
+    }
+
+    #[test]
+    #[should_panic(expected = "range starts at 3 but the queue has 3 elements")]
+    fn extend_from_within_out_of_range() {
+        let mut q = BoundQueue::from_slice(&[1, 2, 3]);
+        q.pop();
+        q.push(4);
+        let _ = q.extend_from_within(3..4);
+    }
+
+    #[test]
+    fn extend_from_within_model() {
+        let mut next = rng(0x2545_f491_4f6c_dd1d);
+        for &pow2 in &[false, true] {
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(6)
+            } else {
+                BoundQueue::new(61)
+            };
+            let cap = q.usable();
+            let mut m: Vec<u8> = vec![];
+            for _ in 0..3000 {
+                match next() % 3 {
+                    0 => {
+                        let b = next() as u8;
+                        q.push(b);
+                        if m.len() < cap {
+                            m.push(b);
+                        }
+                    }
+                    1 if !m.is_empty() => {
+                        let start = (next() % m.len() as u64) as usize;
+                        let count = (next() % 20) as usize;
+                        let r = q.extend_from_within(start..start + count);
+                        if m.len() + count <= cap {
+                            assert_eq!(r, Ok(count));
+                            naive_extend(&mut m, start, start + count);
+                        } else {
+                            assert!(r.is_err());
+                        }
+                    }
+                    _ => {
+                        for _ in 0..next() % 25 {
+                            q.pop();
+                            if !m.is_empty() {
+                                m.remove(0);
+                            }
+                        }
+                    }
+                }
+                assert_eq!(q.as_vec(), m);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_sized() {
+        use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+        // a counter a zero-sized type can reach without carrying a pointer
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+        struct Z;
+        impl Drop for Z {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, AtomicOrdering::Relaxed);
+            }
+        }
+
+        for &pow2 in &[false, true] {
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(2)
+            } else {
+                BoundQueue::new(4)
+            };
+            assert_eq!(q.cap(), 4);
+            for round in 0..5 {
+                for _ in 0..5 {
+                    q.push(Z);
+                }
+                assert!(q.is_full());
+                assert_eq!(q.len(), 4);
+                assert_eq!(q.iter().len(), 4);
+                assert_eq!(q.iter_mut().count(), 4);
+                let (a, b) = q.as_slices();
+                assert_eq!(a.len() + b.len(), 4);
+                // leave head somewhere new for the next round
+                for _ in 0..1 + round % 3 {
+                    assert!(q.pop().is_some());
+                }
+                q.clear();
+                assert!(q.pop().is_none());
+            }
+        }
+        // 5 rounds of 5 pushes, one rejected each, in both queues
+        assert_eq!(DROPS.load(AtomicOrdering::Relaxed), 50);
+
+        let mut q = BoundQueue::<()>::new(3);
+        q.extend(vec![(); 10]);
+        assert_eq!(q.drain().count(), 3);
+        q.push(());
+        q.reserve(10);
+        assert!(q.cap() >= 11);
+        assert_eq!(q.len(), 1);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut q = BoundQueue::<()>::new(0);
+        assert!(q.is_full() && q.is_empty());
+        q.push(());
+        q.push_front(());
+        assert_eq!(q.try_push(()), Err(()));
+        assert_eq!(q.pop(), None);
+        assert_eq!(q.iter().count(), 0);
+
+        let mut q = BoundQueue::<String>::new(0);
+        assert_eq!(q.cap(), 0);
+        assert!(q.is_full() && q.is_empty());
+        q.push("dropped".to_string());
+        assert_eq!(q.try_push("back".to_string()), Err("back".to_string()));
+        assert_eq!(q.len(), 0);
+        assert_eq!(q.pop(), None);
+        assert_eq!(q.pop_back(), None);
+        assert_eq!(q.get(0), None);
+        assert_eq!(q.iter().next(), None);
+        assert_eq!(q.as_slices(), (&[][..], &[][..]));
+        q.clear();
+        q.truncate(0);
+    }
+
+    #[test]
+    fn try_new() {
+        let e = BoundQueue::<u8>::try_new(usize::MAX).err().unwrap();
+        assert_eq!(e.to_string(), "capacity overflow");
+        // the byte size overflows isize
+        assert!(BoundQueue::<u64>::try_new(isize::MAX as usize).is_err());
+        assert!(BoundQueue::<[u8; 1024]>::try_new(usize::MAX / 1024).is_err());
+        assert!(BoundQueue::<u8>::try_new_pow2(64).is_err());
+        assert!(BoundQueue::<u8>::try_new_pow2(63).is_err());
+        assert!(BoundQueue::<u32>::try_new_pow2(62).is_err());
+        assert!(BoundQueue::<u8>::new_aligned(usize::MAX, 8).is_err());
+
+        // zero-sized elements never allocate, but the sentinel slot still
+        // has to fit
+        assert!(BoundQueue::<()>::try_new(usize::MAX).is_err());
+        let mut q = BoundQueue::<()>::try_new(usize::MAX - 1).unwrap();
+        q.push(());
+        q.push(());
+        assert_eq!(q.get(1), Some(&()));
+        assert_eq!(q.get(2), None);
+        assert_eq!(q.pop(), Some(()));
+        let mut q = BoundQueue::<()>::try_new_pow2(20).unwrap();
+        q.push(());
+        assert_eq!(q.pop(), Some(()));
+
+        let mut q = BoundQueue::try_new(0).unwrap();
+        q.push(1);
+        assert_eq!(q.pop(), None);
+        assert_eq!(q.get(0), None);
+
+        let mut q = BoundQueue::try_new(3).unwrap();
+        for i in 0..5 {
+            q.push(i);
+        }
+        q.pop();
+        q.push(5);
+        let got: Vec<_> = (0..4).map(|i| q.get(i).cloned()).collect();
+        assert_eq!(got, vec![Some(1), Some(2), Some(5), None]);
+    }
+
+    // the allocator gets asked for ~4.6 exabytes
+    #[test]
+    #[cfg(not(miri))]
+    fn try_new_alloc_failure() {
+        let e = BoundQueue::<u8>::try_new(isize::MAX as usize / 2)
+            .err()
+            .unwrap();
+        assert!(e.to_string().starts_with("allocation of"));
+    }
+
+    #[test]
+    #[should_panic(expected = "): capacity overflow")]
+    fn new_overflow() {
+        BoundQueue::<u8>::new(usize::MAX);
+    }
+
+    // each element is a megabyte, so the buffer passes isize::MAX bytes
+    // long before the slot count overflows
+    #[test]
+    #[should_panic(expected = "invalid capacity or alignment for queue buffer")]
+    fn new_layout_overflow() {
+        BoundQueue::<[u8; 1 << 20]>::new((isize::MAX as usize >> 20) + 1);
+    }
+
+    #[test]
+    fn new_matches_try_new() {
+        for &size in &[0, 1, 7, 8, 1000] {
+            let mut a = BoundQueue::new(size);
+            let mut b = BoundQueue::try_new(size).unwrap();
+            assert_eq!(a.cap(), b.cap());
+            for i in 0..size + 2 {
+                a.push(i);
+                b.push(i);
+            }
+            assert!(a.iter().eq(b.iter()));
+        }
+        assert_eq!(BoundQueue::<()>::new(5).cap(), 5);
+        let e = BoundQueue::<[u8; 1 << 20]>::try_new((isize::MAX as usize >> 20) + 1)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "invalid capacity or alignment for queue buffer"
+        );
+    }
+
+    #[test]
+    fn grow_wrapped() {
+        for &pow2 in &[false, true] {
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(3)
+            } else {
+                BoundQueue::with_capacity(8)
+            };
+            // fill, pop some, refill so the run crosses the end of the buffer
+            for i in 0..8 {
+                q.push(i.to_string());
+            }
+            for _ in 0..5 {
+                q.pop();
+            }
+            for i in 8..13 {
+                q.push(i.to_string());
+            }
+            assert!(q.is_full());
+            q.grow_to(10);
+            assert_eq!(q.cap(), if pow2 { 16 } else { 10 });
+            q.push(13.to_string());
+            q.push(14.to_string());
+            q.reserve(4);
+            assert!(q.cap() - q.len() >= 4);
+            for i in 15..19 {
+                q.push(i.to_string());
+            }
+            let out: Vec<_> = q.drain().collect();
+            assert_eq!(out, (5..19).map(|i| i.to_string()).collect::<Vec<_>>());
+        }
+
+        let mut q = BoundQueue::new(4);
+        q.grow_to(2);
+        assert_eq!(q.cap(), 4);
+        q.extend(0..4);
+        // reserving one more at a time still doubles
+        q.reserve(1);
+        assert_eq!(q.cap(), 8);
+        q.reserve(4);
+        assert_eq!(q.cap(), 8);
+
+        let mut q = BoundQueue::<u32, u8>::try_new_indexed(10).unwrap();
+        q.push(1);
+        assert!(q.try_grow_to(256).is_err());
+        assert!(q.try_reserve(usize::MAX).is_err());
+        assert_eq!((q.cap(), q.pop()), (10, Some(1)));
+        q.grow_to(255);
+        assert_eq!(q.cap(), 255);
+    }
+
+    #[test]
+    #[should_panic(expected = "BoundQueue::reserve(18446744073709551615): capacity overflow")]
+    #[cfg(target_pointer_width = "64")]
+    fn reserve_overflow() {
+        let mut q = BoundQueue::new(2);
+        q.push(1);
+        q.reserve(usize::MAX);
+    }
+
+    #[test]
+    fn from_iter_keep_last() {
+        let (q, evicted) = BoundQueue::from_iter_keep_last(5, 0..3);
+        assert_eq!((q.as_vec(), evicted), (vec![0, 1, 2], 0));
+        let (q, evicted) = BoundQueue::from_iter_keep_last(5, 0..5);
+        assert_eq!((q.as_vec(), evicted), (vec![0, 1, 2, 3, 4], 0));
+        let (q, evicted) = BoundQueue::from_iter_keep_last(5, 0..10_000);
+        assert_eq!(q.as_vec(), (9995..10_000).collect::<Vec<_>>());
+        assert_eq!(evicted, 9995);
+        let (q, evicted) = BoundQueue::from_iter_keep_last(0, 0..10);
+        assert!(q.is_empty());
+        assert_eq!(evicted, 10);
+
+        // evicted items are dropped as they go, not all at the end
+        let drops = Cell::new(0);
+        let (q, evicted) = BoundQueue::from_iter_keep_last(2, (0..6).map(|_| Counted(&drops)));
+        assert_eq!((drops.get(), evicted), (4, 4));
+        drop(q);
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[test]
+    fn from_iter_keep_first() {
+        let (q, rest) = BoundQueue::from_iter_keep_first(5, 0..3);
+        assert_eq!((q.as_vec(), rest.count()), (vec![0, 1, 2], 0));
+        let (q, rest) = BoundQueue::from_iter_keep_first(5, 0..5);
+        assert_eq!((q.as_vec(), rest.count()), (vec![0, 1, 2, 3, 4], 0));
+        let (q, mut rest) = BoundQueue::from_iter_keep_first(5, 0..10_000);
+        assert_eq!(q.as_vec(), vec![0, 1, 2, 3, 4]);
+        // nothing past the fifth item was consumed
+        assert_eq!(rest.next(), Some(5));
+        assert_eq!(rest.count(), 9994);
+
+        let mut pulled = 0;
+        let (q, _) = BoundQueue::from_iter_keep_first(3, (0..).inspect(|_| pulled += 1));
+        assert_eq!((q.as_vec(), pulled), (vec![0, 1, 2], 3));
+        let (q, rest) = BoundQueue::from_iter_keep_first(0, 0..4);
+        assert!(q.is_empty());
+        assert_eq!(rest.count(), 4);
+    }
+
+    #[test]
+    fn take() {
+        let mut q = BoundQueue::new(4);
+        for i in 0..6 {
+            q.push(i);
+            q.pop();
+        }
+        for i in 0..4 {
+            q.push(i);
+        }
+        let old = q.data.ptr();
+        let taken = q.take();
+        // the elements left with their buffer, nothing was copied
+        assert_eq!(taken.data.ptr(), old);
+        assert_eq!(taken.as_vec(), vec![0, 1, 2, 3]);
+        assert!(q.is_empty());
+        assert_eq!(q.cap(), taken.cap());
+
+        // filling between takes never reallocates
+        for round in 0..3 {
+            let buf = q.data.ptr();
+            for i in 0..5 {
+                q.push(round * 10 + i);
+            }
+            assert_eq!(q.data.ptr(), buf);
+            let taken = q.take();
+            assert_eq!(taken.data.ptr(), buf);
+            assert_eq!(
+                taken.as_vec(),
+                (0..4).map(|i| round * 10 + i).collect::<Vec<_>>()
+            );
+            assert_eq!((q.len(), q.cap()), (0, 4));
+        }
+
+        let mut p = BoundQueue::<u8>::new_pow2(3);
+        p.set_shrink_policy(ShrinkPolicy::WhenBelow {
+            fraction: 0.25,
+            min_capacity: 2,
+        });
+        p.push(1);
+        let taken = p.take();
+        assert_eq!(p.cap(), 8);
+        assert_eq!(p.shrink_policy(), taken.shrink_policy());
+        assert!(p.mask.is_some());
+    }
+
+    #[test]
+    fn swap_with() {
+        // double buffering: the consumer drains one buffer while producers
+        // fill the other, and no allocation happens after the first two
+        let mut front = BoundQueue::new(3);
+        let mut back = BoundQueue::new(3);
+        let bufs = (front.data.ptr(), back.data.ptr());
+        let mut seen = vec![];
+        for round in 0..4 {
+            front.push(round);
+            front.push(round + 100);
+            front.swap_with(&mut back);
+            while let Some(v) = back.pop() {
+                seen.push(v);
+            }
+        }
+        assert_eq!(seen, [0, 100, 1, 101, 2, 102, 3, 103]);
+        assert_eq!((front.data.ptr(), back.data.ptr()), bufs);
+
+        let mut small = BoundQueue::new(1);
+        small.push(7);
+        front.swap_with(&mut small);
+        assert_eq!((front.cap(), small.cap()), (1, 3));
+        assert_eq!(front.as_vec(), vec![7]);
+    }
+
+    #[test]
+    fn shrink_policy() {
+        let policy = ShrinkPolicy::WhenBelow {
+            fraction: 0.25,
+            min_capacity: 64,
+        };
+        let mut q = BoundQueue::new(1024);
+        // wrap the ring before the burst
+        for i in 0..500 {
+            q.push(i);
+            q.pop();
+        }
+        q.set_shrink_policy(policy);
+        for i in 0..1000 {
+            q.push(i);
+        }
+        // no shrink while at least a quarter full
+        for i in 0..744 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(q.cap(), 1024);
+        let mut caps = vec![q.cap()];
+        for i in 744..1000 {
+            assert_eq!(q.pop(), Some(i));
+            assert!(q.iter().cloned().eq(i + 1..1000));
+            if caps.last() != Some(&q.cap()) {
+                caps.push(q.cap());
+            }
+        }
+        assert_eq!(caps, vec![1024, 510, 254, 126, 64]);
+
+        // the bound drops with the capacity
+        for i in 0..100 {
+            q.push(i);
+        }
+        assert_eq!(q.len(), 64);
+        assert!(!q.maybe_shrink(policy));
+        assert!(!q.maybe_shrink(ShrinkPolicy::Never));
+    }
+
+    mod pow2 {
+        use super::*;
+
+        #[test]
+        fn is_empty() {
+            let q = BoundQueue::<i32>::new_pow2(4);
+            assert!(q.is_empty());
+        }
+
+        #[test]
+        fn test_push_pop() {
+            let mut q = BoundQueue::<i32>::new_pow2(4);
+            for i in 1..=40 {
+                q.push(i);
+                assert_eq!(q.pop(), Some(i));
+            }
+        }
+
+        #[test]
+        fn full() {
+            let mut q = BoundQueue::<i32>::new_pow2(3);
+            for round in 0..3 {
+                for i in 0..9 {
+                    q.push(round * 10 + i);
+                }
+                assert!(q.is_full());
+                assert_eq!(q.len(), 8);
+                for i in 0..8 {
+                    assert_eq!(q.pop(), Some(round * 10 + i));
+                }
+                assert!(q.is_empty());
+            }
+        }
+
+        #[test]
+        fn into_iter() {
+            let mut q = BoundQueue::<i32>::new_pow2(4);
+            q.push(1);
+            q.push(2);
+            q.push(3);
+
+            let mut iter = q.into_iter();
+            assert_eq!(iter.next(), Some(1));
+            assert_eq!(iter.next(), Some(2));
+            assert_eq!(iter.next(), Some(3));
+            assert_eq!(iter.next(), None);
+        }
+
+        #[test]
+        fn iter() {
+            let mut q = BoundQueue::<String>::new_pow2(2);
+            for i in 0..3 {
+                q.push(i.to_string());
+            }
+            q.pop();
+            q.pop();
+            for i in 3..6 {
+                q.push(i.to_string());
+            }
+
+            let v: Vec<_> = q.iter().cloned().collect();
+            assert_eq!(v, vec!["2", "3", "4", "5"]);
+        }
+
+        #[test]
+        fn iter_mut() {
+            let mut q = BoundQueue::<i32>::new_pow2(4);
+            q.push(1);
+            q.push(2);
+            q.push(3);
+
+            let mut iter = q.iter_mut();
+            assert_eq!(iter.next(), Some(&mut 1));
+            assert_eq!(iter.next(), Some(&mut 2));
+            assert_eq!(iter.next(), Some(&mut 3));
+            assert_eq!(iter.next(), None);
+        }
+
+        #[test]
+        fn shrink_policy() {
+            let mut q = BoundQueue::new_pow2(10);
+            for i in 0..1000 {
+                q.push(i);
+            }
+            for i in 0..900 {
+                assert_eq!(q.pop(), Some(i));
+            }
+            assert_eq!(q.cap(), 1024);
+            let policy = ShrinkPolicy::WhenBelow {
+                fraction: 0.25,
+                min_capacity: 50,
+            };
+            // 100 left: twice that, rounded up
+            assert!(q.maybe_shrink(policy));
+            assert_eq!(q.cap(), 256);
+            assert!(q.iter().cloned().eq(900..1000));
+            q.set_shrink_policy(policy);
+            for i in 900..1000 {
+                assert_eq!(q.pop(), Some(i));
+            }
+            assert_eq!(q.cap(), 64);
+            for i in 0..70 {
+                q.push(i);
+            }
+            assert!(q.is_full());
+            assert!(q.iter().cloned().eq(0..64));
+        }
+    }
+
+    #[test]
+    fn narrow_index_limits() {
+        assert!(BoundQueue::<u32, u8>::try_new_indexed(255).is_ok());
+        assert!(BoundQueue::<u32, u8>::try_new_indexed(256).is_err());
+        assert!(BoundQueue::<u32, u16>::try_new_indexed(65535).is_ok());
+        assert!(BoundQueue::<u32, u16>::try_new_indexed(65536).is_err());
+        assert!(BoundQueue::<u32, u8>::try_new_pow2_indexed(7).is_ok());
+        let e = BoundQueue::<u32, u8>::try_new_pow2_indexed(8)
+            .err()
+            .unwrap();
+        assert_eq!(e.to_string(), "capacity overflow");
+
+        assert!(mem::size_of::<BoundQueue<u32, u8>>() < mem::size_of::<BoundQueue<u32>>());
+        assert!(mem::size_of::<BoundQueue<u32, u16>>() < mem::size_of::<BoundQueue<u32>>());
+        assert_eq!(
+            mem::size_of::<BoundQueue<u32, usize>>(),
+            mem::size_of::<BoundQueue<u32>>()
+        );
+    }
+
+    // Random pushes, pops and in-place edits checked against a VecDeque,
+    // long enough for the head and tail to wrap the index type many times.
+    fn narrow_model<I: Idx>(mut q: BoundQueue<u32, I>, seed: u64) {
+        let cap = q.usable();
+        let mut model = VecDeque::new();
+        let mut next = rng(seed);
+        for step in 0..20_000 {
+            let r = next();
+            // runs of mostly pushes and mostly pops, so the queue goes both
+            // full and empty
+            let filling = (step / 700) % 2 == 0;
+            let v = (r >> 8) as u32;
+            match r % 14 {
+                0..=3 if filling => {
+                    q.push(v);
+                    if model.len() < cap {
+                        model.push_back(v);
+                    }
+                }
+                0..=3 => assert_eq!(q.pop(), model.pop_front()),
+                4 => assert_eq!(q.pop_with(|x| *x), model.pop_front()),
+                5 => {
+                    let pushed = q.push_with(|| v);
+                    assert_eq!(pushed, model.len() < cap);
+                    if pushed {
+                        model.push_back(v);
+                    }
+                }
+                6 if !model.is_empty() => {
+                    let start = (v as usize) % model.len();
+                    let count = cmp::min((r >> 40) as usize % 4, cap - model.len());
+                    assert_eq!(q.extend_from_within(start..start + count), Ok(count));
+                    for k in 0..count {
+                        let x = model[start + k];
+                        model.push_back(x);
+                    }
+                }
+                7 if step % 50 == 0 => {
+                    q.retain_mut(|x| *x % 3 != 0);
+                    model.retain(|x| *x % 3 != 0);
+                }
+                9 if filling => {
+                    q.push_front(v);
+                    if model.len() < cap {
+                        model.push_front(v);
+                    }
+                }
+                10 => assert_eq!(q.pop_back(), model.pop_back()),
+                11 => {
+                    let n = (v as usize) % (model.len() + 1);
+                    if r & (1 << 40) == 0 {
+                        q.rotate_left(n);
+                        model.rotate_left(n);
+                    } else {
+                        q.rotate_right(n);
+                        model.rotate_right(n);
+                    }
+                }
+                12 if !model.is_empty() => {
+                    let (i, j) = (v as usize % model.len(), (r >> 40) as usize % model.len());
+                    q.swap(i, j);
+                    model.swap(i, j);
+                }
+                8 => {
+                    let n = (v % 20) as usize;
+                    let (a, b) = q.peek_n(n);
+                    let want: Vec<_> = model.iter().take(n).cloned().collect();
+                    assert_eq!([a, b].concat(), want);
+                }
+                _ => {
+                    let i = (v as usize) % (cap + 1);
+                    assert_eq!(q.get(i), model.get(i));
+                }
+            }
+            assert_eq!(q.len(), model.len());
+            assert_eq!(q.is_empty(), model.is_empty());
+            assert_eq!(q.is_full(), model.len() == cap);
+            if step % 97 == 0 {
+                assert!(q.iter().eq(model.iter()));
+                for x in q.iter_mut() {
+                    *x = x.wrapping_add(1);
+                }
+                for x in model.iter_mut() {
+                    *x = x.wrapping_add(1);
+                }
+                assert_eq!(q.duplicate().as_vec(), q.as_vec());
+            }
+        }
+        assert_eq!(q.into_iter().collect::<Vec<_>>(), Vec::from(model));
+    }
+
+    #[test]
+    fn narrow_index_u8() {
+        narrow_model(BoundQueue::<u32, u8>::try_new_indexed(255).unwrap(), 11);
+        narrow_model(BoundQueue::<u32, u8>::try_new_indexed(7).unwrap(), 12);
+        narrow_model(BoundQueue::<u32, u8>::try_new_pow2_indexed(7).unwrap(), 13);
+        narrow_model(BoundQueue::<u32, u8>::try_new_pow2_indexed(3).unwrap(), 14);
+    }
+
+    #[test]
+    fn narrow_index_wider() {
+        narrow_model(BoundQueue::<u32, u16>::try_new_indexed(300).unwrap(), 21);
+        narrow_model(BoundQueue::<u32, u16>::try_new_pow2_indexed(5).unwrap(), 22);
+        narrow_model(BoundQueue::<u32, u32>::try_new_indexed(100).unwrap(), 23);
+        narrow_model(BoundQueue::<u32>::try_new(255).unwrap(), 24);
+    }
+
+    #[test]
+    fn pow2_capacity() {
+        assert_eq!(BoundQueue::<u8>::with_pow2_capacity(5).cap(), 8);
+        assert_eq!(BoundQueue::<u8>::with_pow2_capacity(8).cap(), 8);
+        assert_eq!(BoundQueue::<u8>::with_pow2_capacity(0).cap(), 1);
+        assert!(BoundQueue::<u8>::try_with_pow2_capacity(usize::MAX).is_err());
+
+        let mut q = BoundQueue::near_wrap(4, 2);
+        for i in 0..4 {
+            q.push(i);
+        }
+        assert!(q.is_full());
+        q.push(4);
+        assert_eq!((q.head, q.tail), (usize::MAX - 1, 2));
+        assert_eq!(q.as_slices(), (&[0, 1][..], &[2, 3][..]));
+        assert_eq!(q.pop(), Some(0));
+        q.push_front(9);
+        assert_eq!(q.as_vec(), [9, 1, 2, 3]);
+
+        // the same model the plain layout is held to, with counters that
+        // wrap early on
+        narrow_model(BoundQueue::new(16), 31);
+        narrow_model(BoundQueue::with_pow2_capacity(16), 32);
+        narrow_model(BoundQueue::near_wrap(16, 100), 33);
+        narrow_model(BoundQueue::near_wrap(1, 1), 34);
+    }
+
+    #[test]
+    fn narrow_index_io() {
+        let mut q = BoundQueue::<u8, u8>::try_new_indexed(255).unwrap();
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let mut out = vec![];
+        for chunk in data.chunks(100) {
+            io::Write::write_all(&mut q, chunk).unwrap();
+            let mut buf = [0; 100];
+            io::Read::read_exact(&mut q, &mut buf).unwrap();
+            out.extend_from_slice(&buf);
+        }
+        assert_eq!(out, data);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats() {
+        let stats = |high_water_mark, pushed, popped, rejected| QueueStats {
+            high_water_mark,
+            pushed,
+            popped,
+            rejected,
+        };
+        // plain layout wrapping around its 4 slots, then the masked one
+        // wrapping its counters past usize::MAX
+        for q in [BoundQueue::new(4), BoundQueue::near_wrap(4, 2)] {
+            let mut q = q;
+            assert_eq!(q.stats(), QueueStats::default());
+            // + push, - pop, ! push into a full queue
+            for (i, op) in "++-+++!!--++!-".chars().enumerate() {
+                match op {
+                    '+' => q.push(i),
+                    '-' => assert!(q.pop().is_some()),
+                    _ => assert_eq!(q.try_push(i), Err(i)),
+                }
+            }
+            assert_eq!(q.stats(), stats(4, 7, 4, 3));
+            assert_eq!((q.high_water_mark(), q.total_pushed()), (4, 7));
+            assert_eq!((q.total_popped(), q.rejected()), (4, 3));
+
+            q.reset_stats();
+            // the mark restarts from the length at the reset
+            assert_eq!(q.stats(), stats(3, 0, 0, 0));
+            q.push_front(0);
+            q.push(0);
+            assert_eq!(q.drain_first(2).count(), 2);
+            assert_eq!(q.force_push(0), None);
+            assert_eq!(q.force_push(0), None);
+            assert!(q.force_push(0).is_some());
+            assert!(!q.push_with(|| 0));
+            assert_eq!(q.push_slice(&[1, 2, 3]), 0);
+            assert_eq!(q.stats(), stats(4, 4, 3, 5));
+            q.clear();
+            // dropping in place isn't popping
+            assert_eq!(q.stats(), stats(4, 4, 3, 5));
+        }
+
+        // elements a queue is built with aren't pushes
+        let built = [
+            BoundQueue::from_slice(&[1, 2]),
+            BoundQueue::from(vec![1, 2]),
+            BoundQueue::from_vec_in_place(vec![1, 2]),
+            BoundQueue::from_slice(&[1, 2]).clone(),
+            BoundQueue::from_slice(&[1, 2]).duplicate(),
+        ];
+        for q in built.iter() {
+            assert_eq!(q.stats(), stats(2, 0, 0, 0));
+        }
+
+        // the counters stay with the queue that keeps counting
+        let mut q = BoundQueue::from_slice(&[1, 2, 3]);
+        q.pop();
+        let taken = q.take();
+        assert_eq!(taken.stats(), stats(2, 0, 0, 0));
+        assert_eq!(q.stats(), stats(3, 0, 1, 0));
+    }
 }
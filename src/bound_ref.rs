@@ -0,0 +1,293 @@
+use super::{Queue, TryQueue};
+use core::mem::MaybeUninit;
+use core::ptr;
+use error::PushError;
+
+/// The `BoundQueue` ring run over storage owned by the caller: nothing is
+/// allocated or freed, and dropping the view only drops the live elements.
+/// One slot is kept free to tell full from empty, so a buffer of `n` slots
+/// holds at most `n - 1` elements.
+///
+/// The buffer stays mutably borrowed for as long as the view is alive:
+///
+/// ```compile_fail
+/// use std::mem::MaybeUninit;
+/// use queue::Queue;
+/// use queue::bound_ref::BoundQueueRef;
+///
+/// let mut buf: [MaybeUninit<i32>; 4] = unsafe { MaybeUninit::uninit().assume_init() };
+/// let mut q = BoundQueueRef::new(&mut buf);
+/// q.push(1);
+/// buf[0] = MaybeUninit::new(5);
+/// q.push(2);
+/// ```
+pub struct BoundQueueRef<'a, T: 'a> {
+    data: &'a mut [MaybeUninit<T>],
+    head: usize,
+    tail: usize,
+}
+
+impl<'a, T> BoundQueueRef<'a, T> {
+    pub fn new(buf: &'a mut [MaybeUninit<T>]) -> Self {
+        assert!(!buf.is_empty(), "BoundQueueRef needs at least one slot");
+        BoundQueueRef {
+            data: buf,
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    pub fn cap(&self) -> usize {
+        self.data.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        if self.tail >= self.head {
+            self.tail - self.head
+        } else {
+            self.data.len() - self.head + self.tail
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head == self.tail
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.next(self.tail) == self.head
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        if self.is_empty() {
+            return None;
+        }
+        unsafe { Some(&*self.data[self.head].as_ptr()) }
+    }
+
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        if self.is_empty() {
+            return None;
+        }
+        unsafe { Some(&mut *self.data[self.head].as_mut_ptr()) }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            pos: self.head,
+            len: self.len(),
+            data: &*self.data,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            pos: self.head,
+            len: self.len(),
+            data: &mut *self.data,
+        }
+    }
+
+    fn next(&self, idx: usize) -> usize {
+        if idx + 1 == self.data.len() {
+            0
+        } else {
+            idx + 1
+        }
+    }
+}
+
+impl<'a, T> Queue<T> for BoundQueueRef<'a, T> {
+    fn push(&mut self, item: T) {
+        let next = self.next(self.tail);
+        if next == self.head {
+            trace!(warn, cap = self.cap(), "push rejected, queue full");
+            return;
+        }
+        self.data[self.tail] = MaybeUninit::new(item);
+        self.tail = next;
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.head == self.tail {
+            return None;
+        }
+        let v = unsafe { ptr::read(self.data[self.head].as_ptr()) };
+        self.head = self.next(self.head);
+        Some(v)
+    }
+
+    fn len(&self) -> usize {
+        BoundQueueRef::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        BoundQueueRef::is_empty(self)
+    }
+}
+
+impl<'a, T> TryQueue<T> for BoundQueueRef<'a, T> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        if self.is_full() {
+            return Err(PushError::Full(item));
+        }
+        self.push(item);
+        Ok(())
+    }
+}
+
+impl<'a, T> Drop for BoundQueueRef<'a, T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+pub struct Iter<'a, T: 'a> {
+    pos: usize,
+    len: usize,
+    data: &'a [MaybeUninit<T>],
+}
+
+pub struct IterMut<'a, T: 'a> {
+    pos: usize,
+    len: usize,
+    data: &'a mut [MaybeUninit<T>],
+}
+
+pub struct IntoIter<'a, T: 'a>(BoundQueueRef<'a, T>);
+
+impl<'a, T> IntoIterator for BoundQueueRef<'a, T> {
+    type Item = T;
+    type IntoIter = IntoIter<'a, T>;
+    fn into_iter(self) -> IntoIter<'a, T> {
+        IntoIter(self)
+    }
+}
+
+impl<'a, T> Iterator for IntoIter<'a, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let c = self.pos;
+        self.pos = if c + 1 == self.data.len() { 0 } else { c + 1 };
+        self.len -= 1;
+        unsafe { Some(&*self.data[c].as_ptr()) }
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let c = self.pos;
+        self.pos = if c + 1 == self.data.len() { 0 } else { c + 1 };
+        self.len -= 1;
+        // each slot is handed out at most once, so the borrows never overlap
+        unsafe { Some(&mut *self.data[c].as_mut_ptr()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn uninit<T>() -> [MaybeUninit<T>; 11] {
+        unsafe { MaybeUninit::uninit().assume_init() }
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut buf = uninit::<i32>();
+        let mut q = BoundQueueRef::new(&mut buf);
+        assert_eq!(q.cap(), 10);
+        for i in 1..=30 {
+            q.push(i);
+            assert_eq!(q.peek(), Some(&i));
+            assert_eq!(q.pop(), Some(i));
+        }
+        for i in 1..=11 {
+            q.push(i);
+        }
+        assert!(q.is_full());
+        assert_eq!(q.len(), 10);
+        for i in 1..=10 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn iterators() {
+        let mut buf = uninit::<String>();
+        let mut q = BoundQueueRef::new(&mut buf);
+        for i in 0..8 {
+            q.push(i.to_string());
+        }
+        for _ in 0..6 {
+            q.pop();
+        }
+        for i in 8..14 {
+            q.push(i.to_string());
+        }
+        let v: Vec<_> = q.iter().cloned().collect();
+        assert_eq!(v, (6..14).map(|i| i.to_string()).collect::<Vec<_>>());
+
+        for s in q.iter_mut() {
+            s.push('!');
+        }
+        *q.peek_mut().unwrap() = "x".to_string();
+        let v: Vec<_> = q.into_iter().collect();
+        assert_eq!(v[0], "x");
+        assert_eq!(v[7], "13!");
+    }
+
+    #[test]
+    fn static_buffer() {
+        static mut BUF: [MaybeUninit<u32>; 8] = [MaybeUninit::uninit(); 8];
+        let mut q = BoundQueueRef::new(unsafe { &mut *ptr::addr_of_mut!(BUF) });
+        for i in 0..7 {
+            q.push(i);
+        }
+        assert!(q.is_full());
+        assert_eq!(q.iter().sum::<u32>(), 21);
+    }
+
+    struct Counted<'a>(&'a Cell<usize>);
+
+    impl<'a> Drop for Counted<'a> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn drops_only_live() {
+        let drops = Cell::new(0);
+        let mut buf = uninit::<Counted>();
+        {
+            let mut q = BoundQueueRef::new(&mut buf);
+            for _ in 0..10 {
+                q.push(Counted(&drops));
+            }
+            for _ in 0..7 {
+                q.pop();
+            }
+            assert_eq!(drops.get(), 7);
+            for _ in 0..5 {
+                q.push(Counted(&drops));
+            }
+        }
+        assert_eq!(drops.get(), 15);
+    }
+}
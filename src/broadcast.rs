@@ -0,0 +1,307 @@
+use error::{PushError, RecvError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Backpressure,
+    Lossy,
+}
+
+/// Each receiver sees every item; a full ring blocks the sender until the
+/// slowest receiver has moved on.
+pub fn channel<T: Clone>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    new_channel(cap, Mode::Backpressure)
+}
+
+/// Like `channel`, but the sender never blocks: the oldest item is
+/// overwritten and receivers that fall behind get `Lagged(n)`.
+pub fn lossy_channel<T: Clone>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    new_channel(cap, Mode::Lossy)
+}
+
+fn new_channel<T: Clone>(cap: usize, mode: Mode) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "broadcast capacity must be non-zero");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            buf: (0..cap).map(|_| None).collect(),
+            tail: 0,
+            cursors: vec![Some(0)],
+            senders: 1,
+        }),
+        not_full: Condvar::new(),
+        not_empty: Condvar::new(),
+        mode,
+    });
+    let rx = Receiver {
+        shared: shared.clone(),
+        id: 0,
+    };
+    (Sender { shared }, rx)
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    not_full: Condvar,
+    not_empty: Condvar,
+    mode: Mode,
+}
+
+struct State<T> {
+    buf: Vec<Option<T>>,
+    // sequence number of the next item to be sent
+    tail: u64,
+    // read position of every live receiver, indexed by receiver id
+    cursors: Vec<Option<u64>>,
+    senders: usize,
+}
+
+impl<T> State<T> {
+    fn cap(&self) -> u64 {
+        self.buf.len() as u64
+    }
+
+    fn slowest(&self) -> Option<u64> {
+        self.cursors.iter().filter_map(|c| *c).min()
+    }
+
+    fn read(&mut self, id: usize) -> Result<T, TryRecvError>
+    where
+        T: Clone,
+    {
+        let pos = self.cursors[id].unwrap();
+        if self.tail - pos > self.cap() {
+            // only reachable in lossy mode
+            let oldest = self.tail - self.cap();
+            self.cursors[id] = Some(oldest);
+            return Err(TryRecvError::Lagged(oldest - pos));
+        }
+        if pos == self.tail {
+            if self.senders == 0 {
+                return Err(TryRecvError::Closed);
+            }
+            return Err(TryRecvError::Empty);
+        }
+        let idx = (pos % self.cap()) as usize;
+        let v = self.buf[idx].clone().unwrap();
+        self.cursors[id] = Some(pos + 1);
+        Ok(v)
+    }
+
+    fn add_receiver(&mut self, pos: u64) -> usize {
+        match self.cursors.iter().position(|c| c.is_none()) {
+            Some(id) => {
+                self.cursors[id] = Some(pos);
+                id
+            }
+            None => {
+                self.cursors.push(Some(pos));
+                self.cursors.len() - 1
+            }
+        }
+    }
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    id: usize,
+}
+
+impl<T: Clone> Sender<T> {
+    /// Fails only when every receiver is gone.
+    pub fn send(&self, item: T) -> Result<(), PushError<T>> {
+        let mut st = self.shared.lock();
+        loop {
+            let slowest = match st.slowest() {
+                Some(c) => c,
+                None => return Err(PushError::Closed(item)),
+            };
+            if self.shared.mode == Mode::Lossy || st.tail - slowest < st.cap() {
+                break;
+            }
+            blocked_span!(_blocked, "send");
+            st = self
+                .shared
+                .not_full
+                .wait(st)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        let idx = (st.tail % st.cap()) as usize;
+        st.buf[idx] = Some(item);
+        st.tail += 1;
+        drop(st);
+        self.shared.not_empty.notify_all();
+        Ok(())
+    }
+
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut st = self.shared.lock();
+        let pos = st.tail;
+        let id = st.add_receiver(pos);
+        Receiver {
+            shared: self.shared.clone(),
+            id,
+        }
+    }
+}
+
+impl<T: Clone> Receiver<T> {
+    /// Blocks until an item is available. Returns `Closed` once all senders
+    /// are gone and everything sent has been received.
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        let mut st = self.shared.lock();
+        loop {
+            match self.recv_locked(&mut st) {
+                Ok(v) => return Ok(v),
+                Err(TryRecvError::Lagged(n)) => return Err(RecvError::Lagged(n)),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Empty) => {}
+            }
+            blocked_span!(_blocked, "recv");
+            st = self
+                .shared
+                .not_empty
+                .wait(st)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut st = self.shared.lock();
+        self.recv_locked(&mut st)
+    }
+
+    /// A new receiver that only sees items sent from now on.
+    pub fn resubscribe(&self) -> Receiver<T> {
+        let mut st = self.shared.lock();
+        let pos = st.tail;
+        let id = st.add_receiver(pos);
+        Receiver {
+            shared: self.shared.clone(),
+            id,
+        }
+    }
+
+    fn recv_locked(&self, st: &mut State<T>) -> Result<T, TryRecvError> {
+        let v = st.read(self.id)?;
+        if self.shared.mode == Mode::Backpressure {
+            self.shared.not_full.notify_all();
+        }
+        Ok(v)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.resubscribe()
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut st = self.shared.lock();
+        st.senders -= 1;
+        if st.senders == 0 {
+            drop(st);
+            self.shared.not_empty.notify_all();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.lock().cursors[self.id] = None;
+        self.shared.not_full.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn backpressure_full_sequence() {
+        let (tx, mut fast) = channel::<u32>(4);
+        let mut slow = fast.clone();
+
+        let h1 = thread::spawn(move || {
+            let mut v = vec![];
+            while let Ok(x) = fast.recv() {
+                v.push(x);
+            }
+            v
+        });
+        let h2 = thread::spawn(move || {
+            let mut v = vec![];
+            while let Ok(x) = slow.recv() {
+                if x % 10 == 0 {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                v.push(x);
+            }
+            v
+        });
+        for i in 0..100 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+
+        let expected: Vec<u32> = (0..100).collect();
+        assert_eq!(h1.join().unwrap(), expected);
+        assert_eq!(h2.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn lossy_lag() {
+        let (tx, mut rx) = lossy_channel::<u32>(4);
+        for i in 0..10 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Lagged(6)));
+        for i in 6..10 {
+            assert_eq!(rx.try_recv(), Ok(i));
+        }
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        drop(tx);
+        assert_eq!(rx.recv(), Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn resubscribe_sees_only_new() {
+        let (tx, mut rx) = channel::<u32>(4);
+        tx.send(1).unwrap();
+        let mut rx2 = rx.resubscribe();
+        tx.send(2).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx2.try_recv(), Ok(2));
+        assert_eq!(rx2.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn send_without_receivers() {
+        let (tx, rx) = channel::<u32>(2);
+        drop(rx);
+        assert_eq!(tx.send(5), Err(PushError::Closed(5)));
+    }
+}
@@ -0,0 +1,285 @@
+use alloc::collections::TryReserveError;
+use core::alloc::{Layout, LayoutError};
+use core::error::Error;
+use core::fmt;
+
+/// A push that didn't happen. Either way the item is handed back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushError<T> {
+    Full(T),
+    Closed(T),
+}
+
+impl<T> PushError<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            PushError::Full(item) | PushError::Closed(item) => item,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        match *self {
+            PushError::Full(_) => true,
+            PushError::Closed(_) => false,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        !self.is_full()
+    }
+}
+
+impl<T> fmt::Display for PushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PushError::Full(_) => f.write_str("push to a full queue"),
+            PushError::Closed(_) => f.write_str("push to a closed queue"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for PushError<T> {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopError {
+    Empty,
+    Closed,
+}
+
+impl fmt::Display for PopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PopError::Empty => f.write_str("pop from an empty queue"),
+            PopError::Closed => f.write_str("pop from a closed and drained queue"),
+        }
+    }
+}
+
+impl Error for PopError {}
+
+/// A blocking receive that gave up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    Timeout,
+    Closed,
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RecvTimeoutError::Timeout => f.write_str("timed out waiting for an item"),
+            RecvTimeoutError::Closed => f.write_str("queue closed while waiting for an item"),
+        }
+    }
+}
+
+impl Error for RecvTimeoutError {}
+
+/// Returned by a blocking broadcast receive. `Lagged(n)` means the
+/// receiver fell behind a lossy channel and missed `n` items; it can keep
+/// receiving from the oldest item still held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    Closed,
+    Lagged(u64),
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RecvError::Closed => f.write_str("channel closed"),
+            RecvError::Lagged(n) => write!(f, "receiver lagged behind and missed {} items", n),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Closed,
+    Lagged(u64),
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TryRecvError::Empty => f.write_str("channel empty"),
+            TryRecvError::Closed => RecvError::Closed.fmt(f),
+            TryRecvError::Lagged(n) => RecvError::Lagged(n).fmt(f),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+impl From<RecvError> for TryRecvError {
+    fn from(e: RecvError) -> Self {
+        match e {
+            RecvError::Closed => TryRecvError::Closed,
+            RecvError::Lagged(n) => TryRecvError::Lagged(n),
+        }
+    }
+}
+
+/// A buffer that can't be allocated as asked: the capacity arithmetic
+/// overflows, the alignment is not a power of two, or the allocator failed.
+/// Also returned when an all-or-nothing insert doesn't fit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapacityError {
+    kind: CapacityErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CapacityErrorKind {
+    Overflow,
+    Layout(LayoutError),
+    Alloc(Layout),
+    Reserve(TryReserveError),
+    Full { needed: usize, free: usize },
+}
+
+impl CapacityError {
+    pub(crate) fn overflow() -> Self {
+        CapacityError {
+            kind: CapacityErrorKind::Overflow,
+        }
+    }
+
+    pub(crate) fn alloc(layout: Layout) -> Self {
+        CapacityError {
+            kind: CapacityErrorKind::Alloc(layout),
+        }
+    }
+
+    pub(crate) fn full(needed: usize, free: usize) -> Self {
+        CapacityError {
+            kind: CapacityErrorKind::Full { needed, free },
+        }
+    }
+
+    // the layout the allocator refused, if that is what went wrong
+    pub(crate) fn alloc_layout(&self) -> Option<Layout> {
+        match self.kind {
+            CapacityErrorKind::Alloc(layout) => Some(layout),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            CapacityErrorKind::Overflow => f.write_str("capacity overflow"),
+            CapacityErrorKind::Layout(_) => {
+                f.write_str("invalid capacity or alignment for queue buffer")
+            }
+            CapacityErrorKind::Alloc(layout) => {
+                write!(f, "allocation of {} bytes failed", layout.size())
+            }
+            CapacityErrorKind::Reserve(ref e) => e.fmt(f),
+            CapacityErrorKind::Full { needed, free } => write!(
+                f,
+                "no room for {} more elements, only {} free",
+                needed, free
+            ),
+        }
+    }
+}
+
+impl Error for CapacityError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            CapacityErrorKind::Layout(ref e) => Some(e),
+            CapacityErrorKind::Reserve(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<LayoutError> for CapacityError {
+    fn from(e: LayoutError) -> Self {
+        CapacityError {
+            kind: CapacityErrorKind::Layout(e),
+        }
+    }
+}
+
+impl From<TryReserveError> for CapacityError {
+    fn from(e: TryReserveError) -> Self {
+        CapacityError {
+            kind: CapacityErrorKind::Reserve(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bound::BoundQueue;
+
+    #[test]
+    fn display() {
+        assert_eq!(PushError::Full(1).to_string(), "push to a full queue");
+        assert_eq!(PushError::Closed(1).to_string(), "push to a closed queue");
+        assert_eq!(PopError::Empty.to_string(), "pop from an empty queue");
+        assert_eq!(
+            PopError::Closed.to_string(),
+            "pop from a closed and drained queue"
+        );
+        assert_eq!(
+            RecvTimeoutError::Timeout.to_string(),
+            "timed out waiting for an item"
+        );
+        assert_eq!(
+            RecvError::Lagged(3).to_string(),
+            "receiver lagged behind and missed 3 items"
+        );
+        assert_eq!(TryRecvError::Closed.to_string(), "channel closed");
+        assert_eq!(TryRecvError::Empty.to_string(), "channel empty");
+
+        let e = BoundQueue::<u8>::new_aligned(4, 3).err().unwrap();
+        assert_eq!(
+            e.to_string(),
+            "invalid capacity or alignment for queue buffer"
+        );
+        assert!(e.source().is_some());
+        assert_eq!(CapacityError::overflow().to_string(), "capacity overflow");
+        let layout = Layout::from_size_align(4096, 8).unwrap();
+        assert_eq!(
+            CapacityError::alloc(layout).to_string(),
+            "allocation of 4096 bytes failed"
+        );
+    }
+
+    #[test]
+    fn recover_item() {
+        let e = PushError::Full(String::from("a"));
+        assert!(e.is_full());
+        assert_eq!(e.into_inner(), "a");
+        let e = PushError::Closed(vec![1, 2]);
+        assert!(e.is_closed());
+        assert_eq!(e.into_inner(), vec![1, 2]);
+
+        let mut q = BoundQueue::new(1);
+        q.insert_sorted(1).unwrap();
+        assert_eq!(q.insert_sorted(2).map_err(PushError::into_inner), Err(2));
+    }
+
+    #[test]
+    fn boxed() {
+        fn fill(q: &mut BoundQueue<i32>) -> Result<(), Box<dyn Error>> {
+            for i in 0..3 {
+                q.insert_sorted(i)?;
+            }
+            Ok(())
+        }
+        let e = fill(&mut BoundQueue::new(2)).unwrap_err();
+        assert_eq!(e.to_string(), "push to a full queue");
+        assert_eq!(
+            TryRecvError::from(RecvError::Lagged(2)),
+            TryRecvError::Lagged(2)
+        );
+    }
+}
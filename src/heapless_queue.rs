@@ -0,0 +1,202 @@
+use super::{Queue, TryQueue};
+use error::PushError;
+use heapless::spsc;
+use heapless::Deque;
+
+/// Drives a `heapless::spsc::Queue` through the Queue trait. Like a full
+/// BoundQueue, a full heapless queue drops the pushed item; use `try_push`
+/// to get it back. Note that `spsc::Queue<T, N>` holds `N - 1` elements.
+pub struct HeaplessQueue<T, const N: usize>(spsc::Queue<T, N>);
+
+/// Drives a `heapless::Deque` through the Queue trait, pushing at the back
+/// and popping at the front.
+pub struct HeaplessDeque<T, const N: usize>(Deque<T, N>);
+
+impl<T, const N: usize> HeaplessQueue<T, N> {
+    pub fn new() -> Self {
+        HeaplessQueue(spsc::Queue::new())
+    }
+
+    pub fn from_inner(q: spsc::Queue<T, N>) -> Self {
+        HeaplessQueue(q)
+    }
+
+    /// The wrapped queue, e.g. to `split` it for use from an interrupt.
+    pub fn inner_mut(&mut self) -> &mut spsc::Queue<T, N> {
+        &mut self.0
+    }
+
+    pub fn into_inner(self) -> spsc::Queue<T, N> {
+        self.0
+    }
+
+    pub fn try_push(&mut self, item: T) -> Result<(), T> {
+        self.0.enqueue(item)
+    }
+
+    pub fn cap(&self) -> usize {
+        self.0.capacity()
+    }
+}
+
+impl<T, const N: usize> HeaplessDeque<T, N> {
+    pub fn new() -> Self {
+        HeaplessDeque(Deque::new())
+    }
+
+    pub fn from_inner(q: Deque<T, N>) -> Self {
+        HeaplessDeque(q)
+    }
+
+    pub fn inner_mut(&mut self) -> &mut Deque<T, N> {
+        &mut self.0
+    }
+
+    pub fn into_inner(self) -> Deque<T, N> {
+        self.0
+    }
+
+    pub fn try_push(&mut self, item: T) -> Result<(), T> {
+        self.0.push_back(item)
+    }
+
+    pub fn cap(&self) -> usize {
+        self.0.capacity()
+    }
+}
+
+impl<T, const N: usize> Default for HeaplessQueue<T, N> {
+    fn default() -> Self {
+        HeaplessQueue::new()
+    }
+}
+
+impl<T, const N: usize> Default for HeaplessDeque<T, N> {
+    fn default() -> Self {
+        HeaplessDeque::new()
+    }
+}
+
+impl<T, const N: usize> Queue<T> for HeaplessQueue<T, N> {
+    fn push(&mut self, item: T) {
+        if self.try_push(item).is_err() {
+            trace!(warn, cap = self.cap(), "push rejected, queue full");
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.0.dequeue()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T, const N: usize> TryQueue<T> for HeaplessQueue<T, N> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        HeaplessQueue::try_push(self, item).map_err(PushError::Full)
+    }
+}
+
+impl<T, const N: usize> Queue<T> for HeaplessDeque<T, N> {
+    fn push(&mut self, item: T) {
+        if self.try_push(item).is_err() {
+            trace!(warn, cap = self.cap(), "push rejected, queue full");
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T, const N: usize> TryQueue<T> for HeaplessDeque<T, N> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        HeaplessDeque::try_push(self, item).map_err(PushError::Full)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bound::BoundQueue;
+
+    // The same checks run against BoundQueue and each adapter; `cap` is how
+    // many elements the queue holds.
+    fn suite<Q: Queue<i32>>(mut make: impl FnMut() -> Q, cap: usize) {
+        let cap = cap as i32;
+
+        let mut q = make();
+        assert!(q.is_empty());
+        assert_eq!(q.pop(), None);
+
+        // fill past capacity: the extra pushes are dropped
+        for i in 0..cap + 2 {
+            q.push(i);
+        }
+        let mut v = vec![];
+        while let Some(x) = q.pop() {
+            v.push(x);
+        }
+        assert_eq!(v, (0..cap).collect::<Vec<_>>());
+        assert!(q.is_empty());
+
+        // interleave across the wrap
+        let mut q = make();
+        // one value comes out per round, so `round` is the next one due
+        let mut next_in = 0;
+        for round in 0..5 * cap {
+            for _ in 0..1 + round % 2 {
+                q.push(next_in);
+                // a rejected value is offered again next time
+                if next_in - round < cap {
+                    next_in += 1;
+                }
+            }
+            assert_eq!(q.pop(), Some(round));
+        }
+    }
+
+    #[test]
+    fn bound_queue_suite() {
+        suite(|| BoundQueue::new(4), 4);
+    }
+
+    #[test]
+    fn heapless_queue_suite() {
+        suite(HeaplessQueue::<_, 5>::new, 4);
+        assert_eq!(HeaplessQueue::<i32, 5>::new().cap(), 4);
+    }
+
+    #[test]
+    fn heapless_deque_suite() {
+        suite(HeaplessDeque::<_, 4>::new, 4);
+    }
+
+    #[test]
+    fn try_push_full() {
+        let mut q = HeaplessQueue::<_, 3>::new();
+        assert_eq!(q.try_push(1), Ok(()));
+        assert_eq!(q.try_push(2), Ok(()));
+        assert_eq!(q.try_push(3), Err(3));
+
+        let mut d = HeaplessDeque::<_, 2>::new();
+        assert_eq!(d.try_push("a"), Ok(()));
+        assert_eq!(d.try_push("b"), Ok(()));
+        assert_eq!(d.try_push("c"), Err("c"));
+        assert_eq!(d.pop(), Some("a"));
+        assert_eq!(d.into_inner().len(), 1);
+
+        // the split halves still see what went through the adapter
+        let (mut tx, mut rx) = q.inner_mut().split();
+        assert_eq!(rx.dequeue(), Some(1));
+        assert_eq!(tx.enqueue(4), Ok(()));
+        assert_eq!(q.pop(), Some(2));
+        assert_eq!(q.pop(), Some(4));
+    }
+}
@@ -1,13 +1,85 @@
-#![feature(box_into_raw_non_null)]
-#![feature(alloc, raw_vec_internals)]
+// Without the default `std` feature only `core` and `alloc` are used, and
+// the modules that block, keep time or wrap std channels are left out.
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg_attr(not(feature = "std"), macro_use)]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "heapless")]
+extern crate heapless;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "async-interop")]
+extern crate tokio;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(all(test, feature = "serde"))]
+extern crate bincode;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 pub trait Queue<T> {
     fn push(&mut self, item: T);
     fn pop(&mut self) -> Option<T>;
-    fn is_empty(&self) -> bool;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A `Queue` whose pushes say whether the item went in. `push` on a full
+/// bounded queue drops the item; `try_push` hands it back instead.
+pub trait TryQueue<T>: Queue<T> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>>;
+}
+
+/// Marks a queue whose `push` never turns an item away, making it a
+/// `TryQueue` whose `try_push` always succeeds.
+pub trait Unbounded {}
+
+impl<T, Q: Queue<T> + Unbounded> TryQueue<T> for Q {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        self.push(item);
+        Ok(())
+    }
 }
 
+#[macro_use]
+mod trace;
+
+pub mod adapters;
+pub mod array;
+pub mod bit;
 pub mod bound;
+pub mod bound_ref;
+#[cfg(feature = "std")]
+pub mod broadcast;
+pub mod error;
+#[cfg(feature = "heapless")]
+pub mod heapless_queue;
 pub mod unbound;
+pub mod merge;
+#[cfg(feature = "std")]
+pub mod mpsc;
+pub mod multi;
+pub mod persistent;
+#[cfg(feature = "std")]
+pub mod pool;
+pub mod priority;
+mod raw;
+pub mod seg;
+pub mod seqring;
+pub mod spsc;
+#[cfg(feature = "std")]
+pub mod sync;
+pub mod ticket;
+#[cfg(feature = "std")]
+pub mod timestamped;
+#[cfg(feature = "async-interop")]
+pub mod tokio_mpsc;
+pub mod watermark;
+
+use error::PushError;
+pub use merge::{chain, chain_drain, round_robin, RoundRobin};
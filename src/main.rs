@@ -1,14 +1,298 @@
 extern crate queue;
-use queue::*;
 
-fn main(){
-    let   q1  = bound::BoundQueue::<i32>::new(10);
-    push_pop(q1, 432);
-    let   q2  = unbound::UnboundQueue::<&str>::new();
-    push_pop(q2, "hello");
+use queue::bound::BoundQueue;
+use queue::error::PushError;
+use queue::mpsc::MpscQueue;
+use queue::seg::SegQueue;
+use queue::unbound::UnboundQueue;
+use queue::{Queue, TryQueue, Unbounded};
+use std::collections::VecDeque;
+use std::env;
+use std::process;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "usage:
+    queue bench [--impl NAME|all] [--ops N] [--capacity N] [--pattern pingpong|burst]
+    queue demo
+    queue soak [--impl NAME|all] [--seconds N] [--capacity N]";
+
+// Every implementation selectable with --impl. The second value is the
+// number of items the queue will hold before it starts dropping pushes,
+// if it is bounded.
+type Make = fn(usize) -> (Box<dyn TryQueue<u64>>, Option<usize>);
+
+const IMPLS: &[(&str, Make)] = &[
+    ("bound", |cap| (Box::new(BoundQueue::new(cap)), Some(cap))),
+    ("pow2", |cap| {
+        let cap = cap.next_power_of_two();
+        let q = BoundQueue::new_pow2(cap.trailing_zeros());
+        (Box::new(q), Some(cap))
+    }),
+    ("unbound", |_| (Box::new(UnboundQueue::new()), None)),
+    ("seg", |_| (Box::new(SegQueue::new()), None)),
+    ("mpsc", |_| (Box::new(MpscQueue::new()), None)),
+    ("vecdeque", |cap| {
+        (Box::new(Deque(VecDeque::with_capacity(cap))), None)
+    }),
+];
+
+struct Deque(VecDeque<u64>);
+
+impl Unbounded for Deque {}
+
+impl Queue<u64> for Deque {
+    fn push(&mut self, item: u64) {
+        self.0.push_back(item);
+    }
+
+    fn pop(&mut self) -> Option<u64> {
+        self.0.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+struct Args(Vec<String>);
+
+impl Args {
+    fn get(&self, name: &str) -> Option<&str> {
+        let i = self.0.iter().position(|a| a == name)?;
+        match self.0.get(i + 1) {
+            Some(v) => Some(v),
+            None => fail(&format!("{} needs a value", name)),
+        }
+    }
+
+    fn num(&self, name: &str, default: u64) -> u64 {
+        match self.get(name) {
+            None => default,
+            Some(v) => v
+                .parse()
+                .unwrap_or_else(|_| fail(&format!("{}: not a number: {}", name, v))),
+        }
+    }
+
+    fn impls(&self) -> Vec<(&'static str, Make)> {
+        match self.get("--impl").unwrap_or("all") {
+            "all" => IMPLS.to_vec(),
+            name => match IMPLS.iter().find(|i| i.0 == name) {
+                Some(&i) => vec![i],
+                None => {
+                    let names: Vec<_> = IMPLS.iter().map(|i| i.0).collect();
+                    fail(&format!(
+                        "unknown impl {}, expected one of: {}",
+                        name,
+                        names.join(", ")
+                    ))
+                }
+            },
+        }
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    process::exit(2)
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        fail("missing command");
+    }
+    let cmd = args.remove(0);
+    let args = Args(args);
+    match &cmd[..] {
+        "bench" => bench(&args),
+        "demo" => demo(),
+        "soak" => {
+            if !soak(&args) {
+                process::exit(1);
+            }
+        }
+        _ => fail(&format!("unknown command {}", cmd)),
+    }
+}
+
+fn bench(args: &Args) {
+    let ops = args.num("--ops", 1_000_000);
+    let cap = args.num("--capacity", 1024) as usize;
+    let pattern = args.get("--pattern").unwrap_or("pingpong");
+    if cap == 0 {
+        fail("--capacity must be non-zero");
+    }
+    for (name, make) in args.impls() {
+        let (mut q, _) = make(cap);
+        let start = Instant::now();
+        let sum = match pattern {
+            "pingpong" => pingpong(&mut *q, ops),
+            "burst" => burst(&mut *q, ops, cap as u64),
+            _ => fail(&format!("unknown pattern {}", pattern)),
+        };
+        let secs = start.elapsed().as_secs_f64();
+        println!(
+            "{:>9} {}: {} ops in {:.3}s, {:.1} Mops/s (checksum {})",
+            name,
+            pattern,
+            ops,
+            secs,
+            ops as f64 / secs / 1e6,
+            sum
+        );
+    }
+}
+
+// one push followed by one pop, `ops` times
+fn pingpong(q: &mut dyn Queue<u64>, ops: u64) -> u64 {
+    let mut sum = 0u64;
+    for i in 0..ops {
+        q.push(i);
+        sum = sum.wrapping_add(q.pop().unwrap_or(0));
+    }
+    sum
+}
+
+// fill to capacity, then drain, until `ops` items went through
+fn burst(q: &mut dyn Queue<u64>, ops: u64, cap: u64) -> u64 {
+    let mut sum = 0u64;
+    let mut done = 0;
+    while done < ops {
+        let n = cap.min(ops - done);
+        for i in 0..n {
+            q.push(i);
+        }
+        while let Some(v) = q.pop() {
+            sum = sum.wrapping_add(v);
+        }
+        done += n;
+    }
+    sum
+}
+
+// pushes `items` until the queue turns one away, returning how many went
+// in and the first one that didn't
+fn fill<Q: TryQueue<u64> + ?Sized>(q: &mut Q, items: &[u64]) -> (usize, Option<u64>) {
+    for (n, &item) in items.iter().enumerate() {
+        match q.try_push(item) {
+            Ok(()) => {}
+            Err(PushError::Full(item)) | Err(PushError::Closed(item)) => return (n, Some(item)),
+        }
+    }
+    (items.len(), None)
+}
+
+fn demo() {
+    let items: Vec<u64> = (0..6).map(|i| i * 10).collect();
+    for &(name, make) in IMPLS {
+        let (mut q, bound) = make(4);
+        let (pushed, rejected) = fill(&mut *q, &items);
+        let mut popped = vec![];
+        while let Some(v) = q.pop() {
+            popped.push(v);
+        }
+        match (bound, rejected) {
+            (Some(cap), Some(first)) => println!(
+                "{:>9}: capacity {} took {} of 6, turned away {} first, popped {:?}",
+                name, cap, pushed, first, popped
+            ),
+            _ => println!("{:>9}: took all 6, popped {:?}", name, popped),
+        }
+    }
+}
+
+// Runs random operations on every selected queue for `--seconds` each,
+// checking it against a VecDeque model. Returns false on any mismatch.
+fn soak(args: &Args) -> bool {
+    let seconds = args.num("--seconds", 30);
+    let cap = args.num("--capacity", 100) as usize;
+    if cap == 0 {
+        fail("--capacity must be non-zero");
+    }
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(1)
+        | 1;
+    println!("seed {:#x}", seed);
+    let mut ok = true;
+    for (name, make) in args.impls() {
+        let (mut q, bound) = make(cap);
+        let mut model = VecDeque::new();
+        let mut next = rng(seed);
+        let deadline = Instant::now() + Duration::from_secs(seconds);
+        let start = Instant::now();
+        let mut ops = 0u64;
+        let mut failure = None;
+        while failure.is_none() && Instant::now() < deadline {
+            // alternate between mostly pushing and mostly popping so the
+            // queue regularly runs both full and empty
+            let filling = (ops / 5000) & 1 == 0;
+            for _ in 0..1000 {
+                failure = step(&mut *q, &mut model, bound, filling, &mut next);
+                ops += 1;
+                if failure.is_some() {
+                    break;
+                }
+            }
+        }
+        let secs = start.elapsed().as_secs_f64();
+        match failure {
+            None => println!(
+                "{:>9}: {} ops in {:.1}s, {:.1} Mops/s, ok",
+                name,
+                ops,
+                secs,
+                ops as f64 / secs / 1e6
+            ),
+            Some(msg) => {
+                println!("{:>9}: FAILED after {} ops: {}", name, ops, msg);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+fn step(
+    q: &mut dyn TryQueue<u64>,
+    model: &mut VecDeque<u64>,
+    bound: Option<usize>,
+    filling: bool,
+    next: &mut dyn FnMut() -> u64,
+) -> Option<String> {
+    let r = next() % 8;
+    if (filling && r < 5) || (!filling && r < 3) {
+        let v = next();
+        let room = bound.is_none_or(|cap| model.len() < cap);
+        match q.try_push(v) {
+            Ok(()) if room => model.push_back(v),
+            Err(PushError::Full(_)) if !room => {}
+            r => return Some(format!("push with room={} returned {:?}", room, r)),
+        }
+    } else {
+        let got = q.pop();
+        let want = model.pop_front();
+        if got != want {
+            return Some(format!("pop returned {:?}, expected {:?}", got, want));
+        }
+    }
+    if q.is_empty() != model.is_empty() {
+        return Some(format!(
+            "is_empty is {} with {} items queued",
+            q.is_empty(),
+            model.len()
+        ));
+    }
+    None
 }
 
-fn  push_pop<Q,T>(mut q: Q, item :T) where Q:Queue<T>, T: std::fmt::Debug {
-    q.push(item);
-    println!("{:?}", q.pop())
+fn rng(mut x: u64) -> impl FnMut() -> u64 {
+    move || {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        x
+    }
 }
@@ -0,0 +1,313 @@
+use super::Queue;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::iter;
+use core::marker::PhantomData;
+
+/// A queue that can be looked through front to back without popping, so
+/// `chain` and `chain_drain` work across queue types.
+pub trait Walk<T>: Queue<T> {
+    fn walk<'a>(&'a self) -> Refs<'a, T>;
+
+    /// The front element, without popping it.
+    fn peek(&self) -> Option<&T> {
+        self.walk().next()
+    }
+}
+
+pub type Refs<'a, T> = Box<dyn Iterator<Item = &'a T> + 'a>;
+
+/// Iterates `a` then `b` by reference, as if they were one queue.
+pub fn chain<'a, T, A, B>(a: &'a A, b: &'a B) -> Chain<'a, T>
+where
+    A: Walk<T> + ?Sized,
+    B: Walk<T> + ?Sized,
+{
+    Chain {
+        inner: a.walk().chain(b.walk()),
+        len: a.len() + b.len(),
+    }
+}
+
+/// Pops everything from `a`, then everything from `b`. Items pushed to
+/// either queue while draining are picked up too.
+pub fn chain_drain<'a, T, A, B>(a: &'a mut A, b: &'a mut B) -> ChainDrain<'a, T, A, B>
+where
+    A: Walk<T> + ?Sized,
+    B: Walk<T> + ?Sized,
+{
+    ChainDrain {
+        a,
+        b,
+        marker: PhantomData,
+    }
+}
+
+pub struct Chain<'a, T: 'a> {
+    inner: iter::Chain<Refs<'a, T>, Refs<'a, T>>,
+    len: usize,
+}
+
+pub struct ChainDrain<'a, T, A: ?Sized + 'a, B: ?Sized + 'a> {
+    a: &'a mut A,
+    b: &'a mut B,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> Iterator for Chain<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        let v = self.inner.next()?;
+        self.len -= 1;
+        Some(v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Chain<'a, T> {}
+
+impl<'a, T, A, B> Iterator for ChainDrain<'a, T, A, B>
+where
+    A: Walk<T> + ?Sized,
+    B: Walk<T> + ?Sized,
+{
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.a.pop() {
+            Some(v) => Some(v),
+            None => self.b.pop(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.a.len() + self.b.len();
+        (n, Some(n))
+    }
+}
+
+/// Pops from each non-empty queue in turn, skipping empty ones.
+pub fn round_robin<'a, T>(queues: Vec<&'a mut dyn Queue<T>>) -> RoundRobin<'a, T> {
+    RoundRobin {
+        queues,
+        pos: 0,
+        streaming: false,
+        done: false,
+    }
+}
+
+pub struct RoundRobin<'a, T: 'a> {
+    queues: Vec<&'a mut dyn Queue<T>>,
+    pos: usize,
+    streaming: bool,
+    done: bool,
+}
+
+impl<'a, T> RoundRobin<'a, T> {
+    /// Keep yielding after a pass where every queue was empty, so items
+    /// pushed later through `queue_mut` are picked up by `by_ref` loops.
+    pub fn streaming(mut self) -> Self {
+        self.streaming = true;
+        self
+    }
+
+    pub fn queue_mut(&mut self, idx: usize) -> &mut dyn Queue<T> {
+        &mut *self.queues[idx]
+    }
+}
+
+impl<'a, T> Iterator for RoundRobin<'a, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let n = self.queues.len();
+        for _ in 0..n {
+            let i = self.pos;
+            self.pos = if i + 1 == n { 0 } else { i + 1 };
+            if let Some(v) = self.queues[i].pop() {
+                return Some(v);
+            }
+        }
+        if !self.streaming {
+            self.done = true;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bound::BoundQueue;
+    use seg::SegQueue;
+    use unbound::UnboundQueue;
+
+    #[test]
+    fn interleave() {
+        let mut a = BoundQueue::<i32>::new(10);
+        let mut b = UnboundQueue::<i32>::new();
+        let mut c = UnboundQueue::<i32>::new();
+        for i in 0..4 {
+            a.push(i);
+        }
+        b.push(10);
+        for i in 20..22 {
+            c.push(i);
+        }
+
+        let v: Vec<_> = round_robin(vec![&mut a, &mut b, &mut c]).collect();
+        assert_eq!(v, vec![0, 10, 20, 1, 21, 2, 3]);
+        assert!(a.is_empty() && b.is_empty() && c.is_empty());
+    }
+
+    #[test]
+    fn no_starvation() {
+        let mut a = UnboundQueue::<i32>::new();
+        let mut b = UnboundQueue::<i32>::new();
+        b.push(-1);
+        let mut rr = round_robin(vec![&mut a, &mut b]);
+        rr.queue_mut(0).push(0);
+        assert_eq!(rr.next(), Some(0));
+        rr.queue_mut(0).push(1);
+        assert_eq!(rr.next(), Some(-1));
+        assert_eq!(rr.next(), Some(1));
+        assert_eq!(rr.next(), None);
+    }
+
+    #[test]
+    fn total_conservation() {
+        let mut qs: Vec<UnboundQueue<usize>> = (0..5).map(|_| UnboundQueue::new()).collect();
+        let mut expected = 0;
+        for (k, q) in qs.iter_mut().enumerate() {
+            for i in 0..k * 3 {
+                q.push(i);
+                expected += i;
+            }
+        }
+        let refs: Vec<&mut dyn Queue<usize>> = qs.iter_mut().map(|q| q as &mut dyn Queue<usize>).collect();
+        let mut count = 0;
+        let mut sum = 0;
+        for v in round_robin(refs) {
+            count += 1;
+            sum += v;
+        }
+        assert_eq!(count, 3 + 6 + 9 + 12);
+        assert_eq!(sum, expected);
+    }
+
+    #[test]
+    fn streaming() {
+        let mut a = UnboundQueue::<i32>::new();
+        let mut rr = round_robin(vec![&mut a]).streaming();
+        assert_eq!(rr.next(), None);
+        rr.queue_mut(0).push(7);
+        assert_eq!(rr.by_ref().next(), Some(7));
+        assert_eq!(rr.next(), None);
+
+        let mut b = UnboundQueue::<i32>::new();
+        let mut rr = round_robin(vec![&mut b]);
+        assert_eq!(rr.next(), None);
+        rr.queue_mut(0).push(7);
+        assert_eq!(rr.next(), None);
+    }
+
+    #[test]
+    fn chain_seam() {
+        let mut a = BoundQueue::new(4);
+        let mut b = UnboundQueue::new();
+        // wrap `a` so its elements straddle the end of the buffer
+        for i in 0..3 {
+            a.push(i);
+        }
+        for _ in 0..3 {
+            a.pop();
+        }
+        for i in 0..4 {
+            a.push(i);
+        }
+        for i in 4..7 {
+            b.push(i);
+        }
+
+        let it = chain(&a, &b);
+        assert_eq!(it.len(), 7);
+        assert_eq!(it.cloned().collect::<Vec<_>>(), (0..7).collect::<Vec<_>>());
+        let mut it = chain(&a, &b);
+        for left in (0..7).rev() {
+            it.next().unwrap();
+            assert_eq!(it.size_hint(), (left, Some(left)));
+        }
+        assert_eq!(it.next(), None);
+
+        let empty = SegQueue::new();
+        assert_eq!(
+            chain(&empty, &b).cloned().collect::<Vec<_>>(),
+            vec![4, 5, 6]
+        );
+        assert_eq!(chain(&a, &empty).count(), 4);
+        assert_eq!(chain(&empty, &empty).next(), None);
+        // looking doesn't consume
+        assert!(!a.is_empty() && !b.is_empty());
+    }
+
+    #[test]
+    fn chain_dyn() {
+        let mut a = UnboundQueue::new();
+        let mut b = BoundQueue::new(2);
+        a.push("a");
+        b.push("b");
+        let qs: [&dyn Walk<&str>; 2] = [&a, &b];
+        assert_eq!(chain(qs[0], qs[1]).cloned().collect::<Vec<_>>(), ["a", "b"]);
+    }
+
+    // takes items off the front only while they are below `limit`
+    fn drain_below<Q: Walk<i32>>(q: &mut Q, limit: i32) -> Vec<i32> {
+        let mut v = vec![];
+        while q.peek().is_some_and(|&x| x < limit) {
+            v.push(q.pop().unwrap());
+        }
+        v
+    }
+
+    #[test]
+    fn walk_peek() {
+        let mut a = BoundQueue::new(4);
+        let mut b = UnboundQueue::new();
+        let mut c = SegQueue::new();
+        for &i in &[1, 3, 5, 2] {
+            a.push(i);
+            b.push(i);
+            c.push(i);
+        }
+        assert_eq!(drain_below(&mut a, 4), [1, 3]);
+        assert_eq!(drain_below(&mut b, 4), [1, 3]);
+        assert_eq!(drain_below(&mut c, 4), [1, 3]);
+        assert_eq!(Walk::peek(&c), Some(&5));
+        assert_eq!(drain_below(&mut c, 9), [5, 2]);
+        assert_eq!(Walk::peek(&c), None);
+    }
+
+    #[test]
+    fn chain_drain_empties_both() {
+        let mut a = BoundQueue::new(3);
+        let mut b = SegQueue::new();
+        for i in 0..3 {
+            a.push(i);
+        }
+        for i in 3..100 {
+            b.push(i);
+        }
+
+        let mut d = chain_drain(&mut a, &mut b);
+        assert_eq!(d.size_hint(), (100, Some(100)));
+        assert_eq!(d.by_ref().take(5).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(d.size_hint(), (95, Some(95)));
+        assert_eq!(d.collect::<Vec<_>>(), (5..100).collect::<Vec<_>>());
+        assert!(a.is_empty() && b.is_empty());
+        assert_eq!(chain_drain(&mut a, &mut b).next(), None);
+    }
+}
@@ -0,0 +1,187 @@
+use super::{Queue, TryQueue};
+use error::PushError;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Drives a std channel through the Queue trait. `is_empty` and `len` have
+/// to receive to find out, so elements they pull off the channel are parked
+/// in `peeked` and handed out by the next pops. `len` counts what has been
+/// sent so far.
+pub struct MpscQueue<T> {
+    tx: Option<Sender<T>>,
+    rx: Receiver<T>,
+    peeked: RefCell<VecDeque<T>>,
+}
+
+impl<T> MpscQueue<T> {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        MpscQueue::from_channel(tx, rx)
+    }
+
+    pub fn from_channel(tx: Sender<T>, rx: Receiver<T>) -> Self {
+        MpscQueue {
+            tx: Some(tx),
+            rx,
+            peeked: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// A consume-only queue: pushes are dropped, pops take whatever the
+    /// senders elsewhere have sent.
+    pub fn from_receiver(rx: Receiver<T>) -> Self {
+        MpscQueue {
+            tx: None,
+            rx,
+            peeked: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    pub fn sender(&self) -> Option<&Sender<T>> {
+        self.tx.as_ref()
+    }
+}
+
+impl<T> Default for MpscQueue<T> {
+    fn default() -> Self {
+        MpscQueue::new()
+    }
+}
+
+impl<T> Queue<T> for MpscQueue<T> {
+    fn push(&mut self, item: T) {
+        // a `from_channel` sender may feed a receiver that is gone, in
+        // which case the item is dropped; `try_push` hands it back
+        if let Some(ref tx) = self.tx {
+            let _ = tx.send(item);
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        match self.peeked.get_mut().pop_front() {
+            Some(v) => Some(v),
+            None => self.rx.try_recv().ok(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        let mut peeked = self.peeked.borrow_mut();
+        peeked.extend(self.rx.try_iter());
+        peeked.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        let mut peeked = self.peeked.borrow_mut();
+        if peeked.is_empty() {
+            peeked.extend(self.rx.try_recv().ok());
+        }
+        peeked.is_empty()
+    }
+}
+
+impl<T> TryQueue<T> for MpscQueue<T> {
+    /// Fails with `Closed` on a queue made by `from_receiver`, or one whose
+    /// sender's receiver was dropped.
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        match self.tx {
+            Some(ref tx) => tx.send(item).map_err(|e| PushError::Closed(e.0)),
+            None => Err(PushError::Closed(item)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn fill<Q: Queue<i32>>(q: &mut Q, items: &[i32]) {
+        for &i in items {
+            q.push(i);
+        }
+    }
+
+    fn drain<T, Q: Queue<T>>(q: &mut Q) -> Vec<T> {
+        let mut v = vec![];
+        while !q.is_empty() {
+            v.push(q.pop().unwrap());
+        }
+        v
+    }
+
+    #[test]
+    fn generic() {
+        let mut q = MpscQueue::new();
+        assert!(q.is_empty());
+        fill(&mut q, &[1, 2, 3]);
+        assert_eq!(drain(&mut q), vec![1, 2, 3]);
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn peek_keeps_order() {
+        let mut q = MpscQueue::new();
+        q.push(1);
+        assert!(!q.is_empty());
+        assert!(!q.is_empty());
+        q.push(2);
+        assert!(!q.is_empty());
+        assert_eq!(q.pop(), Some(1));
+        q.push(3);
+        assert!(!q.is_empty());
+        assert_eq!(q.pop(), Some(2));
+        assert_eq!(q.pop(), Some(3));
+        assert!(q.is_empty());
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn len_counts_sent() {
+        let mut q = MpscQueue::new();
+        let tx = q.sender().unwrap().clone();
+        assert_eq!(q.len(), 0);
+        q.push(1);
+        tx.send(2).unwrap();
+        assert!(!q.is_empty());
+        tx.send(3).unwrap();
+        assert_eq!(q.len(), 3);
+        assert_eq!(q.pop(), Some(1));
+        q.push(4);
+        assert_eq!(q.len(), 3);
+        assert_eq!(drain(&mut q), vec![2, 3, 4]);
+        assert_eq!(q.len(), 0);
+    }
+
+    #[test]
+    fn from_receiver() {
+        let (tx, rx) = mpsc::channel();
+        let mut q = MpscQueue::from_receiver(rx);
+        assert!(q.sender().is_none());
+        q.push(-1);
+        thread::spawn(move || {
+            for i in 0..5 {
+                tx.send(i).unwrap();
+            }
+        })
+        .join()
+        .unwrap();
+        assert_eq!(drain(&mut q), vec![0, 1, 2, 3, 4]);
+        // every sender is gone now
+        assert!(q.is_empty());
+        assert_eq!(q.pop(), None);
+        assert_eq!(q.try_push(5), Err(PushError::Closed(5)));
+    }
+
+    #[test]
+    fn dead_receiver() {
+        // the sender's own receiver is gone; the one held is unrelated
+        let (tx, rx) = mpsc::channel();
+        let (_other_tx, other_rx) = mpsc::channel();
+        drop(rx);
+        let mut q = MpscQueue::from_channel(tx, other_rx);
+        assert_eq!(q.try_push(1), Err(PushError::Closed(1)));
+        q.push(2);
+        assert!(q.is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use super::{Queue, TryQueue};
+use bound::BoundQueue;
+use error::PushError;
+
+/// A priority queue made of one bounded FIFO per level, so elements of the
+/// same level come out in the order they went in. Higher levels are served
+/// first.
+///
+/// By default draining is strict: a level is only popped from while every
+/// level above it is empty. `with_weights` switches to weighted round-robin,
+/// where each round hands out up to `weights[level]` pops per level, highest
+/// level first. Levels that run dry forfeit the rest of their share for that
+/// round, so no pop is wasted while anything is queued.
+pub struct MultiQueue<T, const LEVELS: usize> {
+    levels: [BoundQueue<T>; LEVELS],
+    default_level: usize,
+    weights: Option<[usize; LEVELS]>,
+    // pops left for each level in the current weighted round
+    credits: [usize; LEVELS],
+    // level the current weighted round has reached
+    cursor: usize,
+}
+
+impl<T, const LEVELS: usize> MultiQueue<T, LEVELS> {
+    /// Each level holds up to `cap` elements. `Queue::push` goes to level 0.
+    pub fn new(cap: usize) -> Self {
+        assert!(LEVELS > 0, "MultiQueue needs at least one level");
+        MultiQueue {
+            levels: [(); LEVELS].map(|_| BoundQueue::new(cap)),
+            default_level: 0,
+            weights: None,
+            credits: [0; LEVELS],
+            cursor: 0,
+        }
+    }
+
+    /// Drains by weighted round-robin instead of strictly. Panics if a
+    /// weight is zero.
+    pub fn with_weights(mut self, weights: [usize; LEVELS]) -> Self {
+        assert!(
+            weights.iter().all(|&w| w > 0),
+            "MultiQueue weights must be non-zero"
+        );
+        self.credits = weights;
+        self.cursor = LEVELS - 1;
+        self.weights = Some(weights);
+        self
+    }
+
+    /// The level `Queue::push` uses. Panics if it is out of range.
+    pub fn with_default_level(mut self, level: usize) -> Self {
+        assert!(level < LEVELS, "level {} out of range", level);
+        self.default_level = level;
+        self
+    }
+
+    /// Hands `item` back if its level is full. Panics if `level` is out of
+    /// range.
+    pub fn push(&mut self, level: usize, item: T) -> Result<(), T> {
+        self.levels[level].try_push(item)
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        match self.weights {
+            None => self.levels.iter_mut().rev().find_map(|q| q.pop()),
+            Some(weights) => self.pop_weighted(&weights),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.iter().map(|q| q.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(|q| q.is_empty())
+    }
+
+    pub fn level_len(&self, level: usize) -> usize {
+        self.levels[level].len()
+    }
+
+    fn pop_weighted(&mut self, weights: &[usize; LEVELS]) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        loop {
+            let level = self.cursor;
+            if self.credits[level] > 0 {
+                if let Some(item) = self.levels[level].pop() {
+                    self.credits[level] -= 1;
+                    return Some(item);
+                }
+            }
+            if level == 0 {
+                self.credits = *weights;
+                self.cursor = LEVELS - 1;
+            } else {
+                self.cursor = level - 1;
+            }
+        }
+    }
+}
+
+impl<T, const LEVELS: usize> Queue<T> for MultiQueue<T, LEVELS> {
+    /// Pushes to the default level, dropping `item` if that level is full.
+    fn push(&mut self, item: T) {
+        let level = self.default_level;
+        if MultiQueue::push(self, level, item).is_err() {
+            trace!(warn, level, "push rejected, level full");
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        MultiQueue::pop(self)
+    }
+
+    fn len(&self) -> usize {
+        MultiQueue::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        MultiQueue::is_empty(self)
+    }
+}
+
+impl<T, const LEVELS: usize> TryQueue<T> for MultiQueue<T, LEVELS> {
+    /// Pushes to the default level.
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        let level = self.default_level;
+        MultiQueue::push(self, level, item).map_err(PushError::Full)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fifo_within_level() {
+        let mut q = MultiQueue::<_, 3>::new(8).with_default_level(1);
+        for i in 0..6 {
+            q.push(i % 3, i).unwrap();
+        }
+        Queue::push(&mut q, 6);
+        Queue::push(&mut q, 7);
+        assert_eq!(q.len(), 8);
+        assert_eq!(q.level_len(1), 4);
+        let out: Vec<_> = (0..8).map(|_| q.pop().unwrap()).collect();
+        assert_eq!(out, [2, 5, 1, 4, 6, 7, 0, 3]);
+        assert!(q.is_empty());
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn push_full_level() {
+        let mut q = MultiQueue::<_, 2>::new(2);
+        q.push(1, 'a').unwrap();
+        q.push(1, 'b').unwrap();
+        assert_eq!(q.push(1, 'c'), Err('c'));
+        q.push(0, 'd').unwrap();
+        Queue::push(&mut q, 'e');
+        Queue::push(&mut q, 'f');
+        assert_eq!(q.len(), 4);
+    }
+
+    #[test]
+    fn strict_order() {
+        let mut q = MultiQueue::<_, 4>::new(64);
+        let mut seed = 7u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as usize
+        };
+        for round in 0..200 {
+            for _ in 0..next() % 4 {
+                let level = next() % 4;
+                let _ = q.push(level, (level, round));
+            }
+            if let Some((level, _)) = q.pop() {
+                for above in level + 1..4 {
+                    assert_eq!(q.level_len(above), 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn weighted_ratios() {
+        let mut q = MultiQueue::<_, 3>::new(64).with_weights([1, 2, 5]);
+        let mut counts = [0; 3];
+        for _ in 0..800 {
+            // keep every level backlogged
+            for level in 0..3 {
+                while q.push(level, level).is_ok() {}
+            }
+            counts[q.pop().unwrap()] += 1;
+        }
+        assert_eq!(counts, [100, 200, 500]);
+
+        // an empty level's share goes to the others
+        let mut q = MultiQueue::<_, 2>::new(64).with_weights([1, 3]);
+        for i in 0..4 {
+            q.push(0, i).unwrap();
+        }
+        q.push(1, 10).unwrap();
+        let out: Vec<_> = (0..5).map(|_| q.pop().unwrap()).collect();
+        assert_eq!(out, [10, 0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,270 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+type List<T> = Option<Arc<Node<T>>>;
+
+struct Node<T> {
+    value: T,
+    next: List<T>,
+}
+
+/// An immutable FIFO queue. `push` and `pop` return a new queue and leave
+/// the old one as it was; the two share every node they have in common, so
+/// keeping old versions around is cheap and `clone` is O(1).
+///
+/// Elements are popped off `front` and pushed onto `back`, which is kept in
+/// reverse order. When a pop empties `front`, `back` is reversed into a
+/// fresh `front`, cloning its elements, so `front` is only empty when the
+/// whole queue is.
+pub struct PQueue<T> {
+    front: List<T>,
+    back: List<T>,
+    len: usize,
+}
+
+impl<T> PQueue<T> {
+    pub fn new() -> Self {
+        PQueue {
+            front: None,
+            back: None,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&self, item: T) -> Self {
+        let (front, back) = match self.front {
+            None => (cons(item, None), None),
+            Some(_) => (self.front.clone(), cons(item, self.back.clone())),
+        };
+        PQueue {
+            front,
+            back,
+            len: self.len + 1,
+        }
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.front.as_ref().map(|node| &node.value)
+    }
+
+    /// The front element and the queue without it.
+    pub fn pop(&self) -> Option<(T, Self)>
+    where
+        T: Clone,
+    {
+        let node = self.front.as_ref()?;
+        let rest = match node.next {
+            Some(_) => PQueue {
+                front: node.next.clone(),
+                back: self.back.clone(),
+                len: self.len - 1,
+            },
+            None => PQueue {
+                front: reversed(&self.back),
+                back: None,
+                len: self.len - 1,
+            },
+        };
+        Some((node.value.clone(), rest))
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut back = vec![];
+        let mut pos = self.back.as_ref();
+        while let Some(node) = pos {
+            back.push(&node.value);
+            pos = node.next.as_ref();
+        }
+        Iter {
+            front: self.front.as_deref(),
+            back,
+        }
+    }
+}
+
+fn cons<T>(value: T, next: List<T>) -> List<T> {
+    Some(Arc::new(Node { value, next }))
+}
+
+fn reversed<T: Clone>(mut list: &List<T>) -> List<T> {
+    let mut out = None;
+    while let Some(ref node) = *list {
+        out = cons(node.value.clone(), out);
+        list = &node.next;
+    }
+    out
+}
+
+impl<T> Clone for PQueue<T> {
+    fn clone(&self) -> Self {
+        PQueue {
+            front: self.front.clone(),
+            back: self.back.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Default for PQueue<T> {
+    fn default() -> Self {
+        PQueue::new()
+    }
+}
+
+impl<T> Drop for PQueue<T> {
+    // unlink the nodes no other queue shares one at a time, so dropping a
+    // long list doesn't recurse
+    fn drop(&mut self) {
+        for list in &mut [self.front.take(), self.back.take()] {
+            let mut cur = list.take();
+            while let Some(node) = cur {
+                cur = match Arc::try_unwrap(node) {
+                    Ok(mut node) => node.next.take(),
+                    Err(_) => None,
+                };
+            }
+        }
+    }
+}
+
+pub struct Iter<'a, T: 'a> {
+    front: Option<&'a Node<T>>,
+    // the back list, newest first; popped from the end
+    back: Vec<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.front {
+            Some(node) => {
+                self.front = node.next.as_deref();
+                Some(&node.value)
+            }
+            None => self.back.pop(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items<T: Clone>(q: &PQueue<T>) -> Vec<T> {
+        q.iter().cloned().collect()
+    }
+
+    #[test]
+    fn fifo() {
+        let mut q = PQueue::new();
+        for i in 0..10 {
+            q = q.push(i);
+        }
+        assert_eq!(q.len(), 10);
+        assert_eq!(items(&q), (0..10).collect::<Vec<_>>());
+        for i in 0..10 {
+            assert_eq!(q.peek(), Some(&i));
+            let (v, rest) = q.pop().unwrap();
+            assert_eq!(v, i);
+            q = rest;
+            if i == 4 {
+                q = q.push(10);
+            }
+        }
+        assert_eq!(items(&q), vec![10]);
+        let (_, q) = q.pop().unwrap();
+        assert!(q.is_empty());
+        assert!(q.pop().is_none());
+        assert_eq!(q.peek(), None);
+    }
+
+    #[test]
+    fn snapshots() {
+        let empty = PQueue::new();
+        let a = empty.push("a");
+        let ab = a.push("b");
+        let abc = ab.push("c");
+        let (_, bc) = abc.pop().unwrap();
+        let bcd = bc.push("d");
+        let (_, cd) = bcd.pop().unwrap();
+        let cde = cd.push("e");
+        let abx = ab.push("x");
+
+        assert!(empty.is_empty());
+        assert_eq!(items(&a), ["a"]);
+        assert_eq!(items(&ab), ["a", "b"]);
+        assert_eq!(items(&abc), ["a", "b", "c"]);
+        assert_eq!(items(&bc), ["b", "c"]);
+        assert_eq!(items(&bcd), ["b", "c", "d"]);
+        assert_eq!(items(&cd), ["c", "d"]);
+        assert_eq!(items(&cde), ["c", "d", "e"]);
+        assert_eq!(items(&abx), ["a", "b", "x"]);
+        assert_eq!(abx.len(), 3);
+        assert_eq!(cde.len(), 3);
+    }
+
+    #[test]
+    fn sharing() {
+        // front list [1, 2], back list [3]
+        let base = PQueue::new()
+            .push(0)
+            .push(1)
+            .push(2)
+            .pop()
+            .unwrap()
+            .1
+            .push(3);
+        let front = base.front.clone().unwrap();
+        let back = base.back.clone().unwrap();
+        // base plus the two handles above
+        assert_eq!(Arc::strong_count(&front), 2);
+
+        let copy = base.clone();
+        assert_eq!(Arc::strong_count(&front), 3);
+        assert_eq!(Arc::strong_count(&back), 3);
+
+        // pushing shares the old back list as the new node's tail
+        let pushed = base.push(4);
+        assert_eq!(Arc::strong_count(&front), 4);
+        assert_eq!(Arc::strong_count(&back), 4);
+        assert!(Arc::ptr_eq(
+            pushed.back.as_ref().unwrap().next.as_ref().unwrap(),
+            &back
+        ));
+
+        // popping while the front has more nodes shares the rest of it
+        let (_, popped) = copy.pop().unwrap();
+        assert!(popped.front.is_some());
+        assert!(Arc::ptr_eq(
+            popped.front.as_ref().unwrap(),
+            front.next.as_ref().unwrap()
+        ));
+
+        drop((copy, pushed, popped));
+        assert_eq!(Arc::strong_count(&front), 2);
+        assert_eq!(Arc::strong_count(&back), 2);
+    }
+
+    #[test]
+    fn shared_drop() {
+        let mut q = PQueue::new();
+        for i in 0..200_000 {
+            q = q.push(i);
+        }
+        let (_, rest) = q.pop().unwrap();
+        let keep = rest.clone();
+        drop(q);
+        drop(rest);
+        assert_eq!(keep.len(), 199_999);
+        assert_eq!(keep.iter().next(), Some(&1));
+        assert_eq!(keep.iter().last(), Some(&199_999));
+    }
+}
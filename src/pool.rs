@@ -0,0 +1,325 @@
+use super::Queue;
+use bound::BoundQueue;
+use error::PushError;
+use std::cell::{Cell, RefCell};
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use sync::BlockingQueue;
+
+pub type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A fixed set of threads running jobs from a bounded queue. A job that
+/// panics is abandoned and its worker moves on to the next one.
+pub struct WorkerPool {
+    jobs: Arc<BlockingQueue<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(n_threads: usize, queue_capacity: usize) -> Self {
+        assert!(n_threads > 0, "worker pool needs at least one thread");
+        assert!(
+            queue_capacity > 0,
+            "worker pool queue capacity must be non-zero"
+        );
+        let jobs = Arc::new(BlockingQueue::bounded(queue_capacity));
+        let workers = (0..n_threads)
+            .map(|_| {
+                let jobs = jobs.clone();
+                thread::spawn(move || {
+                    while let Some(job) = jobs.pop() {
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    }
+                })
+            })
+            .collect();
+        WorkerPool { jobs, workers }
+    }
+
+    /// Queues `f`, blocking while the queue is full.
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, f: F) {
+        // only drop closes the queue, so this can't fail
+        let _ = self.jobs.push(Box::new(f));
+    }
+
+    /// Queues `f` if there is room, otherwise hands it back, boxed, in
+    /// `PushError::Full`.
+    pub fn try_execute<F: FnOnce() + Send + 'static>(&self, f: F) -> Result<(), PushError<Job>> {
+        self.jobs.try_push(Box::new(f))
+    }
+
+    /// Stops accepting jobs, lets the workers finish everything already
+    /// queued, and joins them.
+    pub fn shutdown(self) {
+        drop(self);
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.jobs.close();
+        for w in self.workers.drain(..) {
+            let _ = w.join();
+        }
+    }
+}
+
+/// Idle objects kept for reuse. `get` lends one out, making a new one with
+/// the factory if none is idle, and the guard puts it back when dropped.
+/// Objects returned to a pool that already holds `capacity` idle ones are
+/// dropped instead.
+pub struct ObjectPool<T> {
+    idle: RefCell<BoundQueue<T>>,
+    factory: RefCell<Factory<T>>,
+    reset: Option<RefCell<Reset<T>>>,
+    outstanding: Cell<usize>,
+    max_outstanding: usize,
+}
+
+type Factory<T> = Box<dyn FnMut() -> T>;
+type Reset<T> = Box<dyn FnMut(&mut T)>;
+
+impl<T> ObjectPool<T> {
+    /// A pool keeping up to `capacity` idle objects and lending out at most
+    /// `max_outstanding` at a time.
+    pub fn new<F>(capacity: usize, max_outstanding: usize, factory: F) -> Self
+    where
+        F: FnMut() -> T + 'static,
+    {
+        ObjectPool {
+            idle: RefCell::new(BoundQueue::new(capacity)),
+            factory: RefCell::new(Box::new(factory)),
+            reset: None,
+            outstanding: Cell::new(0),
+            max_outstanding,
+        }
+    }
+
+    /// Runs `reset` on every object as it comes back, before it is queued
+    /// or dropped.
+    pub fn with_reset<R>(mut self, reset: R) -> Self
+    where
+        R: FnMut(&mut T) + 'static,
+    {
+        self.reset = Some(RefCell::new(Box::new(reset)));
+        self
+    }
+
+    /// Lends out an idle object, or a new one if none is idle. None if
+    /// `max_outstanding` objects are already lent out.
+    pub fn get(&self) -> Option<PoolGuard<'_, T>> {
+        if self.outstanding.get() == self.max_outstanding {
+            return None;
+        }
+        let obj = match self.idle.borrow_mut().pop() {
+            Some(obj) => obj,
+            None => (*self.factory.borrow_mut())(),
+        };
+        self.outstanding.set(self.outstanding.get() + 1);
+        Some(PoolGuard {
+            pool: self,
+            obj: Some(obj),
+        })
+    }
+
+    pub fn idle(&self) -> usize {
+        self.idle.borrow().len()
+    }
+
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.get()
+    }
+}
+
+pub struct PoolGuard<'a, T: 'a> {
+    pool: &'a ObjectPool<T>,
+    obj: Option<T>,
+}
+
+impl<'a, T> Deref for PoolGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.obj.as_ref().unwrap()
+    }
+}
+
+impl<'a, T> DerefMut for PoolGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.obj.as_mut().unwrap()
+    }
+}
+
+impl<'a, T> Drop for PoolGuard<'a, T> {
+    fn drop(&mut self) {
+        let pool = self.pool;
+        pool.outstanding.set(pool.outstanding.get() - 1);
+        let mut obj = match self.obj.take() {
+            Some(obj) => obj,
+            None => return,
+        };
+        if let Some(ref reset) = pool.reset {
+            (*reset.borrow_mut())(&mut obj);
+        }
+        if let Some(slot) = pool.idle.borrow_mut().push_slot() {
+            slot.write(obj);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+
+    #[test]
+    fn runs_each_job_once() {
+        let runs: Arc<Vec<AtomicUsize>> = Arc::new((0..500).map(|_| AtomicUsize::new(0)).collect());
+        let pool = WorkerPool::new(4, 8);
+        for i in 0..500 {
+            let runs = runs.clone();
+            pool.execute(move || {
+                runs[i].fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        pool.shutdown();
+        assert!(runs.iter().all(|r| r.load(Ordering::SeqCst) == 1));
+    }
+
+    #[test]
+    fn shutdown_drains_pending() {
+        let done = Arc::new(AtomicUsize::new(0));
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let pool = WorkerPool::new(1, 16);
+        pool.execute(move || gate_rx.recv().unwrap());
+        for _ in 0..10 {
+            let done = done.clone();
+            pool.execute(move || {
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        // the worker is still stuck on the first job, so all ten are queued
+        gate_tx.send(()).unwrap();
+        pool.shutdown();
+        assert_eq!(done.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn try_execute_full() {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let pool = WorkerPool::new(1, 2);
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            gate_rx.recv().unwrap();
+        });
+        started_rx.recv().unwrap();
+        assert!(pool.try_execute(|| ()).is_ok());
+        assert!(pool.try_execute(|| ()).is_ok());
+        assert!(matches!(pool.try_execute(|| ()), Err(PushError::Full(_))));
+        gate_tx.send(()).unwrap();
+        pool.shutdown();
+    }
+
+    #[test]
+    fn panicking_job() {
+        let done = Arc::new(AtomicUsize::new(0));
+        let pool = WorkerPool::new(1, 4);
+        pool.execute(|| panic!("job failed"));
+        let d = done.clone();
+        pool.execute(move || {
+            d.fetch_add(1, Ordering::SeqCst);
+        });
+        pool.shutdown();
+        assert_eq!(done.load(Ordering::SeqCst), 1);
+    }
+
+    struct Buf {
+        id: usize,
+        data: Vec<u8>,
+        drops: Rc<Cell<usize>>,
+    }
+
+    impl Drop for Buf {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    fn buf_pool(capacity: usize, max: usize) -> (ObjectPool<Buf>, Rc<Cell<usize>>) {
+        let drops = Rc::new(Cell::new(0));
+        let d = drops.clone();
+        let mut next_id = 0;
+        let pool = ObjectPool::new(capacity, max, move || {
+            next_id += 1;
+            Buf {
+                id: next_id,
+                data: Vec::with_capacity(64),
+                drops: d.clone(),
+            }
+        });
+        (pool, drops)
+    }
+
+    #[test]
+    fn object_pool_reuse() {
+        let resets = Rc::new(Cell::new(0));
+        let r = resets.clone();
+        let (pool, drops) = buf_pool(4, 8);
+        let pool = pool.with_reset(move |b: &mut Buf| {
+            r.set(r.get() + 1);
+            b.data.clear();
+        });
+        for round in 0..10 {
+            let mut b = pool.get().unwrap();
+            assert_eq!(b.id, 1);
+            assert!(b.data.is_empty());
+            b.data.push(round);
+        }
+        assert_eq!(resets.get(), 10);
+        {
+            let a = pool.get().unwrap();
+            let b = pool.get().unwrap();
+            assert_eq!((a.id, b.id), (1, 2));
+            assert_eq!(pool.outstanding(), 2);
+        }
+        assert_eq!(resets.get(), 12);
+        assert_eq!((pool.idle(), pool.outstanding()), (2, 0));
+        // FIFO reuse: b was dropped (returned) before a
+        assert_eq!(pool.get().unwrap().id, 2);
+        assert_eq!(pool.get().unwrap().id, 1);
+        assert_eq!(drops.get(), 0);
+    }
+
+    #[test]
+    fn object_pool_max_outstanding() {
+        let (pool, _) = buf_pool(4, 2);
+        let a = pool.get().unwrap();
+        let b = pool.get().unwrap();
+        assert!(pool.get().is_none());
+        drop(a);
+        let c = pool.get().unwrap();
+        assert_eq!(c.id, 1);
+        assert!(pool.get().is_none());
+        drop((b, c));
+        assert_eq!(pool.idle(), 2);
+    }
+
+    #[test]
+    fn object_pool_full_on_return() {
+        let (pool, drops) = buf_pool(2, 5);
+        let guards: Vec<_> = (0..5).map(|_| pool.get().unwrap()).collect();
+        assert_eq!(pool.idle(), 0);
+        drop(guards);
+        // two fit back into the pool, the other three are dropped
+        assert_eq!(drops.get(), 3);
+        assert_eq!(pool.idle(), 2);
+        let ids: Vec<_> = (0..2).map(|_| pool.get().unwrap().id).collect();
+        assert_eq!(ids, [1, 2]);
+        drop(pool);
+        assert_eq!(drops.get(), 5);
+    }
+}
@@ -0,0 +1,268 @@
+use super::{Queue, Unbounded};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::iter::FromIterator;
+use core::slice;
+
+/// A binary heap behind the `Queue` trait: `pop` hands out the element
+/// that orders first, the smallest by default.
+///
+/// Elements that compare equal come out in the order they were pushed.
+/// Each one carries a push counter that breaks ties, so the heap never has
+/// to be stable itself.
+pub struct PriorityQueue<T, C = fn(&T, &T) -> Ordering> {
+    heap: Vec<Entry<T>>,
+    cmp: C,
+    // sequence number for the next push
+    seq: u64,
+}
+
+struct Entry<T> {
+    seq: u64,
+    item: T,
+}
+
+impl<T: Ord> PriorityQueue<T> {
+    /// Pops the smallest element first.
+    pub fn new() -> Self {
+        PriorityQueue::with_cmp(Ord::cmp)
+    }
+
+    /// Pops the largest element first.
+    pub fn max_first() -> Self {
+        PriorityQueue::with_cmp(|a: &T, b: &T| b.cmp(a))
+    }
+}
+
+impl<T: Ord> Default for PriorityQueue<T> {
+    fn default() -> Self {
+        PriorityQueue::new()
+    }
+}
+
+impl<T> PriorityQueue<T> {
+    /// Pops the element whose key is smallest first.
+    pub fn by_key<K: Ord, F: Fn(&T) -> K>(key: F) -> PriorityQueue<T, impl Fn(&T, &T) -> Ordering> {
+        PriorityQueue::with_cmp(move |a: &T, b: &T| key(a).cmp(&key(b)))
+    }
+}
+
+impl<T, C: Fn(&T, &T) -> Ordering> PriorityQueue<T, C> {
+    /// Pops first whichever element `cmp` puts first.
+    pub fn with_cmp(cmp: C) -> Self {
+        PriorityQueue {
+            heap: Vec::new(),
+            cmp,
+            seq: 0,
+        }
+    }
+
+    /// The element `pop` would return.
+    pub fn peek(&self) -> Option<&T> {
+        self.heap.first().map(|e| &e.item)
+    }
+
+    /// Every element, in no particular order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            inner: self.heap.iter(),
+        }
+    }
+
+    /// Pops everything, in priority order.
+    pub fn into_sorted_iter(self) -> IntoSortedIter<T, C> {
+        IntoSortedIter { q: self }
+    }
+
+    pub fn clear(&mut self) {
+        self.heap.clear();
+    }
+
+    // whether `a` has to come out before `b`
+    fn before(&self, a: usize, b: usize) -> bool {
+        let (a, b) = (&self.heap[a], &self.heap[b]);
+        match (self.cmp)(&a.item, &b.item) {
+            Ordering::Less => true,
+            Ordering::Greater => false,
+            Ordering::Equal => a.seq < b.seq,
+        }
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if !self.before(i, parent) {
+                break;
+            }
+            self.heap.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        let len = self.heap.len();
+        loop {
+            let left = 2 * i + 1;
+            if left >= len {
+                break;
+            }
+            let right = left + 1;
+            let child = if right < len && self.before(right, left) {
+                right
+            } else {
+                left
+            };
+            if !self.before(child, i) {
+                break;
+            }
+            self.heap.swap(i, child);
+            i = child;
+        }
+    }
+}
+
+impl<T, C: Fn(&T, &T) -> Ordering> Queue<T> for PriorityQueue<T, C> {
+    fn push(&mut self, item: T) {
+        self.heap.push(Entry {
+            seq: self.seq,
+            item,
+        });
+        self.seq += 1;
+        let last = self.heap.len() - 1;
+        self.sift_up(last);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        let item = self.heap.swap_remove(0).item;
+        self.sift_down(0);
+        Some(item)
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+impl<T, C> Unbounded for PriorityQueue<T, C> {}
+
+impl<T: Ord> FromIterator<T> for PriorityQueue<T> {
+    fn from_iter<It: IntoIterator<Item = T>>(iter: It) -> Self {
+        let mut q = PriorityQueue::new();
+        q.extend(iter);
+        q
+    }
+}
+
+impl<T, C: Fn(&T, &T) -> Ordering> Extend<T> for PriorityQueue<T, C> {
+    fn extend<It: IntoIterator<Item = T>>(&mut self, iter: It) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+pub struct Iter<'a, T: 'a> {
+    inner: slice::Iter<'a, Entry<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|e| &e.item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+pub struct IntoSortedIter<T, C> {
+    q: PriorityQueue<T, C>,
+}
+
+impl<T, C: Fn(&T, &T) -> Ordering> Iterator for IntoSortedIter<T, C> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.q.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.q.len();
+        (len, Some(len))
+    }
+}
+
+impl<T, C: Fn(&T, &T) -> Ordering> ExactSizeIterator for IntoSortedIter<T, C> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // same shape as the bench loops in main.rs
+    fn burst(q: &mut dyn Queue<u64>, items: &[u64]) -> Vec<u64> {
+        for &i in items {
+            q.push(i);
+        }
+        let mut out = vec![];
+        while let Some(v) = q.pop() {
+            out.push(v);
+        }
+        out
+    }
+
+    #[test]
+    fn order() {
+        let items = [5, 1, 4, 1, 5, 9, 2, 6, 5, 3];
+        let mut sorted = items.to_vec();
+        sorted.sort_unstable();
+        assert_eq!(burst(&mut PriorityQueue::new(), &items), sorted);
+        sorted.reverse();
+        assert_eq!(burst(&mut PriorityQueue::max_first(), &items), sorted);
+
+        let mut q: PriorityQueue<_> = items.iter().cloned().collect();
+        assert_eq!(q.peek(), Some(&1));
+        assert_eq!(q.len(), 10);
+        let mut seen: Vec<_> = q.iter().cloned().collect();
+        seen.sort_unstable();
+        assert_eq!(seen, [1, 1, 2, 3, 4, 5, 5, 5, 6, 9]);
+        q.pop();
+        q.push(0);
+        assert!(q.into_sorted_iter().eq(vec![0, 1, 2, 3, 4, 5, 5, 5, 6, 9]));
+    }
+
+    #[test]
+    fn ties_in_push_order() {
+        let mut q = PriorityQueue::by_key(|e: &(u8, usize)| e.0);
+        // interleave pushes and pops so ties get shuffled through the heap
+        let mut out = vec![];
+        for i in 0..200 {
+            q.push(((i * 7 % 5) as u8, i));
+            if i % 3 == 0 {
+                out.extend(q.pop());
+            }
+        }
+        out.extend(q.into_sorted_iter());
+        assert_eq!(out.len(), 200);
+        let mut by_prio = vec![vec![]; 5];
+        for &(p, i) in &out {
+            by_prio[p as usize].push(i);
+        }
+        for ids in by_prio {
+            assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        }
+
+        let mut q = PriorityQueue::with_cmp(|a: &(i32, &str), b: &(i32, &str)| b.0.cmp(&a.0));
+        for &e in &[(1, "a"), (2, "b"), (1, "c"), (2, "d"), (1, "e")] {
+            q.push(e);
+        }
+        let names: Vec<_> = q.into_sorted_iter().map(|e| e.1).collect();
+        assert_eq!(names, ["b", "d", "a", "c", "e"]);
+    }
+}
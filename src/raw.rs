@@ -0,0 +1,89 @@
+use alloc::alloc::{self, Layout};
+use core::cmp;
+use core::mem;
+use core::ptr::{self, NonNull};
+use error::CapacityError;
+
+// An uninitialized buffer of `cap` slots, allocated with at least the
+// alignment of T. Zero-sized types never allocate but still report the
+// `cap` asked for, so index math over them is the same as for any T.
+pub struct RawBuf<T> {
+    ptr: NonNull<T>,
+    cap: usize,
+    align: usize,
+}
+
+impl<T> RawBuf<T> {
+    pub fn with_capacity(cap: usize) -> Self {
+        RawBuf::with_capacity_aligned(cap, mem::align_of::<T>())
+    }
+
+    // like with_align, but panics on overflow and aborts on allocation
+    // failure, as Vec does
+    pub fn with_capacity_aligned(cap: usize, align: usize) -> Self {
+        match RawBuf::with_align(cap, align) {
+            Ok(buf) => buf,
+            Err(e) => match e.alloc_layout() {
+                Some(layout) => alloc::handle_alloc_error(layout),
+                None => panic!("capacity overflow"),
+            },
+        }
+    }
+
+    // `align` is raised to align_of::<T>() if smaller. Never panics or
+    // aborts: allocation failure is an error too.
+    pub fn with_align(cap: usize, align: usize) -> Result<Self, CapacityError> {
+        let align = cmp::max(align, mem::align_of::<T>());
+        let size = mem::size_of::<T>();
+        if size == 0 || cap == 0 {
+            // still validate the alignment the caller asked for, and honour
+            // it: the dangling pointer is `align` itself
+            Layout::from_size_align(0, align)?;
+            return Ok(RawBuf {
+                ptr: unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(align)) },
+                cap,
+                align,
+            });
+        }
+        let bytes = size.checked_mul(cap).ok_or_else(CapacityError::overflow)?;
+        let layout = Layout::from_size_align(bytes, align)?;
+        let ptr = unsafe { alloc::alloc(layout) as *mut T };
+        match NonNull::new(ptr) {
+            Some(ptr) => Ok(RawBuf { ptr, cap, align }),
+            None => Err(CapacityError::alloc(layout)),
+        }
+    }
+
+    // takes over an allocation made by Vec<T> with capacity `cap`
+    pub unsafe fn from_raw_parts(ptr: *mut T, cap: usize) -> Self {
+        RawBuf {
+            ptr: NonNull::new_unchecked(ptr),
+            cap,
+            align: mem::align_of::<T>(),
+        }
+    }
+
+    pub fn ptr(&self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
+    pub fn align(&self) -> usize {
+        self.align
+    }
+}
+
+impl<T> Drop for RawBuf<T> {
+    fn drop(&mut self) {
+        let size = mem::size_of::<T>();
+        if size != 0 && self.cap != 0 {
+            unsafe {
+                let layout = Layout::from_size_align_unchecked(size * self.cap, self.align);
+                alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout);
+            }
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use super::{Queue, Unbounded};
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::{self, NonNull};
+use merge::{Refs, Walk};
+
+const BLOCK: usize = 64;
+
+struct Block<T> {
+    slots: [MaybeUninit<T>; BLOCK],
+    next: Option<NonNull<Block<T>>>,
+}
+
+impl<T> Block<T> {
+    fn new() -> Box<Self> {
+        Box::new(Block {
+            slots: unsafe { MaybeUninit::uninit().assume_init() },
+            next: None,
+        })
+    }
+}
+
+/// A growable queue of fixed-size blocks linked together. Pushing allocates
+/// only when the tail block is full and popping frees a block once it has
+/// been drained, keeping one drained block aside for the next allocation
+/// so a queue hovering at a block boundary doesn't thrash the allocator.
+pub struct SegQueue<T> {
+    head: Option<NonNull<Block<T>>>,
+    tail: Option<NonNull<Block<T>>>,
+    // next slot to pop in the head block
+    head_idx: usize,
+    // next slot to fill in the tail block
+    tail_idx: usize,
+    len: usize,
+    spare: Option<Box<Block<T>>>,
+    marker: PhantomData<Box<Block<T>>>,
+}
+
+impl<T> SegQueue<T> {
+    pub fn new() -> Self {
+        SegQueue {
+            head: None,
+            tail: None,
+            head_idx: 0,
+            tail_idx: 0,
+            len: 0,
+            spare: None,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            block: self.head,
+            idx: self.head_idx,
+            len: self.len,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            block: self.head,
+            idx: self.head_idx,
+            len: self.len,
+            marker: PhantomData,
+        }
+    }
+
+    fn new_block(&mut self) -> NonNull<Block<T>> {
+        let b = self.spare.take().unwrap_or_else(Block::new);
+        NonNull::from(Box::leak(b))
+    }
+
+    fn free_block(&mut self, b: NonNull<Block<T>>) {
+        let mut b = unsafe { Box::from_raw(b.as_ptr()) };
+        if self.spare.is_none() {
+            b.next = None;
+            self.spare = Some(b);
+        }
+    }
+}
+
+impl<T> Default for SegQueue<T> {
+    fn default() -> Self {
+        SegQueue::new()
+    }
+}
+
+impl<T> Queue<T> for SegQueue<T> {
+    fn push(&mut self, item: T) {
+        let mut tail = match self.tail {
+            Some(t) if self.tail_idx < BLOCK => t,
+            _ => {
+                let b = self.new_block();
+                match self.tail {
+                    None => self.head = Some(b),
+                    Some(mut t) => unsafe { t.as_mut().next = Some(b) },
+                }
+                self.tail = Some(b);
+                self.tail_idx = 0;
+                b
+            }
+        };
+        unsafe {
+            tail.as_mut().slots[self.tail_idx] = MaybeUninit::new(item);
+        }
+        self.tail_idx += 1;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let head = self.head.unwrap();
+        let v = unsafe { ptr::read(head.as_ref().slots[self.head_idx].as_ptr()) };
+        self.head_idx += 1;
+        self.len -= 1;
+        if self.len == 0 {
+            // start over at the front of the same block
+            self.head_idx = 0;
+            self.tail_idx = 0;
+            let next = unsafe { head.as_ref().next };
+            debug_assert!(next.is_none());
+        } else if self.head_idx == BLOCK {
+            self.head = unsafe { head.as_ref().next };
+            self.head_idx = 0;
+            self.free_block(head);
+        }
+        Some(v)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<T> Unbounded for SegQueue<T> {}
+
+impl<T> Walk<T> for SegQueue<T> {
+    fn walk<'a>(&'a self) -> Refs<'a, T> {
+        Box::new(self.iter())
+    }
+}
+
+impl<T> Drop for SegQueue<T> {
+    fn drop(&mut self) {
+        self.clear();
+        if let Some(b) = self.head.take() {
+            unsafe { drop(Box::from_raw(b.as_ptr())) };
+        }
+    }
+}
+
+impl<T> Extend<T> for SegQueue<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+pub struct Iter<'a, T: 'a> {
+    block: Option<NonNull<Block<T>>>,
+    idx: usize,
+    len: usize,
+    marker: PhantomData<&'a T>,
+}
+
+pub struct IterMut<'a, T: 'a> {
+    block: Option<NonNull<Block<T>>>,
+    idx: usize,
+    len: usize,
+    marker: PhantomData<&'a mut T>,
+}
+
+pub struct IntoIter<T>(SegQueue<T>);
+
+impl<T> IntoIterator for SegQueue<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+// Steps to the next occupied slot, following the block link when the
+// current block is used up.
+fn advance<T>(block: &mut Option<NonNull<Block<T>>>, idx: &mut usize) -> *mut T {
+    let mut b = block.unwrap();
+    if *idx == BLOCK {
+        b = unsafe { b.as_ref().next.unwrap() };
+        *block = Some(b);
+        *idx = 0;
+    }
+    let p = unsafe { (*b.as_ptr()).slots[*idx].as_mut_ptr() };
+    *idx += 1;
+    p
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { &*advance(&mut self.block, &mut self.idx) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { &mut *advance(&mut self.block, &mut self.idx) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    struct Counted<'a>(&'a Cell<usize>);
+
+    impl<'a> Drop for Counted<'a> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn block_boundaries() {
+        for k in 1..4 {
+            for &n in &[k * BLOCK - 1, k * BLOCK, k * BLOCK + 1] {
+                let mut q = SegQueue::new();
+                q.extend(0..n);
+                assert_eq!(q.len(), n);
+                assert!(q.iter().cloned().eq(0..n));
+                for i in 0..n {
+                    assert_eq!(q.pop(), Some(i));
+                }
+                assert_eq!(q.pop(), None);
+                // refill after draining across the boundary
+                q.extend(0..n);
+                assert!(q.into_iter().eq(0..n));
+            }
+        }
+    }
+
+    #[test]
+    fn churn_at_boundary() {
+        let mut q = SegQueue::new();
+        q.extend(0..BLOCK - 1);
+        for i in BLOCK - 1..10 * BLOCK {
+            q.push(i);
+            assert_eq!(q.pop(), Some(i + 1 - BLOCK));
+            assert_eq!(q.len(), BLOCK - 1);
+        }
+        assert!(q.iter().cloned().eq(9 * BLOCK + 1..10 * BLOCK));
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut q = SegQueue::new();
+        q.extend(0..BLOCK + 1);
+        q.pop();
+        for x in q.iter_mut() {
+            *x *= 2;
+        }
+        assert!(q.iter().cloned().eq((1..BLOCK + 1).map(|x| x * 2)));
+        assert_eq!(q.iter_mut().size_hint(), (BLOCK, Some(BLOCK)));
+    }
+
+    #[test]
+    fn drop_and_clear() {
+        let drops = Cell::new(0);
+        {
+            let mut q = SegQueue::new();
+            for _ in 0..2 * BLOCK + 1 {
+                q.push(Counted(&drops));
+            }
+            for _ in 0..BLOCK + 1 {
+                q.pop();
+            }
+            assert_eq!(drops.get(), BLOCK + 1);
+            q.clear();
+            assert_eq!(drops.get(), 2 * BLOCK + 1);
+            assert!(q.is_empty());
+            for _ in 0..3 {
+                q.push(Counted(&drops));
+            }
+        }
+        assert_eq!(drops.get(), 2 * BLOCK + 4);
+    }
+}
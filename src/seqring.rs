@@ -0,0 +1,116 @@
+use bound::{BoundQueue, Iter};
+use core::cmp;
+use core::iter::Skip;
+
+/// A bounded ring that numbers every record it is given and overwrites the
+/// oldest one when full. Readers keep the sequence number they want next and
+/// ask for everything from there with `read_since`, which also says how many
+/// of those records were overwritten before they got to them.
+pub struct SeqRing<T> {
+    records: BoundQueue<T>,
+    // sequence number the next push gets
+    next: u64,
+}
+
+impl<T> SeqRing<T> {
+    /// Keeps the last `cap` records.
+    pub fn new(cap: usize) -> Self {
+        assert!(cap > 0, "SeqRing needs room for one record");
+        SeqRing {
+            records: BoundQueue::new(cap),
+            next: 0,
+        }
+    }
+
+    /// Stores `item`, dropping the oldest record if the ring is full, and
+    /// returns its sequence number. Numbers start at 0.
+    pub fn push(&mut self, item: T) -> u64 {
+        self.records.force_push(item);
+        self.next += 1;
+        self.next - 1
+    }
+
+    /// The number of the newest record, or None before the first push.
+    pub fn latest_seq(&self) -> Option<u64> {
+        self.next.checked_sub(1)
+    }
+
+    /// The number of the oldest record still held.
+    pub fn oldest_seq(&self) -> u64 {
+        self.next - self.records.len() as u64
+    }
+
+    /// The records numbered `seq` and up that are still held, along with how
+    /// many records numbered `seq` and up were already overwritten. A reader
+    /// that has seen everything up to `latest_seq()` passes one past it.
+    pub fn read_since(&self, seq: u64) -> (u64, Skip<Iter<'_, T>>) {
+        let oldest = self.oldest_seq();
+        let lost = oldest.saturating_sub(seq);
+        // clamped before the cast so a cursor far ahead can't wrap around
+        // to a small skip where usize is narrower than u64
+        let ahead = seq.saturating_sub(oldest);
+        let skip = cmp::min(ahead, self.records.len() as u64) as usize;
+        (lost, self.records.iter().skip(skip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_up() {
+        let mut ring = SeqRing::new(4);
+        assert_eq!(ring.latest_seq(), None);
+        assert_eq!(ring.read_since(0).0, 0);
+        assert_eq!(ring.read_since(0).1.count(), 0);
+
+        let mut cursor = 0;
+        let mut seen = vec![];
+        for i in 0..20 {
+            assert_eq!(ring.push(i * 10), i);
+            if i % 3 == 2 {
+                let (lost, recs) = ring.read_since(cursor);
+                assert_eq!(lost, 0);
+                seen.extend(recs.cloned());
+                cursor = ring.latest_seq().unwrap() + 1;
+            }
+        }
+        let (lost, recs) = ring.read_since(cursor);
+        assert_eq!(lost, 0);
+        seen.extend(recs.cloned());
+        assert_eq!(seen, (0..20).map(|i| i * 10).collect::<Vec<_>>());
+        assert_eq!(ring.latest_seq(), Some(19));
+    }
+
+    #[test]
+    fn falls_behind() {
+        let mut ring = SeqRing::new(5);
+        for i in 0..3 {
+            ring.push(i);
+        }
+        let cursor = 1;
+        for i in 3..17 {
+            ring.push(i);
+        }
+        assert_eq!(ring.oldest_seq(), 12);
+        // records 1 through 11 were overwritten before the reader came back
+        let (lost, recs) = ring.read_since(cursor);
+        assert_eq!(lost, 11);
+        assert_eq!(recs.cloned().collect::<Vec<_>>(), [12, 13, 14, 15, 16]);
+
+        // lost plus surviving covers exactly the requested range
+        for cursor in 0..20 {
+            let (lost, recs) = ring.read_since(cursor);
+            let n = recs.count() as u64;
+            assert_eq!(lost + n, 17u64.saturating_sub(cursor));
+        }
+        assert_eq!(ring.read_since(14).1.next(), Some(&14));
+
+        // cursors past the end get nothing, however far out
+        for &cursor in &[17, 12 + (1 << 32), u64::MAX] {
+            let (lost, recs) = ring.read_since(cursor);
+            assert_eq!((lost, recs.count()), (0, 0));
+        }
+    }
+}
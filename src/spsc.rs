@@ -0,0 +1,361 @@
+use alloc::sync::Arc;
+use bound::BoundQueue;
+use core::ptr;
+use core::slice;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use raw::RawBuf;
+
+/// A bounded channel between one producer thread and one consumer thread,
+/// over the same ring `BoundQueue` uses. Each index is stored by one side
+/// only, so neither push nor pop takes a lock.
+pub fn channel<T>(cap: usize) -> (Producer<T>, Consumer<T>) {
+    BoundQueue::new(cap).split()
+}
+
+// One slot is kept free to tell full from empty, as in BoundQueue.
+struct Ring<T> {
+    data: RawBuf<T>,
+    // next slot to pop; only the consumer stores it
+    head: AtomicUsize,
+    // next slot to push; only the producer stores it
+    tail: AtomicUsize,
+}
+
+// the two halves hand elements across threads but never share one
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    fn next(&self, idx: usize) -> usize {
+        if idx + 1 == self.data.cap() {
+            0
+        } else {
+            idx + 1
+        }
+    }
+
+    fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        if tail >= head {
+            tail - head
+        } else {
+            self.data.cap() - head + tail
+        }
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let tail = *self.tail.get_mut();
+        let mut head = *self.head.get_mut();
+        while head != tail {
+            unsafe {
+                ptr::drop_in_place(self.data.ptr().add(head));
+            }
+            head = self.next(head);
+        }
+    }
+}
+
+// takes over a BoundQueue's buffer with the live elements in head..tail
+pub(crate) fn from_buf<T>(data: RawBuf<T>, head: usize, tail: usize) -> (Producer<T>, Consumer<T>) {
+    let ring = Arc::new(Ring {
+        data,
+        head: AtomicUsize::new(head),
+        tail: AtomicUsize::new(tail),
+    });
+    (Producer { ring: ring.clone() }, Consumer { ring })
+}
+
+/// The sending half of `channel`.
+pub struct Producer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> Producer<T> {
+    /// Hands `item` back if the ring is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        let next = self.ring.next(tail);
+        if next == self.ring.head.load(Ordering::Acquire) {
+            return Err(item);
+        }
+        unsafe {
+            ptr::write(self.ring.data.ptr().add(tail), item);
+        }
+        self.ring.tail.store(next, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn cap(&self) -> usize {
+        self.ring.data.cap() - 1
+    }
+
+    /// How many elements are queued; the consumer may be taking them as
+    /// this returns.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.cap()
+    }
+}
+
+/// The receiving half of `channel`.
+///
+/// `peek` hands out `&T`, so a shared consumer is only `Sync` when `T` is:
+///
+/// ```compile_fail
+/// use queue::spsc::{channel, Consumer};
+/// use std::cell::Cell;
+///
+/// fn shared<S: Sync>(_: &S) {}
+///
+/// let (_tx, rx): (_, Consumer<Cell<u64>>) = channel(4);
+/// shared(&rx);
+/// ```
+pub struct Consumer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+// the ring alone would make this Sync for any T: Send
+unsafe impl<T: Send + Sync> Sync for Consumer<T> {}
+
+impl<T> Consumer<T> {
+    /// `None` if nothing is queued.
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        if head == self.ring.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let v = unsafe { ptr::read(self.ring.data.ptr().add(head)) };
+        self.ring
+            .head
+            .store(self.ring.next(head), Ordering::Release);
+        Some(v)
+    }
+
+    /// The element the next `pop` returns, without taking it.
+    ///
+    /// Only the consumer advances head, and it needs `&mut self` to do so,
+    /// so the slot stays live for as long as the reference does. The
+    /// producer only ever writes the slot at tail and advances tail past
+    /// it; it never touches the slots in head..tail, so nothing writes to
+    /// the element while it is borrowed.
+    pub fn peek(&self) -> Option<&T> {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        if head == self.ring.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        unsafe { Some(&*self.ring.data.ptr().add(head)) }
+    }
+
+    /// The queued elements from the front up to the end of the buffer or
+    /// the producer's last push, whichever comes first; empty if nothing
+    /// is queued. The rest, if the run wraps, shows up once the front part
+    /// has been popped. Safe for the same reason as `peek`.
+    pub fn peek_slice(&self) -> &[T] {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        let tail = self.ring.tail.load(Ordering::Acquire);
+        let end = if tail >= head {
+            tail
+        } else {
+            self.ring.data.cap()
+        };
+        unsafe { slice::from_raw_parts(self.ring.data.ptr().add(head), end - head) }
+    }
+
+    pub fn cap(&self) -> usize {
+        self.ring.data.cap() - 1
+    }
+
+    /// How many elements are queued; the producer may be adding more as
+    /// this returns.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::thread;
+    use Queue;
+
+    #[test]
+    fn push_pop() {
+        let (mut tx, mut rx) = channel(3);
+        assert_eq!(tx.cap(), 3);
+        assert_eq!(rx.pop(), None);
+        for round in 0..5 {
+            for i in 0..3 {
+                tx.push(round * 3 + i).unwrap();
+            }
+            assert!(tx.is_full());
+            assert_eq!(tx.push(-1), Err(-1));
+            assert_eq!(rx.len(), 3);
+            for i in 0..3 {
+                assert_eq!(rx.pop(), Some(round * 3 + i));
+            }
+            assert!(rx.is_empty());
+        }
+    }
+
+    #[test]
+    fn split_keeps_order() {
+        for &pow2 in &[false, true] {
+            let mut q = if pow2 {
+                BoundQueue::new_pow2(3)
+            } else {
+                BoundQueue::new(8)
+            };
+            // leave the run wrapped around the end of the buffer
+            for i in 0..8 {
+                q.push(i);
+            }
+            for _ in 0..5 {
+                q.pop();
+            }
+            for i in 8..12 {
+                q.push(i);
+            }
+            let (mut tx, mut rx) = q.split();
+            assert_eq!(tx.cap(), 8);
+            tx.push(12).unwrap();
+            let out: Vec<_> = (0..8).map(|_| rx.pop().unwrap()).collect();
+            assert_eq!(out, [5, 6, 7, 8, 9, 10, 11, 12]);
+            assert_eq!(rx.pop(), None);
+        }
+    }
+
+    #[test]
+    fn peek() {
+        let (mut tx, mut rx) = channel(4);
+        assert_eq!(rx.peek(), None);
+        assert!(rx.peek_slice().is_empty());
+        for round in 0..6 {
+            tx.push(round.to_string()).unwrap();
+            tx.push(String::new()).unwrap();
+            let peeked = rx.peek().cloned();
+            assert_eq!(peeked, Some(round.to_string()));
+            // peeking twice sees the same element, and pop returns it
+            assert_eq!(rx.peek(), peeked.as_ref());
+            assert_eq!(rx.pop(), peeked);
+            assert_eq!(rx.peek(), Some(&String::new()));
+            assert_eq!(rx.pop(), Some(String::new()));
+        }
+    }
+
+    #[test]
+    fn peek_slice_stops_at_wrap() {
+        // 5 slots; leave the run wrapped around the end of the buffer
+        let (mut tx, mut rx) = channel(4);
+        for i in 0..3 {
+            tx.push(i).unwrap();
+        }
+        assert_eq!(rx.peek_slice(), [0, 1, 2]);
+        for _ in 0..3 {
+            rx.pop();
+        }
+        for i in 3..7 {
+            tx.push(i).unwrap();
+        }
+        assert_eq!(rx.peek_slice(), [3, 4]);
+        rx.pop();
+        rx.pop();
+        assert_eq!(rx.peek_slice(), [5, 6]);
+    }
+
+    #[test]
+    fn peek_during_push() {
+        let (mut tx, rx) = channel(64);
+        tx.push(0u64).unwrap();
+        let front = rx.peek().unwrap();
+        // the producer keeps pushing while the front is borrowed
+        let producer = thread::spawn(move || {
+            for i in 1..64 {
+                tx.push(i).unwrap();
+            }
+            tx
+        });
+        for _ in 0..100 {
+            assert_eq!(*front, 0);
+            assert_eq!(rx.peek(), Some(&0));
+            thread::yield_now();
+        }
+        let _tx = producer.join().unwrap();
+        assert_eq!(*front, 0);
+        assert_eq!(rx.len(), 64);
+        assert_eq!(rx.peek_slice().len(), 64);
+    }
+
+    struct Counted<'a>(&'a Cell<usize>);
+
+    impl<'a> Drop for Counted<'a> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn drop_leftovers() {
+        let drops = Cell::new(0);
+        let (mut tx, mut rx) = channel(4);
+        for _ in 0..6 {
+            assert!(tx.push(Counted(&drops)).is_ok());
+            assert!(tx.push(Counted(&drops)).is_ok());
+            rx.pop();
+            rx.pop();
+        }
+        for _ in 0..3 {
+            assert!(tx.push(Counted(&drops)).is_ok());
+        }
+        assert_eq!(drops.get(), 12);
+        drop(rx);
+        // the producer still holds the ring
+        assert_eq!(drops.get(), 12);
+        drop(tx);
+        assert_eq!(drops.get(), 15);
+    }
+
+    #[test]
+    fn threads() {
+        let n: u64 = if cfg!(miri) { 1_000 } else { 4_000_000 };
+        let (mut tx, mut rx) = channel(64);
+        let producer = thread::spawn(move || {
+            for i in 0..n {
+                let mut item = i;
+                while let Err(back) = tx.push(item) {
+                    item = back;
+                    thread::yield_now();
+                }
+            }
+        });
+        let mut sum = 0;
+        let mut expected = 0;
+        while expected < n {
+            match rx.pop() {
+                Some(x) => {
+                    assert_eq!(x, expected);
+                    sum += x;
+                    expected += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+        assert_eq!(sum, n * (n - 1) / 2);
+        assert_eq!(rx.pop(), None);
+    }
+}
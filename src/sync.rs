@@ -0,0 +1,327 @@
+use super::Queue;
+use bound::BoundQueue;
+use error::{PopError, PushError, RecvTimeoutError};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use unbound::UnboundQueue;
+
+/// A queue shared between threads: `push` waits while it is full and `pop`
+/// waits while it is empty. After `close`, pushes fail and pops hand out
+/// what is left, then return None.
+pub struct BlockingQueue<T> {
+    state: Mutex<State<T>>,
+    not_full: Condvar,
+    not_empty: Condvar,
+}
+
+struct State<T> {
+    queue: Inner<T>,
+    closed: bool,
+}
+
+enum Inner<T> {
+    Bound(BoundQueue<T>),
+    Unbound(UnboundQueue<T>),
+}
+
+impl<T> Inner<T> {
+    fn is_full(&self) -> bool {
+        match *self {
+            Inner::Bound(ref q) => q.is_full(),
+            Inner::Unbound(_) => false,
+        }
+    }
+
+    fn push(&mut self, item: T) {
+        match *self {
+            Inner::Bound(ref mut q) => q.push(item),
+            Inner::Unbound(ref mut q) => q.push(item),
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        match *self {
+            Inner::Bound(ref mut q) => q.pop(),
+            Inner::Unbound(ref mut q) => q.pop(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match *self {
+            Inner::Bound(ref q) => q.len(),
+            Inner::Unbound(ref q) => q.len(),
+        }
+    }
+}
+
+impl<T> BlockingQueue<T> {
+    /// Holds up to `cap` items; `push` waits for room beyond that.
+    pub fn bounded(cap: usize) -> Self {
+        BlockingQueue::with_inner(Inner::Bound(BoundQueue::new(cap)))
+    }
+
+    /// Never full, so `push` never waits.
+    pub fn unbounded() -> Self {
+        BlockingQueue::with_inner(Inner::Unbound(UnboundQueue::new()))
+    }
+
+    fn with_inner(queue: Inner<T>) -> Self {
+        BlockingQueue {
+            state: Mutex::new(State {
+                queue,
+                closed: false,
+            }),
+            not_full: Condvar::new(),
+            not_empty: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Waits for room; fails only if the queue is closed.
+    pub fn push(&self, item: T) -> Result<(), PushError<T>> {
+        let mut st = self.lock();
+        loop {
+            if st.closed {
+                return Err(PushError::Closed(item));
+            }
+            if !st.queue.is_full() {
+                self.enqueue(st, item);
+                return Ok(());
+            }
+            blocked_span!(_blocked, "push");
+            st = self.not_full.wait(st).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    pub fn try_push(&self, item: T) -> Result<(), PushError<T>> {
+        let st = self.lock();
+        if st.closed {
+            return Err(PushError::Closed(item));
+        }
+        if st.queue.is_full() {
+            return Err(PushError::Full(item));
+        }
+        self.enqueue(st, item);
+        Ok(())
+    }
+
+    /// Like `push`, but gives up after `timeout` and hands the item back
+    /// in `PushError::Full`.
+    pub fn push_timeout(&self, item: T, timeout: Duration) -> Result<(), PushError<T>> {
+        let deadline = Instant::now() + timeout;
+        let mut st = self.lock();
+        loop {
+            if st.closed {
+                return Err(PushError::Closed(item));
+            }
+            if !st.queue.is_full() {
+                self.enqueue(st, item);
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(PushError::Full(item));
+            }
+            blocked_span!(_blocked, "push");
+            st = self
+                .not_full
+                .wait_timeout(st, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Waits for an item; None once the queue is closed and drained.
+    pub fn pop(&self) -> Option<T> {
+        let mut st = self.lock();
+        loop {
+            if let Some(item) = self.dequeue(&mut st) {
+                return Some(item);
+            }
+            if st.closed {
+                return None;
+            }
+            blocked_span!(_blocked, "pop");
+            st = self.not_empty.wait(st).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    pub fn try_pop(&self) -> Result<T, PopError> {
+        let mut st = self.lock();
+        match self.dequeue(&mut st) {
+            Some(item) => Ok(item),
+            None if st.closed => Err(PopError::Closed),
+            None => Err(PopError::Empty),
+        }
+    }
+
+    pub fn pop_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut st = self.lock();
+        loop {
+            if let Some(item) = self.dequeue(&mut st) {
+                return Ok(item);
+            }
+            if st.closed {
+                return Err(RecvTimeoutError::Closed);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            blocked_span!(_blocked, "pop");
+            st = self
+                .not_empty
+                .wait_timeout(st, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Turns away further pushes and wakes every waiting thread. Items
+    /// already queued can still be popped.
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.not_full.notify_all();
+        self.not_empty.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn enqueue(&self, mut st: MutexGuard<'_, State<T>>, item: T) {
+        st.queue.push(item);
+        self.not_empty.notify_one();
+    }
+
+    fn dequeue(&self, st: &mut State<T>) -> Option<T> {
+        let item = st.queue.pop();
+        if item.is_some() {
+            self.not_full.notify_one();
+        }
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn try_ops() {
+        let q = BlockingQueue::bounded(2);
+        assert_eq!(q.try_pop(), Err(PopError::Empty));
+        q.try_push(1).unwrap();
+        q.push(2).unwrap();
+        assert_eq!(q.try_push(3), Err(PushError::Full(3)));
+        assert_eq!(
+            q.push_timeout(3, Duration::from_millis(10)),
+            Err(PushError::Full(3))
+        );
+        assert_eq!(q.len(), 2);
+        q.close();
+        assert!(q.is_closed());
+        assert_eq!(q.push(4), Err(PushError::Closed(4)));
+        assert_eq!(q.try_push(4), Err(PushError::Closed(4)));
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.try_pop(), Ok(2));
+        assert_eq!(q.pop(), None);
+        assert_eq!(q.try_pop(), Err(PopError::Closed));
+        assert_eq!(
+            q.pop_timeout(Duration::from_secs(1)),
+            Err(RecvTimeoutError::Closed)
+        );
+    }
+
+    #[test]
+    fn timeouts() {
+        let q = Arc::new(BlockingQueue::bounded(1));
+        assert_eq!(
+            q.pop_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        q.push(0).unwrap();
+        let q2 = q.clone();
+        let h = thread::spawn(move || q2.push_timeout(1, Duration::from_secs(10)));
+        assert_eq!(q.pop_timeout(Duration::from_secs(10)), Ok(0));
+        assert_eq!(h.join().unwrap(), Ok(()));
+        assert_eq!(q.pop_timeout(Duration::from_secs(10)), Ok(1));
+    }
+
+    #[test]
+    fn close_wakes_waiters() {
+        let q = Arc::new(BlockingQueue::<i32>::unbounded());
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let q = q.clone();
+                thread::spawn(move || q.pop())
+            })
+            .collect();
+        q.close();
+        for w in waiters {
+            assert_eq!(w.join().unwrap(), None);
+        }
+    }
+
+    fn producers_consumers(q: BlockingQueue<u64>) {
+        let per_producer: u64 = if cfg!(miri) { 50 } else { 20_000 };
+        let q = Arc::new(q);
+        let producers: Vec<_> = (0..4)
+            .map(|p| {
+                let q = q.clone();
+                thread::spawn(move || {
+                    for i in 0..per_producer {
+                        q.push(p * per_producer + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        let consumers: Vec<_> = (0..3)
+            .map(|_| {
+                let q = q.clone();
+                thread::spawn(move || {
+                    let mut got = vec![];
+                    while let Some(x) = q.pop() {
+                        got.push(x);
+                    }
+                    got
+                })
+            })
+            .collect();
+        for p in producers {
+            p.join().unwrap();
+        }
+        q.close();
+        let mut all: Vec<u64> = consumers
+            .into_iter()
+            .flat_map(|c| c.join().unwrap())
+            .collect();
+        all.sort_unstable();
+        // nothing lost, nothing twice
+        assert!(all.iter().cloned().eq(0..4 * per_producer));
+    }
+
+    #[test]
+    fn bounded_threads() {
+        producers_consumers(BlockingQueue::bounded(8));
+    }
+
+    #[test]
+    fn unbounded_threads() {
+        producers_consumers(BlockingQueue::unbounded());
+    }
+}
@@ -0,0 +1,221 @@
+use super::{Queue, Unbounded};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use unbound::UnboundQueue;
+
+const QUEUED: u8 = 0;
+const CANCELLED: u8 = 1;
+const TAKEN: u8 = 2;
+
+/// An unbounded queue whose entries can be cancelled in O(1) through the
+/// `Ticket` handed out when they were pushed. A cancelled entry stays in
+/// place until `pop` reaches it, then it is dropped and skipped.
+pub struct TicketQueue<T> {
+    entries: UnboundQueue<Entry<T>>,
+    // cancelled entries still in `entries`
+    cancelled: Arc<AtomicUsize>,
+}
+
+struct Entry<T> {
+    item: T,
+    state: Arc<AtomicU8>,
+}
+
+/// Cancels one entry of a `TicketQueue`. Can be cloned and used from any
+/// thread; only the first cancel of an entry that hasn't been popped yet
+/// has any effect.
+#[derive(Clone)]
+pub struct Ticket {
+    state: Arc<AtomicU8>,
+    cancelled: Arc<AtomicUsize>,
+}
+
+impl Ticket {
+    /// Returns true if this call cancelled the entry, false if it was
+    /// already cancelled or popped.
+    pub fn cancel(&self) -> bool {
+        // counted first so the queue never sees a cancelled entry it
+        // hasn't been told about
+        self.cancelled.fetch_add(1, Ordering::SeqCst);
+        let won = self
+            .state
+            .compare_exchange(QUEUED, CANCELLED, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok();
+        if !won {
+            self.cancelled.fetch_sub(1, Ordering::SeqCst);
+        }
+        won
+    }
+}
+
+impl<T> TicketQueue<T> {
+    pub fn new() -> Self {
+        TicketQueue {
+            entries: UnboundQueue::new(),
+            cancelled: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn push_with_ticket(&mut self, item: T) -> Ticket {
+        let state = Arc::new(AtomicU8::new(QUEUED));
+        self.entries.push(Entry {
+            item,
+            state: state.clone(),
+        });
+        Ticket {
+            state,
+            cancelled: self.cancelled.clone(),
+        }
+    }
+
+    /// Cancels the entry `ticket` was issued for, if it belongs to this
+    /// queue; see `Ticket::cancel`.
+    pub fn cancel(&self, ticket: &Ticket) -> bool {
+        Arc::ptr_eq(&self.cancelled, &ticket.cancelled) && ticket.cancel()
+    }
+
+    /// Number of entries that are neither cancelled nor popped.
+    pub fn len(&self) -> usize {
+        self.queued()
+            .saturating_sub(self.cancelled.load(Ordering::SeqCst))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of entries still held, cancelled ones included.
+    pub fn queued(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+impl<T> Default for TicketQueue<T> {
+    fn default() -> Self {
+        TicketQueue::new()
+    }
+}
+
+impl<T> Queue<T> for TicketQueue<T> {
+    fn push(&mut self, item: T) {
+        self.push_with_ticket(item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        while let Some(e) = self.entries.pop() {
+            let taken = e
+                .state
+                .compare_exchange(QUEUED, TAKEN, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok();
+            if taken {
+                return Some(e.item);
+            }
+            self.cancelled.fetch_sub(1, Ordering::SeqCst);
+        }
+        None
+    }
+
+    fn len(&self) -> usize {
+        TicketQueue::len(self)
+    }
+}
+
+impl<T> Unbounded for TicketQueue<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::thread;
+
+    struct Counted<'a>(&'a Cell<usize>, i32);
+
+    impl<'a> Drop for Counted<'a> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    fn pop_all(q: &mut TicketQueue<Counted>) -> Vec<i32> {
+        let mut v = vec![];
+        while let Some(c) = q.pop() {
+            v.push(c.1);
+        }
+        v
+    }
+
+    #[test]
+    fn cancel_anywhere() {
+        let drops = Cell::new(0);
+        let mut q = TicketQueue::new();
+        let tickets: Vec<_> = (0..6)
+            .map(|i| q.push_with_ticket(Counted(&drops, i)))
+            .collect();
+        assert!(q.cancel(&tickets[0]));
+        assert!(q.cancel(&tickets[3]));
+        assert!(tickets[5].cancel());
+        assert!(!q.cancel(&tickets[3]));
+        assert_eq!((q.len(), q.queued()), (3, 6));
+        // cancelled entries are only dropped once pop reaches them
+        assert_eq!(drops.get(), 0);
+
+        assert_eq!(q.pop().map(|c| c.1), Some(1));
+        assert_eq!(drops.get(), 2);
+        assert!(!tickets[1].cancel());
+        assert_eq!(pop_all(&mut q), [2, 4]);
+        assert_eq!(drops.get(), 6);
+        assert!(q.is_empty());
+        assert_eq!((q.len(), q.queued()), (0, 0));
+        assert!(!tickets[0].cancel());
+    }
+
+    #[test]
+    fn cancel_all_then_drop() {
+        let drops = Cell::new(0);
+        let mut q = TicketQueue::new();
+        let t: Vec<_> = (0..4)
+            .map(|i| q.push_with_ticket(Counted(&drops, i)))
+            .collect();
+        for t in &t {
+            assert!(t.cancel());
+        }
+        assert!(q.is_empty());
+        q.push(Counted(&drops, 9));
+        assert_eq!(q.len(), 1);
+        drop(q);
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn foreign_ticket() {
+        let mut a = TicketQueue::new();
+        let mut b = TicketQueue::new();
+        let t = a.push_with_ticket(1);
+        b.push(2);
+        assert!(!b.cancel(&t));
+        assert_eq!(b.pop(), Some(2));
+        assert!(a.cancel(&t));
+        assert_eq!(a.pop(), None);
+    }
+
+    #[test]
+    fn cancel_from_threads() {
+        let mut q = TicketQueue::new();
+        let tickets: Vec<_> = (0..1000).map(|i| q.push_with_ticket(i)).collect();
+        let handles: Vec<_> = tickets
+            .chunks(250)
+            .map(|c| {
+                let c = c.to_vec();
+                thread::spawn(move || c.iter().step_by(2).filter(|t| t.cancel()).count())
+            })
+            .collect();
+        let n: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(n, 500);
+        assert_eq!(q.len(), 500);
+        let mut popped = vec![];
+        while let Some(v) = q.pop() {
+            popped.push(v);
+        }
+        assert_eq!(popped, (0..1000).filter(|i| i % 2 == 1).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,185 @@
+use super::{Queue, TryQueue};
+use error::PushError;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<F: Fn() -> Instant> Clock for F {
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+/// Records the arrival time of every pushed item on top of any queue of
+/// `(Instant, T)`. Arrival times only grow from front to back, so expiring
+/// old items never needs to look past the front.
+///
+/// The front entry is held outside of `inner` so it can be inspected without
+/// popping; a bounded `inner` therefore holds one item more than its
+/// capacity.
+pub struct Timestamped<Q, T, C = SystemClock> {
+    inner: Q,
+    // invariant: `inner` is empty whenever `front` is None
+    front: Option<(Instant, T)>,
+    clock: C,
+    marker: PhantomData<T>,
+}
+
+impl<Q: Queue<(Instant, T)>, T> Timestamped<Q, T> {
+    pub fn new(inner: Q) -> Self {
+        Timestamped::with_clock(inner, SystemClock)
+    }
+}
+
+impl<Q: Queue<(Instant, T)>, T, C: Clock> Timestamped<Q, T, C> {
+    pub fn with_clock(mut inner: Q, clock: C) -> Self {
+        let front = inner.pop();
+        Timestamped {
+            inner,
+            front,
+            clock,
+            marker: PhantomData,
+        }
+    }
+
+    /// Pops and drops every item that arrived before `cutoff`, returning
+    /// how many were removed.
+    pub fn purge_older_than(&mut self, cutoff: Instant) -> usize {
+        let mut n = 0;
+        while let Some(true) = self.front.as_ref().map(|f| f.0 < cutoff) {
+            self.pop();
+            n += 1;
+        }
+        n
+    }
+
+    /// How long the front item has been waiting.
+    pub fn age_of_front(&self) -> Option<Duration> {
+        self.front
+            .as_ref()
+            .map(|f| self.clock.now().saturating_duration_since(f.0))
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.front.as_ref().map(|f| &f.1)
+    }
+}
+
+impl<Q: Queue<(Instant, T)>, T, C: Clock> Queue<T> for Timestamped<Q, T, C> {
+    fn push(&mut self, item: T) {
+        let entry = (self.clock.now(), item);
+        if self.front.is_none() {
+            self.front = Some(entry);
+        } else {
+            self.inner.push(entry);
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let f = self.front.take();
+        self.front = self.inner.pop();
+        f.map(|f| f.1)
+    }
+
+    fn len(&self) -> usize {
+        usize::from(self.front.is_some()) + self.inner.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.front.is_none()
+    }
+}
+
+impl<Q: TryQueue<(Instant, T)>, T, C: Clock> TryQueue<T> for Timestamped<Q, T, C> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        let entry = (self.clock.now(), item);
+        if self.front.is_none() {
+            self.front = Some(entry);
+            return Ok(());
+        }
+        self.inner.try_push(entry).map_err(|e| match e {
+            PushError::Full(entry) => PushError::Full(entry.1),
+            PushError::Closed(entry) => PushError::Closed(entry.1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bound::BoundQueue;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use unbound::UnboundQueue;
+
+    fn mock() -> (Rc<Cell<Instant>>, impl Fn() -> Instant) {
+        let now = Rc::new(Cell::new(Instant::now()));
+        let n = now.clone();
+        (now, move || n.get())
+    }
+
+    fn advance(now: &Cell<Instant>, ms: u64) {
+        now.set(now.get() + Duration::from_millis(ms));
+    }
+
+    #[test]
+    fn purge() {
+        let (now, clock) = mock();
+        let start = now.get();
+        let mut q = Timestamped::with_clock(UnboundQueue::new(), clock);
+        for i in 0..10 {
+            q.push(i);
+            advance(&now, 10);
+        }
+        assert_eq!(q.age_of_front(), Some(Duration::from_millis(100)));
+
+        // items 0..4 arrived at 0, 10, 20, 30ms
+        assert_eq!(q.len(), 10);
+        assert_eq!(q.purge_older_than(start + Duration::from_millis(35)), 4);
+        assert_eq!(q.peek(), Some(&4));
+        assert_eq!(q.len(), 6);
+        assert_eq!(q.age_of_front(), Some(Duration::from_millis(60)));
+        assert_eq!(q.purge_older_than(start + Duration::from_millis(35)), 0);
+
+        assert_eq!(q.purge_older_than(start + Duration::from_millis(1000)), 6);
+        assert!(q.is_empty());
+        assert_eq!(q.age_of_front(), None);
+    }
+
+    #[test]
+    fn try_push_full() {
+        let mut q = Timestamped::new(BoundQueue::new(1));
+        // one item waits in front, outside the inner queue
+        assert_eq!(q.try_push('a'), Ok(()));
+        assert_eq!(q.try_push('b'), Ok(()));
+        assert_eq!(q.try_push('c'), Err(PushError::Full('c')));
+        assert_eq!(q.pop(), Some('a'));
+        assert_eq!(q.try_push('c'), Ok(()));
+    }
+
+    #[test]
+    fn newer_survive() {
+        let (now, clock) = mock();
+        let mut q = Timestamped::with_clock(BoundQueue::new(8), clock);
+        q.push("old");
+        advance(&now, 50);
+        let cutoff = now.get();
+        q.push("new");
+        q.push("newer");
+        assert_eq!(q.purge_older_than(cutoff), 1);
+        assert_eq!(q.pop(), Some("new"));
+        assert_eq!(q.pop(), Some("newer"));
+        assert_eq!(q.pop(), None);
+    }
+}
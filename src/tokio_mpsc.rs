@@ -0,0 +1,170 @@
+use super::{Queue, TryQueue};
+use error::PushError;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{self, Receiver, Sender, UnboundedReceiver, UnboundedSender};
+
+/// Drives a bounded tokio channel through the Queue trait using only
+/// `try_send`/`try_recv`, so no runtime is needed. Like a full BoundQueue,
+/// a full channel drops the pushed item; so does a closed one.
+pub struct TokioQueue<T> {
+    tx: Sender<T>,
+    rx: Receiver<T>,
+}
+
+/// The unbounded counterpart of `TokioQueue`.
+pub struct TokioUnboundedQueue<T> {
+    tx: UnboundedSender<T>,
+    rx: UnboundedReceiver<T>,
+}
+
+impl<T> TokioQueue<T> {
+    pub fn new(cap: usize) -> Self {
+        let (tx, rx) = mpsc::channel(cap);
+        TokioQueue::from_channel(tx, rx)
+    }
+
+    pub fn from_channel(tx: Sender<T>, rx: Receiver<T>) -> Self {
+        TokioQueue { tx, rx }
+    }
+
+    pub fn sender(&self) -> &Sender<T> {
+        &self.tx
+    }
+
+    pub fn into_inner(self) -> (Sender<T>, Receiver<T>) {
+        (self.tx, self.rx)
+    }
+}
+
+impl<T> TokioUnboundedQueue<T> {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        TokioUnboundedQueue::from_channel(tx, rx)
+    }
+
+    pub fn from_channel(tx: UnboundedSender<T>, rx: UnboundedReceiver<T>) -> Self {
+        TokioUnboundedQueue { tx, rx }
+    }
+
+    pub fn sender(&self) -> &UnboundedSender<T> {
+        &self.tx
+    }
+
+    pub fn into_inner(self) -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+        (self.tx, self.rx)
+    }
+}
+
+impl<T> Default for TokioUnboundedQueue<T> {
+    fn default() -> Self {
+        TokioUnboundedQueue::new()
+    }
+}
+
+impl<T> Queue<T> for TokioQueue<T> {
+    fn push(&mut self, item: T) {
+        let _ = self.tx.try_send(item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+
+    fn len(&self) -> usize {
+        self.rx.len()
+    }
+}
+
+impl<T> TryQueue<T> for TokioQueue<T> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        self.tx.try_send(item).map_err(|e| match e {
+            TrySendError::Full(item) => PushError::Full(item),
+            TrySendError::Closed(item) => PushError::Closed(item),
+        })
+    }
+}
+
+impl<T> Queue<T> for TokioUnboundedQueue<T> {
+    fn push(&mut self, item: T) {
+        let _ = self.tx.send(item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+
+    fn len(&self) -> usize {
+        self.rx.len()
+    }
+}
+
+/// Never full, but fails with `Closed` if the sender's receiver is gone,
+/// which `from_channel` allows.
+impl<T> TryQueue<T> for TokioUnboundedQueue<T> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        self.tx.send(item).map_err(|e| PushError::Closed(e.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<Q: Queue<i32>>(q: &mut Q, n: i32) -> Vec<i32> {
+        for i in 0..n {
+            q.push(i);
+        }
+        let mut v = vec![];
+        while !q.is_empty() {
+            v.push(q.pop().unwrap());
+        }
+        v
+    }
+
+    #[test]
+    fn through_trait() {
+        let mut q = TokioUnboundedQueue::new();
+        assert_eq!(roundtrip(&mut q, 100), (0..100).collect::<Vec<_>>());
+        assert_eq!(q.pop(), None);
+        assert_eq!(q.try_push(100), Ok(()));
+        assert_eq!(q.pop(), Some(100));
+
+        let mut q = TokioQueue::new(8);
+        assert_eq!(roundtrip(&mut q, 8), (0..8).collect::<Vec<_>>());
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn full() {
+        let mut q = TokioQueue::new(3);
+        // the fourth and fifth pushes are dropped
+        assert_eq!(roundtrip(&mut q, 5), vec![0, 1, 2]);
+        q.push(9);
+        assert_eq!(q.pop(), Some(9));
+    }
+
+    #[test]
+    fn closed() {
+        // receiver gone: pushes go nowhere and nothing comes back
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (_, dead_rx) = mpsc::unbounded_channel();
+        drop(rx);
+        let mut q = TokioUnboundedQueue::from_channel(tx, dead_rx);
+        q.push(1);
+        assert!(q.is_empty());
+        assert_eq!(q.try_push(2), Err(PushError::Closed(2)));
+        assert_eq!(q.pop(), None);
+
+        // other senders gone: what was sent is still drained
+        let (tx, rx) = mpsc::channel(4);
+        let other = tx.clone();
+        other.try_send(1).unwrap();
+        drop(other);
+        let mut q = TokioQueue::from_channel(tx, rx);
+        q.push(2);
+        assert_eq!(roundtrip(&mut q, 0), vec![1, 2]);
+        let (tx, mut rx) = q.into_inner();
+        drop(tx);
+        assert!(rx.try_recv().is_err());
+    }
+}
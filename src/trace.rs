@@ -0,0 +1,151 @@
+//! Events the queues emit with the `tracing` feature. They carry what a
+//! queue knows about itself (capacity, length, max length) but never an
+//! element: the queues take any `T`, with no `Debug` bound to format it by,
+//! so an eviction says that the front element went, not what it was.
+
+// Forwards to the `tracing` macro of the same name when the feature is on
+// and expands to nothing otherwise, so the arguments are never evaluated.
+macro_rules! trace {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        {
+            ::tracing::$level!($($arg)+);
+        }
+    };
+}
+
+// Binds a "blocked" span to `$guard` for the rest of the enclosing block;
+// put it right before a condvar wait.
+#[cfg(feature = "std")]
+macro_rules! blocked_span {
+    ($guard:ident, $op:expr) => {
+        #[cfg(feature = "tracing")]
+        let $guard = ::tracing::debug_span!("blocked", op = $op).entered();
+    };
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use bound::BoundQueue;
+    use pool::WorkerPool;
+    use std::fmt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{self, Event, Metadata, Subscriber};
+    use unbound::UnboundQueue;
+    use Queue;
+
+    // Records every event as "LEVEL message k=v ..." and every span as
+    // "span name k=v ...".
+    #[derive(Clone, Default)]
+    struct Capture {
+        lines: Arc<Mutex<Vec<String>>>,
+        next_id: Arc<AtomicU64>,
+    }
+
+    struct Line(String);
+
+    impl Visit for Line {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                self.0 += &format!(" {:?}", value);
+            } else {
+                self.0 += &format!(" {}={:?}", field.name(), value);
+            }
+        }
+    }
+
+    impl Subscriber for Capture {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut line = Line(format!("span {}", span.metadata().name()));
+            span.record(&mut line);
+            self.lines.lock().unwrap().push(line.0);
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut line = Line(event.metadata().level().to_string());
+            event.record(&mut line);
+            self.lines.lock().unwrap().push(line.0);
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    impl Capture {
+        fn take(&self) -> Vec<String> {
+            self.lines.lock().unwrap().drain(..).collect()
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        let capture = Capture::default();
+        tracing::subscriber::with_default(capture.clone(), || {
+            let mut q = BoundQueue::new(2);
+            for i in 0..3 {
+                q.push(i);
+            }
+            q.force_push(3);
+            drop(q);
+
+            let mut u = UnboundQueue::new();
+            u.set_max_len(1);
+            u.push(1);
+            u.push(2);
+        });
+        assert_eq!(
+            capture.take(),
+            vec![
+                "DEBUG bound queue created cap=2",
+                "WARN push rejected, queue full cap=2",
+                "DEBUG evicted front element cap=2",
+                "DEBUG bound queue dropped cap=2 len=2",
+                "DEBUG unbound queue created",
+                "DEBUG evicted front element max_len=1",
+                "DEBUG unbound queue dropped len=1 max_len=1",
+            ]
+        );
+    }
+
+    #[test]
+    fn blocked_execute() {
+        let capture = Capture::default();
+        tracing::subscriber::with_default(capture.clone(), || {
+            let (gate_tx, gate_rx) = mpsc::channel::<()>();
+            let (started_tx, started_rx) = mpsc::channel();
+            let pool = WorkerPool::new(1, 1);
+            pool.execute(move || {
+                started_tx.send(()).unwrap();
+                gate_rx.recv().unwrap();
+            });
+            started_rx.recv().unwrap();
+            pool.execute(|| ());
+            let opener = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                gate_tx.send(()).unwrap();
+            });
+            // the queue is full until the first job is let go
+            pool.execute(|| ());
+            opener.join().unwrap();
+            pool.shutdown();
+        });
+        let lines = capture.take();
+        // the wait happens inside the pool's BlockingQueue
+        assert!(lines.contains(&"span blocked op=\"push\"".to_string()));
+    }
+}
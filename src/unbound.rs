@@ -1,61 +1,476 @@
-use super::Queue;
-use std::marker::PhantomData;
-use std::ptr::NonNull;
+use super::{Queue, Unbounded};
+use alloc::alloc::{self, Layout};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use bound::BoundQueue;
+use core::cmp::{self, Ordering};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::{FromIterator, Take};
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
+use core::ptr::{self, NonNull};
+use merge::{Refs, Walk};
+#[cfg(feature = "serde")]
+use serde::de::{Deserializer, SeqAccess, Visitor};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize, Serializer};
 
-struct Node<T> {
-    next: Option<NonNull<Node<T>>>,
+/// One heap-allocated link of an `UnboundQueue`. Only reachable through raw
+/// pointers, see `into_parts` and `from_parts`.
+pub struct Node<T> {
+    next: Link<T>,
     data: T,
 }
 
+/// A link to the next node, or the end of the chain.
+pub type Link<T> = Option<NonNull<Node<T>>>;
+
+/// A FIFO queue of singly linked nodes, with no limit on its length unless
+/// one is set with `set_max_len`.
+///
+/// A queue is `Send` and `Sync` when its elements are, so one holding `Rc`s
+/// can't leave its thread:
+///
+/// ```compile_fail
+/// use queue::unbound::UnboundQueue;
+/// use std::rc::Rc;
+/// use std::thread;
+///
+/// let q = UnboundQueue::from_slice(&[Rc::new(1)]);
+/// thread::spawn(move || drop(q));
+/// ```
 pub struct UnboundQueue<T> {
-    head: Option<NonNull<Node<T>>>,
-    tail: Option<NonNull<Node<T>>>,
+    head: Link<T>,
+    tail: Link<T>,
     len: usize,
+    max_len: usize,
+    // popped nodes kept for reuse, linked through `next`; their data has
+    // been moved out
+    cache: Link<T>,
+    cached: usize,
+    cache_cap: usize,
     marker: PhantomData<Box<Node<T>>>,
 }
 
+// The queue owns its nodes outright, as a chain of Boxes would, so sending
+// it sends the elements, and a shared queue only ever hands out `&T`.
+unsafe impl<T: Send> Send for UnboundQueue<T> {}
+unsafe impl<T: Sync> Sync for UnboundQueue<T> {}
+
 impl<T> Node<T> {
     fn new(data: T) -> Self {
-        Node {
-            next: None,
-            data: data,
-        }
+        Node { next: None, data }
+    }
+
+    /// Allocates an unlinked node holding `data`.
+    pub fn alloc(data: T) -> NonNull<Node<T>> {
+        let node = Box::new(Node::new(data));
+        NonNull::from(Box::leak(node))
+    }
+
+    /// Frees a node allocated by `alloc` or taken from a queue, returning
+    /// its element. The node's link is forgotten, not followed.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be live and owned by the caller; it is dangling after.
+    pub unsafe fn free(node: NonNull<Node<T>>) -> T {
+        Box::from_raw(node.as_ptr()).data
+    }
+
+    // frees the node's memory without dropping anything in it
+    unsafe fn dealloc(node: NonNull<Node<T>>) {
+        alloc::dealloc(node.as_ptr() as *mut u8, Layout::new::<Node<T>>());
+    }
+
+    /// # Safety
+    ///
+    /// `node` must be live.
+    pub unsafe fn next(node: NonNull<Node<T>>) -> Link<T> {
+        (*node.as_ptr()).next
+    }
+
+    /// # Safety
+    ///
+    /// `node` must be live and not part of a queue.
+    pub unsafe fn set_next(node: NonNull<Node<T>>, next: Link<T>) {
+        (*node.as_ptr()).next = next;
+    }
+
+    /// # Safety
+    ///
+    /// `node` must be live for `'a` and not otherwise borrowed meanwhile.
+    pub unsafe fn data<'a>(node: NonNull<Node<T>>) -> &'a mut T {
+        &mut (*node.as_ptr()).data
     }
 }
 
 impl<T> UnboundQueue<T> {
     pub fn new() -> Self {
+        trace!(debug, "unbound queue created");
         UnboundQueue {
             head: None,
             tail: None,
             len: 0,
+            max_len: usize::MAX,
+            cache: None,
+            cached: 0,
+            cache_cap: 0,
             marker: PhantomData,
         }
     }
+
+    /// A queue that keeps the nodes of up to `cache_size` popped elements
+    /// and reuses them for later pushes instead of going back to the
+    /// allocator each time. Cached nodes are freed with the queue.
+    pub fn with_node_cache(cache_size: usize) -> Self {
+        let mut q = UnboundQueue::new();
+        q.cache_cap = cache_size;
+        q
+    }
+
+    /// A queue of clones of `s`, front first.
+    pub fn from_slice(s: &[T]) -> Self
+    where
+        T: Clone,
+    {
+        let mut q = UnboundQueue::new();
+        for x in s {
+            q.push(x.clone());
+        }
+        q
+    }
+
+    /// Takes the queue apart into its head node, tail node and length
+    /// without touching the nodes. The caller now owns the chain; rebuild a
+    /// queue with `from_parts` or free the nodes with `Node::free`, or they
+    /// leak. The max length and node cache are not kept.
+    pub fn into_parts(self) -> (Link<T>, Link<T>, usize) {
+        let mut q = ManuallyDrop::new(self);
+        q.free_cache();
+        (q.head, q.tail, q.len)
+    }
+
+    /// Adopts a chain of nodes as a queue with no max length.
+    ///
+    /// # Safety
+    ///
+    /// Either `head` and `tail` are both `None` and `len` is 0, or `len`
+    /// nodes allocated by `Node::alloc` (or taken from a queue) lead from
+    /// `head` to `tail` following `next`, with `tail.next` being `None`.
+    /// Nothing else may own or touch these nodes afterwards.
+    pub unsafe fn from_parts(head: Link<T>, tail: Link<T>, len: usize) -> Self {
+        trace!(debug, len, "unbound queue created");
+        UnboundQueue {
+            head,
+            tail,
+            len,
+            max_len: usize::MAX,
+            cache: None,
+            cached: 0,
+            cache_cap: 0,
+            marker: PhantomData,
+        }
+    }
+
+    /// Caps the queue at `n` elements: pushing onto a full queue evicts the
+    /// front element. Elements already beyond the cap are dropped from the
+    /// front right away. `usize::MAX` (the default) means no cap.
+    pub fn set_max_len(&mut self, n: usize) {
+        self.max_len = n;
+        self.trim();
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Hands over every node in O(1), leaving an empty queue with the same
+    /// max length and node cache behind.
+    pub fn take(&mut self) -> Self {
+        let mut fresh = UnboundQueue::with_node_cache(self.cache_cap);
+        fresh.max_len = self.max_len;
+        let mut taken = mem::replace(self, fresh);
+        mem::swap(&mut self.cache, &mut taken.cache);
+        mem::swap(&mut self.cached, &mut taken.cached);
+        taken
+    }
+
+    /// Exchanges the two queues' nodes and max lengths in O(1).
+    pub fn swap_with(&mut self, other: &mut Self) {
+        mem::swap(self, other);
+    }
+
+    /// Moves every node of `other` to the back of this queue in O(1),
+    /// leaving `other` empty. If that takes this queue past its max length,
+    /// elements are dropped from the front as `push` would.
+    pub fn append(&mut self, other: &mut Self) {
+        let (head, tail) = match (other.head.take(), other.tail.take()) {
+            (Some(head), Some(tail)) => (head, tail),
+            _ => return,
+        };
+        unsafe {
+            match self.tail {
+                None => self.head = Some(head),
+                Some(mut last) => last.as_mut().next = Some(head),
+            }
+        }
+        self.tail = Some(tail);
+        self.len += mem::replace(&mut other.len, 0);
+        self.trim();
+    }
+
+    /// Moves every node of `other` to the front of this queue in O(1),
+    /// leaving `other` empty. If that takes this queue past its max length,
+    /// elements are dropped from the front, so `other`'s go first.
+    pub fn prepend(&mut self, other: &mut Self) {
+        let (head, mut tail) = match (other.head.take(), other.tail.take()) {
+            (Some(head), Some(tail)) => (head, tail),
+            _ => return,
+        };
+        unsafe {
+            tail.as_mut().next = self.head;
+        }
+        if self.tail.is_none() {
+            self.tail = Some(tail);
+        }
+        self.head = Some(head);
+        self.len += mem::replace(&mut other.len, 0);
+        self.trim();
+    }
+
+    // drops front elements until the queue is within its max length
+    fn trim(&mut self) {
+        while self.len > self.max_len {
+            trace!(debug, max_len = self.max_len, "evicted front element");
+            self.pop();
+        }
+    }
+
+    /// Pushes `item`, returning the front element if that took the queue
+    /// past its max length.
+    pub fn push_evicting(&mut self, item: T) -> Option<T> {
+        let node = self.new_node(item);
+        self.link_node(node);
+        self.evict()
+    }
+
+    /// Pops the front element and pushes `item`, moving `item` into the
+    /// front node and relinking that node at the back, so nothing is freed
+    /// or allocated. On an empty queue this is `push_evicting`.
+    pub fn pop_and_push(&mut self, item: T) -> Option<T> {
+        let node = match self.head {
+            Some(node) => node,
+            None => return self.push_evicting(item),
+        };
+        unsafe {
+            self.head = Node::next(node);
+            if self.head.is_none() {
+                self.tail = None;
+            }
+            self.len -= 1;
+            let old = mem::replace(Node::data(node), item);
+            Node::set_next(node, None);
+            self.link_node(node);
+            Some(old)
+        }
+    }
+
+    /// Like `push`, but hands `item` back instead of aborting when the
+    /// node can't be allocated.
+    pub fn try_push_alloc(&mut self, item: T) -> Result<(), T> {
+        self.try_push_with(item, alloc::alloc)
+    }
+
+    /// Offers `item` to `merge` along with the back element; if `merge`
+    /// hands it back (or the queue is empty, in which case `merge` isn't
+    /// called) it is pushed as with `try_push_alloc`.
+    pub fn push_or_merge<F>(&mut self, item: T, merge: F) -> Result<(), T>
+    where
+        F: FnOnce(&mut T, T) -> Option<T>,
+    {
+        let item = match self.back_mut() {
+            None => item,
+            Some(back) => match merge(back, item) {
+                Some(item) => item,
+                None => return Ok(()),
+            },
+        };
+        self.try_push_alloc(item)
+    }
+
+    // `alloc` stands in for the global allocator so tests can make it fail
+    fn try_push_with(&mut self, item: T, alloc: unsafe fn(Layout) -> *mut u8) -> Result<(), T> {
+        if self.cache.is_some() {
+            let node = self.new_node(item);
+            self.link_node(node);
+            self.evict();
+            return Ok(());
+        }
+        let node = match NonNull::new(unsafe { alloc(Layout::new::<Node<T>>()) }) {
+            Some(p) => p.cast::<Node<T>>(),
+            None => return Err(item),
+        };
+        unsafe { ptr::write(node.as_ptr(), Node::new(item)) };
+        self.link_node(node);
+        self.evict();
+        Ok(())
+    }
+
+    // pops the front element if the last push took the queue past max_len
+    fn evict(&mut self) -> Option<T> {
+        if self.len > self.max_len {
+            trace!(debug, max_len = self.max_len, "evicted front element");
+            return self.pop();
+        }
+        None
+    }
+}
+
+impl<T> Default for UnboundQueue<T> {
+    fn default() -> Self {
+        UnboundQueue::new()
+    }
+}
+
+/// Copies every element into fresh nodes; the max length and node cache
+/// size carry over, not the cached nodes.
+impl<T: Clone> Clone for UnboundQueue<T> {
+    fn clone(&self) -> Self {
+        let mut q = UnboundQueue::with_node_cache(self.cache_cap);
+        q.max_len = self.max_len;
+        q.extend(self.iter().cloned());
+        q
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for UnboundQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Compares the elements in order; the max length is not part of it.
+impl<T: PartialEq> PartialEq for UnboundQueue<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for UnboundQueue<T> {}
+
+impl<T: Hash> Hash for UnboundQueue<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        for item in self.iter() {
+            item.hash(state);
+        }
+    }
+}
+
+/// Written as a sequence of the elements front to back. The max length is
+/// not kept.
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for UnboundQueue<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(self.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for UnboundQueue<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct SeqVisitor<T>(PhantomData<T>);
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for SeqVisitor<T> {
+            type Value = UnboundQueue<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a sequence")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut q = UnboundQueue::new();
+                while let Some(v) = seq.next_element()? {
+                    q.push(v);
+                }
+                Ok(q)
+            }
+        }
+
+        d.deserialize_seq(SeqVisitor(PhantomData))
+    }
 }
 
 impl<T> Queue<T> for UnboundQueue<T> {
     fn push(&mut self, item: T) {
-        let boxnode = Box::new(Node::new(item));
-        self.push_node(boxnode);
+        self.push_evicting(item);
     }
 
     fn pop(&mut self) -> Option<T> {
-        self.pop_node().map(|node| node.data)
+        self.pop_node().map(|node| unsafe { self.release(node) })
     }
 
-    fn is_empty(&self) -> bool {
-        self.head.is_none()
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+// a push past the max length evicts from the front, it never fails
+impl<T> Unbounded for UnboundQueue<T> {}
+
+impl<T> Walk<T> for UnboundQueue<T> {
+    fn peek(&self) -> Option<&T> {
+        UnboundQueue::peek(self)
+    }
+
+    fn walk<'a>(&'a self) -> Refs<'a, T> {
+        Box::new(self.iter())
     }
 }
 
 impl<T> UnboundQueue<T> {
-    pub fn into_iter(self) -> IntoIter<T> {
-        IntoIter(self)
+    // a node holding `item`, from the cache if it has one
+    fn new_node(&mut self, item: T) -> NonNull<Node<T>> {
+        match self.cache {
+            Some(node) => unsafe {
+                self.cache = Node::next(node);
+                self.cached -= 1;
+                ptr::write(node.as_ptr(), Node::new(item));
+                node
+            },
+            None => Node::alloc(item),
+        }
+    }
+
+    // Moves the element out of an unlinked node, then caches or frees the
+    // node. The node is dealt with first so a panicking drop of the element
+    // can't lose it.
+    unsafe fn release(&mut self, node: NonNull<Node<T>>) -> T {
+        let item = ptr::read(&(*node.as_ptr()).data);
+        if self.cached < self.cache_cap {
+            Node::set_next(node, self.cache);
+            self.cache = Some(node);
+            self.cached += 1;
+        } else {
+            Node::dealloc(node);
+        }
+        item
+    }
+
+    fn free_cache(&mut self) {
+        while let Some(node) = self.cache {
+            unsafe {
+                self.cache = Node::next(node);
+                Node::dealloc(node);
+            }
+        }
+        self.cached = 0;
     }
 
-    fn push_node(&mut self, mut node: Box<Node<T>>) {
-        let node = Some(Box::into_raw_non_null(node));
+    fn link_node(&mut self, node: NonNull<Node<T>>) {
+        let node = Some(node);
         unsafe {
             match self.tail {
                 None => self.head = node,
@@ -66,77 +481,652 @@ impl<T> UnboundQueue<T> {
         }
     }
 
-    fn pop_node(&mut self) -> Option<Box<Node<T>>> {
-        self.head.map(|node| unsafe {
-            let node = Box::from_raw(node.as_ptr());
-            self.head = node.next;
-            if let None = self.head {
-                self.tail = None;
+    // unlinks the front node, handing it to the caller
+    fn pop_node(&mut self) -> Link<T> {
+        let node = self.head?;
+        self.head = unsafe { Node::next(node) };
+        if self.head.is_none() {
+            self.tail = None;
+        }
+        self.len -= 1;
+        Some(node)
+    }
+
+    /// Runs `f` on the front element inside its node, then frees the node,
+    /// so the element is never moved. The element is removed and dropped
+    /// exactly once even if `f` panics.
+    pub fn pop_with<R, F: FnOnce(&mut T) -> R>(&mut self, f: F) -> Option<R> {
+        struct Guard<'a, T: 'a>(&'a mut UnboundQueue<T>, NonNull<Node<T>>);
+        impl<'a, T> Drop for Guard<'a, T> {
+            fn drop(&mut self) {
+                unsafe { drop(self.0.release(self.1)) };
             }
-            self.len -= 1;
-            node
-        })
+        }
+        let node = self.pop_node()?;
+        let guard = Guard(self, node);
+        Some(f(unsafe { Node::data(guard.1) }))
     }
 
-    pub fn iter(&self) -> Iter<T> {
+    /// Removes every element, yielding them front to back; see
+    /// `drain_first`.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        let len = self.len;
+        self.drain_first(len)
+    }
+
+    /// Removes the first `n` elements, or all of them if there are fewer,
+    /// yielding them front to back. Whatever is left when the iterator is
+    /// dropped gets dropped too, even if one of those drops panics.
+    pub fn drain_first(&mut self, n: usize) -> Drain<'_, T> {
+        let left = cmp::min(n, self.len);
+        Drain { q: self, left }
+    }
+
+    /// A cursor on the front element, for removing and inserting in the
+    /// middle of the queue; see `CursorMut`.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            cur: self.head,
+            prev: None,
+            index: 0,
+            q: self,
+        }
+    }
+
+    /// Drops every element; the max length is kept.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Drops elements from the back until at most `len` are left.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            drop(self.split_off(len));
+        }
+    }
+
+    /// Moves the elements from index `at` on into a new queue with the same
+    /// max length and node cache size, by walking `at` nodes and cutting one
+    /// link. `split_off(0)` is `take`; `split_off(len())` returns an empty
+    /// queue.
+    ///
+    /// Panics if `at > len()`.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(
+            at <= self.len,
+            "split index {} is past the end of a queue of length {}",
+            at,
+            self.len
+        );
+        if at == 0 {
+            return self.take();
+        }
+        let mut last = self.head;
+        for _ in 1..at {
+            last = last.and_then(|node| unsafe { node.as_ref().next });
+        }
+        let mut back = UnboundQueue::with_node_cache(self.cache_cap);
+        back.max_len = self.max_len;
+        unsafe {
+            let mut last = last.unwrap();
+            back.head = last.as_mut().next.take();
+            back.tail = back.head.and(self.tail);
+            back.len = self.len - at;
+            self.tail = Some(last);
+            self.len = at;
+        }
+        back
+    }
+
+    pub fn contains(&self, x: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter().any(|e| e == x)
+    }
+
+    /// Index from the front of the first element `f` accepts.
+    pub fn position<F: Fn(&T) -> bool>(&self, f: F) -> Option<usize> {
+        self.iter().position(f)
+    }
+
+    /// Like `retain_mut`, for predicates that only need to look.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.retain_mut(|e| f(e))
+    }
+
+    /// Keeps the elements for which `f` returns true, unlinking and
+    /// dropping the rest. `f` sees every element once, front to back.
+    pub fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        let mut prev: Link<T> = None;
+        let mut cur = self.head;
+        while let Some(node) = cur {
+            unsafe {
+                let next = (*node.as_ptr()).next;
+                if f(&mut (*node.as_ptr()).data) {
+                    prev = Some(node);
+                } else {
+                    match prev {
+                        None => self.head = next,
+                        Some(mut p) => p.as_mut().next = next,
+                    }
+                    if next.is_none() {
+                        self.tail = prev;
+                    }
+                    self.len -= 1;
+                    drop(self.release(node));
+                }
+                cur = next;
+            }
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
         Iter {
             pos: self.head.as_ref().map(|node| unsafe { node.as_ref() }),
+            len: self.len,
         }
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<T> {
+    /// The element `idx` places behind the front, found by walking the
+    /// links from head.
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        self.iter().nth(idx)
+    }
+
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        self.iter_mut().nth(idx)
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.head.map(|node| unsafe { &(*node.as_ptr()).data })
+    }
+
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        self.head.map(|node| unsafe { Node::data(node) })
+    }
+
+    /// The most recently pushed element.
+    pub fn back(&self) -> Option<&T> {
+        self.tail.map(|node| unsafe { &(*node.as_ptr()).data })
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.tail.map(|node| unsafe { Node::data(node) })
+    }
+
+    /// Links `item` in front of head, so `pop` returns it next. On a queue
+    /// at its max length the front is evicted, which is `item` itself.
+    pub fn push_front(&mut self, item: T) {
+        let node = self.new_node(item);
+        unsafe { Node::set_next(node, self.head) };
+        if self.tail.is_none() {
+            self.tail = Some(node);
+        }
+        self.head = Some(node);
+        self.len += 1;
+        self.trim();
+    }
+
+    /// Removes the most recently pushed element. The links only run from
+    /// front to back, so this walks the whole queue: O(n).
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.len <= 1 {
+            return self.pop();
+        }
+        let mut last = self.head?;
+        for _ in 2..self.len {
+            last = unsafe { Node::next(last)? };
+        }
+        unsafe {
+            let tail = last.as_mut().next.take()?;
+            self.tail = Some(last);
+            self.len -= 1;
+            Some(self.release(tail))
+        }
+    }
+
+    /// Moves the elements into a `BoundQueue` that holds `cap` of them, or
+    /// all of them if there are more, front first.
+    pub fn into_bound(self, cap: usize) -> BoundQueue<T> {
+        let mut q = BoundQueue::new(cmp::max(cap, self.len));
+        q.extend(self);
+        q
+    }
+
+    /// The first `n` elements (or all of them, if fewer), without popping.
+    pub fn peek_n(&self, n: usize) -> Take<Iter<'_, T>> {
+        self.iter().take(n)
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
         IterMut {
             pos: self.head.as_mut().map(|node| unsafe { node.as_mut() }),
+            len: self.len,
+        }
+    }
+
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.sort_by(Ord::cmp)
+    }
+
+    /// Stable bottom-up merge sort that relinks the nodes in place: no
+    /// element is moved or cloned and no memory is allocated. If `cmp`
+    /// panics, every element is still in the queue, in some order.
+    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut cmp: F) {
+        if self.len < 2 {
+            return;
+        }
+        let mut g = SortGuard {
+            rest: self.head.take(),
+            queue: self,
+            out: None,
+            out_tail: None,
+            left: None,
+            left_len: 0,
+        };
+        // each pass merges neighbouring runs of `width` nodes
+        let mut width = 1;
+        loop {
+            let mut merges = 0;
+            while g.rest.is_some() {
+                merges += 1;
+                g.left = g.rest;
+                while g.left_len < width && g.rest.is_some() {
+                    g.rest = unsafe { Node::next(g.rest.unwrap()) };
+                    g.left_len += 1;
+                }
+                let mut right_len = width;
+                while g.left_len > 0 {
+                    let take_left = match g.rest {
+                        Some(right) if right_len > 0 => unsafe {
+                            let l = &(*g.left.unwrap().as_ptr()).data;
+                            cmp(l, &(*right.as_ptr()).data) != Ordering::Greater
+                        },
+                        _ => true,
+                    };
+                    let node = if take_left {
+                        let node = g.left.unwrap();
+                        g.left = unsafe { Node::next(node) };
+                        g.left_len -= 1;
+                        node
+                    } else {
+                        let node = g.rest.unwrap();
+                        g.rest = unsafe { Node::next(node) };
+                        right_len -= 1;
+                        node
+                    };
+                    g.emit(node);
+                }
+                // what is left of the right run is already linked in order
+                while right_len > 0 && g.rest.is_some() {
+                    let node = g.rest.unwrap();
+                    g.rest = unsafe { Node::next(node) };
+                    right_len -= 1;
+                    g.emit(node);
+                }
+            }
+            unsafe { Node::set_next(g.out_tail.unwrap(), None) };
+            g.rest = g.out.take();
+            g.out_tail = None;
+            if merges == 1 {
+                return;
+            }
+            width *= 2;
         }
     }
 }
 
 impl<T> Drop for UnboundQueue<T> {
     fn drop(&mut self) {
-        while let Some(_) = self.pop_node() {}
+        trace!(
+            debug,
+            len = self.len,
+            max_len = self.max_len,
+            "unbound queue dropped"
+        );
+        // nothing left to cache for, and the cache is already freed if an
+        // element's drop panics
+        self.free_cache();
+        self.cache_cap = 0;
+        while self.pop().is_some() {}
+    }
+}
+
+// The state of a sort between steps. Dropping it links whatever is
+// mid-merge back into one chain and hands it to the queue, so a panicking
+// comparison doesn't lose nodes.
+struct SortGuard<'a, T: 'a> {
+    queue: &'a mut UnboundQueue<T>,
+    // merged so far in this pass; `out_tail.next` is stale
+    out: Link<T>,
+    out_tail: Link<T>,
+    // the unmerged part of the left run, `left_len` nodes; the last one's
+    // `next` is stale
+    left: Link<T>,
+    left_len: usize,
+    // everything after the left run, starting with the rest of the right run
+    rest: Link<T>,
+}
+
+impl<'a, T> SortGuard<'a, T> {
+    fn emit(&mut self, node: NonNull<Node<T>>) {
+        match self.out_tail {
+            None => self.out = Some(node),
+            Some(tail) => unsafe { Node::set_next(tail, Some(node)) },
+        }
+        self.out_tail = Some(node);
+    }
+}
+
+impl<'a, T> Drop for SortGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            let mut rest = self.rest;
+            if self.left_len > 0 {
+                let mut last = self.left.unwrap();
+                for _ in 1..self.left_len {
+                    last = Node::next(last).unwrap();
+                }
+                Node::set_next(last, rest);
+                rest = self.left;
+            }
+            match self.out_tail {
+                None => self.out = rest,
+                Some(tail) => Node::set_next(tail, rest),
+            }
+            let mut tail = self.out;
+            while let Some(next) = tail.and_then(|node| Node::next(node)) {
+                tail = Some(next);
+            }
+            self.queue.head = self.out;
+            self.queue.tail = tail;
+        }
     }
 }
 
 pub struct Iter<'a, T: 'a> {
     pos: Option<&'a Node<T>>,
+    len: usize,
 }
 
 pub struct IterMut<'a, T: 'a> {
     pos: Option<&'a mut Node<T>>,
+    len: usize,
 }
 
+// Iter only lends out `&T` and IterMut `&mut T`, each element at most once,
+// so they are as Send and Sync as those references.
+unsafe impl<'a, T: Sync> Send for Iter<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for Iter<'a, T> {}
+unsafe impl<'a, T: Send> Send for IterMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for IterMut<'a, T> {}
+
 pub struct IntoIter<T>(UnboundQueue<T>);
 
+pub struct Drain<'a, T: 'a> {
+    q: &'a mut UnboundQueue<T>,
+    // elements still to be removed
+    left: usize,
+}
+
+impl<'a, T> Iterator for Drain<'a, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        if self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        self.q.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Drain<'a, T> {}
+
+impl<'a, T> Drop for Drain<'a, T> {
+    fn drop(&mut self) {
+        // keeps going if dropping an element panics
+        struct Guard<'r, 'a: 'r, T: 'a>(&'r mut Drain<'a, T>);
+        impl<'r, 'a, T> Drop for Guard<'r, 'a, T> {
+            fn drop(&mut self) {
+                self.0.for_each(drop);
+            }
+        }
+        while let Some(item) = self.next() {
+            let guard = Guard(self);
+            drop(item);
+            mem::forget(guard);
+        }
+    }
+}
+
+/// Walks an `UnboundQueue` front to back, removing and inserting as it
+/// goes, like `LinkedList`'s cursor but forward only. Past the back
+/// element the cursor sits on a ghost position that holds nothing;
+/// moving on from there starts over at the front.
+///
+/// Inserts aren't held to the max length while the cursor is alive; the
+/// queue is trimmed from the front when it is dropped.
+pub struct CursorMut<'a, T: 'a> {
+    q: &'a mut UnboundQueue<T>,
+    // None on the ghost
+    cur: Link<T>,
+    // the node linking to `cur`, None if `cur` is head
+    prev: Link<T>,
+    index: usize,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    /// Position from the front, None on the ghost.
+    pub fn index(&self) -> Option<usize> {
+        self.cur.map(|_| self.index)
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.cur.map(|node| unsafe { Node::data(node) })
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        let next = match self.cur {
+            Some(node) => unsafe { Node::next(node) },
+            None => self.q.head,
+        };
+        next.map(|node| unsafe { Node::data(node) })
+    }
+
+    pub fn move_next(&mut self) {
+        match self.cur {
+            Some(node) => {
+                self.prev = Some(node);
+                self.cur = unsafe { Node::next(node) };
+                self.index += 1;
+            }
+            None => {
+                self.prev = None;
+                self.cur = self.q.head;
+                self.index = 0;
+            }
+        }
+    }
+
+    /// Unlinks the current element and moves on to the one after it.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.cur?;
+        unsafe {
+            let next = Node::next(node);
+            match self.prev {
+                None => self.q.head = next,
+                Some(mut p) => p.as_mut().next = next,
+            }
+            if next.is_none() {
+                self.q.tail = self.prev;
+            }
+            self.q.len -= 1;
+            self.cur = next;
+            Some(self.q.release(node))
+        }
+    }
+
+    /// Links `item` in after the current element, or at the front if the
+    /// cursor is on the ghost. The cursor stays where it is.
+    pub fn insert_after(&mut self, item: T) {
+        let node = self.q.new_node(item);
+        unsafe {
+            match self.cur {
+                Some(mut cur) => {
+                    Node::set_next(node, cur.as_ref().next);
+                    cur.as_mut().next = Some(node);
+                    if self.q.tail == Some(cur) {
+                        self.q.tail = Some(node);
+                    }
+                }
+                None => {
+                    Node::set_next(node, self.q.head);
+                    self.q.head = Some(node);
+                    if self.q.tail.is_none() {
+                        self.q.tail = Some(node);
+                    }
+                    // the ghost follows the back element
+                    self.prev = self.q.tail;
+                    self.index += 1;
+                }
+            }
+        }
+        self.q.len += 1;
+    }
+}
+
+impl<'a, T> Drop for CursorMut<'a, T> {
+    fn drop(&mut self) {
+        self.q.trim();
+    }
+}
+
+impl<T> IntoIterator for UnboundQueue<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a UnboundQueue<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut UnboundQueue<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+impl<T> FromIterator<T> for UnboundQueue<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut q = UnboundQueue::new();
+        q.extend(iter);
+        q
+    }
+}
+
+impl<T> From<Vec<T>> for UnboundQueue<T> {
+    fn from(v: Vec<T>) -> Self {
+        v.into_iter().collect()
+    }
+}
+
+impl<T> From<VecDeque<T>> for UnboundQueue<T> {
+    fn from(d: VecDeque<T>) -> Self {
+        d.into_iter().collect()
+    }
+}
+
+impl<T> From<UnboundQueue<T>> for Vec<T> {
+    fn from(q: UnboundQueue<T>) -> Self {
+        q.into_iter().collect()
+    }
+}
+
+impl<T> From<UnboundQueue<T>> for VecDeque<T> {
+    fn from(q: UnboundQueue<T>) -> Self {
+        q.into_iter().collect()
+    }
+}
+
+/// Pushes every item in turn, so a max length evicts from the front as
+/// `push` does.
+impl<T> Extend<T> for UnboundQueue<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
 impl<T> Iterator for IntoIter<T> {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> {
         self.0.pop()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
 }
 
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
 impl<'a, T> Iterator for Iter<'a, T> {
     type Item = &'a T;
     fn next(&mut self) -> Option<Self::Item> {
         self.pos.map(|node| {
             self.pos = node.next.as_ref().map(|node| unsafe { node.as_ref() });
+            self.len -= 1;
             &node.data
         })
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
 }
 
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
 impl<'a, T> Iterator for IterMut<'a, T> {
     type Item = &'a mut T;
     fn next(&mut self) -> Option<Self::Item> {
         self.pos.take().map(|node| {
             self.pos = node.next.as_mut().map(|node| unsafe { node.as_mut() });
+            self.len -= 1;
             &mut node.data
         })
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
 }
 
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
 
     #[test]
     fn test_push_pop() {
@@ -194,4 +1184,863 @@ mod tests {
         assert_eq!(iter.next(), Some(&mut 3));
         assert_eq!(iter.next(), None);
     }
+
+    #[test]
+    fn iter_traits() {
+        let mut q: UnboundQueue<_> = (0..5).collect();
+        q.extend(5..8);
+        let mut iter = q.iter();
+        assert_eq!(iter.len(), 8);
+        iter.next();
+        assert_eq!(iter.size_hint(), (7, Some(7)));
+
+        for x in &mut q {
+            *x += 1;
+        }
+        assert_eq!((&q).into_iter().sum::<i32>(), 36);
+        assert_eq!(q.iter_mut().len(), 8);
+
+        let mut bounded = UnboundQueue::new();
+        bounded.set_max_len(3);
+        bounded.extend(0..10);
+        let iter = bounded.into_iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.collect::<Vec<_>>(), [7, 8, 9]);
+    }
+
+    #[test]
+    fn std_traits() {
+        let mut q: UnboundQueue<_> = (0..4).map(|i| i.to_string()).collect();
+        q.set_max_len(6);
+        assert_eq!(format!("{:?}", q), r#"["0", "1", "2", "3"]"#);
+
+        let c = q.clone();
+        assert_eq!(c, q);
+        assert_eq!(c.max_len(), 6);
+        // the copy has nodes of its own
+        assert!(c.iter().zip(q.iter()).all(|(a, b)| !ptr::eq(a, b)));
+        let mut iter = q.iter();
+        assert_eq!(iter.next().map(|s| &s[..]), Some("0"));
+        drop(c);
+        assert_eq!(iter.cloned().collect::<Vec<_>>(), ["1", "2", "3"]);
+
+        let mut other = UnboundQueue::new();
+        other.extend(q.iter().cloned());
+        let hash = |q: &UnboundQueue<String>| {
+            let mut h = ::std::collections::hash_map::DefaultHasher::new();
+            q.hash(&mut h);
+            h.finish()
+        };
+        assert_eq!(other, q);
+        assert_eq!(hash(&other), hash(&q));
+        other.pop();
+        assert_ne!(other, q);
+    }
+
+    #[test]
+    fn drain() {
+        let mut q: UnboundQueue<_> = (0..6).map(|i| i.to_string()).collect();
+        let mut d = q.drain_first(3);
+        assert_eq!(d.len(), 3);
+        assert_eq!(d.next().as_deref(), Some("0"));
+        drop(d);
+        assert_eq!(q.len(), 3);
+        assert_eq!(q.peek().map(|s| &s[..]), Some("3"));
+        q.push("6".to_string());
+        assert_eq!(q.drain().collect::<Vec<_>>(), ["3", "4", "5", "6"]);
+        assert!(q.is_empty());
+        assert_eq!(q.back(), None);
+        assert_eq!(q.drain_first(2).count(), 0);
+        q.push("7".to_string());
+        assert_eq!(q.iter().collect::<Vec<_>>(), ["7"]);
+    }
+
+    #[test]
+    fn drain_drop_panic() {
+        struct Bomb<'a>(&'a Cell<usize>, bool);
+        impl<'a> Drop for Bomb<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+                assert!(!self.1, "boom");
+            }
+        }
+
+        let drops = Cell::new(0);
+        let mut q = UnboundQueue::new();
+        for i in 0..6 {
+            q.push(Bomb(&drops, i == 2));
+        }
+        let r = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut d = q.drain_first(5);
+            d.next();
+        }));
+        assert!(r.is_err());
+        // all five were dropped and the sixth is still queued
+        assert_eq!(drops.get(), 5);
+        assert_eq!(q.len(), 1);
+        drop(q);
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[test]
+    fn append_prepend() {
+        fn q(r: ::std::ops::Range<i32>) -> UnboundQueue<String> {
+            r.map(|i| i.to_string()).collect()
+        }
+        fn v(q: &UnboundQueue<String>) -> Vec<i32> {
+            q.iter().map(|s| s.parse().unwrap()).collect()
+        }
+        for (a, b) in [(0..0, 0..0), (0..3, 0..0), (0..0, 3..5), (0..3, 3..5)] {
+            let mut front = q(a.clone());
+            let mut back = q(b.clone());
+            front.append(&mut back);
+            assert!(back.is_empty() && back.peek().is_none());
+            front.push("9".to_string());
+            back.push("8".to_string());
+            assert_eq!(v(&back), [8]);
+            let mut want: Vec<_> = a.clone().chain(b.clone()).collect();
+            want.push(9);
+            assert_eq!(v(&front), want);
+            assert_eq!(front.len(), want.len());
+
+            let mut front = q(a.clone());
+            let mut back = q(b.clone());
+            back.prepend(&mut front);
+            assert!(front.is_empty() && front.back().is_none());
+            back.push("9".to_string());
+            assert_eq!(v(&back), want);
+            assert_eq!(back.len(), want.len());
+        }
+
+        // the max length drops from the front after splicing
+        let mut a = q(0..4);
+        a.set_max_len(5);
+        a.append(&mut q(4..7));
+        assert_eq!(v(&a), [2, 3, 4, 5, 6]);
+        a.prepend(&mut q(10..12));
+        assert_eq!(v(&a), [2, 3, 4, 5, 6]);
+        a.pop();
+        a.push("7".to_string());
+        assert_eq!(v(&a), [3, 4, 5, 6, 7]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use bincode::Options;
+
+        let opts = bincode::DefaultOptions::new();
+        for n in [0, 1, 5] {
+            let q: UnboundQueue<_> = (0..n).map(|i| i.to_string()).collect();
+            let json = serde_json::to_string(&q).unwrap();
+            assert_eq!(
+                serde_json::from_str::<UnboundQueue<String>>(&json).unwrap(),
+                q
+            );
+            let bytes = opts.serialize(&q).unwrap();
+            assert_eq!(opts.deserialize::<UnboundQueue<String>>(&bytes).unwrap(), q);
+        }
+        let q: UnboundQueue<u32> = serde_json::from_str("[3, 1, 2]").unwrap();
+        assert_eq!(serde_json::to_string(&q).unwrap(), "[3,1,2]");
+    }
+
+    #[test]
+    fn truncate_clear() {
+        let drops = Cell::new(0);
+        for keep in 0..7 {
+            drops.set(0);
+            let mut q = UnboundQueue::new();
+            for i in 0..5 {
+                q.push(Counted(&drops, i));
+            }
+            q.truncate(keep);
+            assert_eq!(q.len(), keep.min(5));
+            assert_eq!(drops.get(), 5 - q.len());
+            // the tail is right: pushes land after the kept elements
+            q.push(Counted(&drops, 9));
+            let ids: Vec<_> = q.iter().map(|c| c.1).collect();
+            let mut want: Vec<_> = (0..keep.min(5) as i32).collect();
+            want.push(9);
+            assert_eq!(ids, want);
+            q.clear();
+            assert!(q.is_empty() && q.back().is_none());
+            assert_eq!(drops.get(), 6);
+        }
+
+        let mut q: UnboundQueue<_> = (0..4).collect();
+        q.set_max_len(3);
+        q.clear();
+        assert_eq!(q.max_len(), 3);
+    }
+
+    fn send_sync<T: Send + Sync>() {}
+
+    // these only compile if the types are covariant in T
+    #[allow(dead_code)]
+    fn shorten<'a>(q: UnboundQueue<&'static str>) -> UnboundQueue<&'a str> {
+        q
+    }
+
+    #[allow(dead_code)]
+    fn shorten_iter<'i, 'a>(it: Iter<'i, &'static str>) -> Iter<'i, &'a str> {
+        it
+    }
+
+    #[test]
+    fn auto_traits() {
+        send_sync::<UnboundQueue<i32>>();
+        send_sync::<Iter<'_, i32>>();
+        send_sync::<IterMut<'_, i32>>();
+        send_sync::<IntoIter<i32>>();
+        send_sync::<Drain<'_, i32>>();
+
+        let mut q: UnboundQueue<_> = (0..100).map(|i| i.to_string()).collect();
+        let q = ::std::thread::spawn(move || {
+            q.pop();
+            q
+        })
+        .join()
+        .unwrap();
+        assert_eq!(q.len(), 99);
+        assert_eq!(q.peek().map(|s| &s[..]), Some("1"));
+    }
+
+    struct Counted<'a>(&'a Cell<usize>, i32);
+
+    impl<'a> Drop for Counted<'a> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn retain_mut() {
+        let drops = Cell::new(0);
+        let mut q = UnboundQueue::new();
+        for i in 0..10 {
+            q.push(Counted(&drops, i));
+        }
+        let mut calls = 0;
+        q.retain_mut(|c| {
+            calls += 1;
+            c.1 *= 10;
+            c.1 % 30 == 0
+        });
+        assert_eq!(calls, 10);
+        assert_eq!(drops.get(), 6);
+        assert_eq!(q.len, 4);
+        let v: Vec<_> = q.iter().map(|c| c.1).collect();
+        assert_eq!(v, vec![0, 30, 60, 90]);
+
+        // the retained tail must still be linkable
+        q.push(Counted(&drops, 100));
+        assert_eq!(q.iter().last().unwrap().1, 100);
+    }
+
+    #[test]
+    fn get() {
+        let mut q: UnboundQueue<_> = (0..4).collect();
+        q.pop();
+        assert_eq!(q.get(0), Some(&1));
+        assert_eq!(q.get(2), Some(&3));
+        assert_eq!(q.get(3), None);
+        *q.get_mut(2).unwrap() = 30;
+        assert_eq!(q.back(), Some(&30));
+    }
+
+    #[test]
+    fn deque_ends() {
+        let mut q = UnboundQueue::new();
+        assert_eq!(q.pop_back(), None);
+        q.push_front(2);
+        assert_eq!(q.back(), Some(&2));
+        q.push_front(1);
+        q.push(3);
+        q.push_front(0);
+        assert_eq!(q.len(), 4);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(q.pop_back(), Some(3));
+        assert_eq!(q.back(), Some(&2));
+        // the new tail still links on push
+        q.push(4);
+        assert_eq!(q.iter_mut().len(), 4);
+        assert_eq!(q.pop_back(), Some(4));
+        assert_eq!(q.pop(), Some(0));
+        assert_eq!(q.pop_back(), Some(2));
+        assert_eq!(q.pop_back(), Some(1));
+        assert!(q.is_empty() && q.back().is_none());
+        q.push(5);
+        assert_eq!(q.pop_back(), Some(5));
+
+        q.extend(0..3);
+        q.set_max_len(3);
+        q.push_front(-1);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn cursor() {
+        let drops = Cell::new(0);
+        let mut q = UnboundQueue::new();
+        for i in 0..6 {
+            q.push(Counted(&drops, i));
+        }
+        let ids = |q: &UnboundQueue<Counted>| q.iter().map(|c| c.1).collect::<Vec<_>>();
+        {
+            let mut c = q.cursor_front_mut();
+            // head
+            assert_eq!(c.remove_current().map(|c| c.1), Some(0));
+            assert_eq!(c.index(), Some(0));
+            assert_eq!(c.current().map(|c| c.1), Some(1));
+            // interior
+            c.move_next();
+            c.move_next();
+            assert_eq!(c.remove_current().map(|c| c.1), Some(3));
+            assert_eq!(c.peek_next().map(|c| c.1), Some(5));
+            c.insert_after(Counted(&drops, 10));
+            c.move_next();
+            c.move_next();
+            // tail
+            assert_eq!(c.index(), Some(4));
+            assert_eq!(c.remove_current().map(|c| c.1), Some(5));
+            assert_eq!(c.index(), None);
+            assert!(c.current().is_none());
+        }
+        assert_eq!(drops.get(), 3);
+        assert_eq!(q.len(), 4);
+        assert_eq!(q.back().map(|c| c.1), Some(10));
+        q.push(Counted(&drops, 11));
+        assert_eq!(ids(&q), [1, 2, 4, 10, 11]);
+
+        {
+            let mut c = q.cursor_front_mut();
+            while c.current().is_some() {
+                c.move_next();
+            }
+            // the ghost inserts at the front and wraps around to it
+            c.insert_after(Counted(&drops, -1));
+            assert!(c.current().is_none());
+            c.move_next();
+            assert_eq!(c.current().map(|c| c.1), Some(-1));
+            while c.remove_current().is_some() {}
+            c.insert_after(Counted(&drops, 20));
+        }
+        assert_eq!(ids(&q), [20]);
+        assert_eq!(drops.get(), 9);
+        q.push(Counted(&drops, 21));
+        assert_eq!(q.pop().map(|c| c.1), Some(20));
+        assert_eq!(q.back().map(|c| c.1), Some(21));
+
+        // the max length is enforced once the cursor is gone
+        q.set_max_len(2);
+        {
+            let mut c = q.cursor_front_mut();
+            c.insert_after(Counted(&drops, 22));
+            c.insert_after(Counted(&drops, 23));
+            assert_eq!(c.peek_next().map(|c| c.1), Some(23));
+        }
+        assert_eq!(ids(&q), [23, 22]);
+    }
+
+    #[test]
+    fn contains_retain() {
+        let mut q: UnboundQueue<_> = (0..6).collect();
+        assert!(q.contains(&5) && !q.contains(&6));
+        assert_eq!(q.position(|&x| x * x > 10), Some(4));
+        assert_eq!(q.position(|&x| x > 5), None);
+
+        q.retain(|&x| x != 5);
+        assert_eq!(q.back(), Some(&4));
+        q.push(7);
+        q.retain(|&x| x % 2 == 1);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), [1, 3, 7]);
+        q.retain(|_| false);
+        assert!(q.is_empty() && q.back().is_none());
+    }
+
+    #[test]
+    fn retain_mut_ends() {
+        let mut q = UnboundQueue::new();
+        for i in 0..5 {
+            q.push(i);
+        }
+        q.retain_mut(|x| *x != 0 && *x != 4);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), vec![1, 2, 3]);
+        q.push(5);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), vec![1, 2, 3, 5]);
+
+        q.retain_mut(|_| true);
+        assert_eq!(q.len, 4);
+
+        q.retain_mut(|_| false);
+        assert_eq!(q.len, 0);
+        assert!(q.is_empty());
+        assert!(q.tail.is_none());
+        q.push(6);
+        assert_eq!(q.pop(), Some(6));
+    }
+
+    #[test]
+    fn push_evicting() {
+        let mut q = UnboundQueue::new();
+        q.set_max_len(4);
+        let mut evicted = vec![];
+        for i in 0..12 {
+            if let Some(v) = q.push_evicting(i) {
+                evicted.push(v);
+            }
+            assert!(q.len <= 4);
+        }
+        assert_eq!(q.len, 4);
+        assert_eq!(evicted, (0..8).collect::<Vec<_>>());
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), vec![8, 9, 10, 11]);
+
+        q.push(12);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), vec![9, 10, 11, 12]);
+        q.set_max_len(2);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), vec![11, 12]);
+
+        q.set_max_len(0);
+        assert_eq!(q.push_evicting(13), Some(13));
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn from_slice() {
+        let src = vec!["x".to_string(), "y".to_string()];
+        let mut q = UnboundQueue::from_slice(&src);
+        assert_eq!(q.len, 2);
+        q.iter_mut().for_each(|s| s.push('?'));
+        assert_eq!(q.pop().as_deref(), Some("x?"));
+        assert_eq!(q.pop().as_deref(), Some("y?"));
+        assert_eq!(q.pop(), None);
+        assert_eq!(src, ["x", "y"]);
+    }
+
+    #[test]
+    fn pop_with() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let drops = Cell::new(0);
+        {
+            let mut q = UnboundQueue::new();
+            for i in 0..3 {
+                q.push(Counted(&drops, i));
+            }
+            assert_eq!(q.pop_with(|c| c.1 * 10), Some(0));
+            assert_eq!(drops.get(), 1);
+            let r = catch_unwind(AssertUnwindSafe(|| q.pop_with(|_| panic!("boom"))));
+            assert!(r.is_err());
+            assert_eq!(drops.get(), 2);
+            assert_eq!(q.len, 1);
+            assert_eq!(q.iter().next().unwrap().1, 2);
+        }
+        assert_eq!(drops.get(), 3);
+        assert_eq!(UnboundQueue::<i32>::new().pop_with(|x| *x), None);
+    }
+
+    #[test]
+    fn parts_round_trip() {
+        let (head, tail, len) = UnboundQueue::<i32>::new().into_parts();
+        assert!(head.is_none() && tail.is_none() && len == 0);
+        let mut q = unsafe { UnboundQueue::from_parts(head, tail, len) };
+        q.push(1);
+        assert_eq!(q.pop(), Some(1));
+
+        let mut q = UnboundQueue::new();
+        q.set_max_len(3);
+        for i in 0..5 {
+            q.push(i.to_string());
+        }
+        let (head, tail, len) = q.into_parts();
+        assert_eq!(len, 3);
+        unsafe {
+            assert_eq!(Node::data(head.unwrap()), "2");
+            assert_eq!(Node::data(tail.unwrap()), "4");
+            assert!(Node::next(tail.unwrap()).is_none());
+        }
+        let mut q = unsafe { UnboundQueue::from_parts(head, tail, len) };
+        assert_eq!(q.max_len(), usize::MAX);
+        q.push("5".to_string());
+        let v: Vec<_> = q.into_iter().collect();
+        assert_eq!(v, ["2", "3", "4", "5"]);
+    }
+
+    #[test]
+    fn splice_chains() {
+        let mut a = UnboundQueue::new();
+        let mut b = UnboundQueue::new();
+        for i in 0..3 {
+            a.push(Box::new(i));
+            b.push(Box::new(i + 3));
+        }
+        let (a_head, a_tail, a_len) = a.into_parts();
+        let (b_head, b_tail, b_len) = b.into_parts();
+
+        // a chain built by hand, linked after b
+        let mut c_head = None;
+        for i in (6..9).rev() {
+            let node = Node::alloc(Box::new(i));
+            unsafe { Node::set_next(node, c_head) };
+            c_head = Some(node);
+        }
+        let mut c_tail = c_head.unwrap();
+        unsafe {
+            while let Some(next) = Node::next(c_tail) {
+                c_tail = next;
+            }
+            Node::set_next(a_tail.unwrap(), b_head);
+            Node::set_next(b_tail.unwrap(), c_head);
+        }
+
+        let mut q = unsafe { UnboundQueue::from_parts(a_head, Some(c_tail), a_len + b_len + 3) };
+        assert_eq!(
+            q.iter().map(|b| **b).collect::<Vec<_>>(),
+            (0..9).collect::<Vec<_>>()
+        );
+        assert_eq!(q.pop(), Some(Box::new(0)));
+        q.push(Box::new(9));
+        // a detached node can be freed on its own
+        let extra = Node::alloc(Box::new(-1));
+        assert_eq!(unsafe { Node::free(extra) }, Box::new(-1));
+        assert_eq!(
+            q.into_iter().map(|b| *b).collect::<Vec<_>>(),
+            (1..10).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn peek_n() {
+        let mut q = UnboundQueue::new();
+        assert_eq!(q.peek_n(4).next(), None);
+        for i in 0..5 {
+            q.push(i);
+        }
+        assert_eq!(q.peek_n(0).next(), None);
+        let front: Vec<_> = q.peek_n(3).collect();
+        assert_eq!(front, [&0, &1, &2]);
+        assert_eq!(q.peek_n(16).count(), 5);
+        for i in 0..5 {
+            assert_eq!(q.pop(), Some(i));
+        }
+    }
+
+    unsafe fn no_memory(_: Layout) -> *mut u8 {
+        ptr::null_mut()
+    }
+
+    #[test]
+    fn try_push_alloc() {
+        let drops = Cell::new(0);
+        let mut q = UnboundQueue::new();
+        q.push(Counted(&drops, 1));
+        match q.try_push_with(Counted(&drops, 2), no_memory) {
+            Err(Counted(_, v)) => assert_eq!(v, 2),
+            Ok(()) => panic!("pushed without memory"),
+        }
+        // the rejected item was handed back and dropped here, not in the queue
+        assert_eq!(drops.get(), 1);
+        assert_eq!(q.len, 1);
+        assert!(q.try_push_alloc(Counted(&drops, 3)).is_ok());
+        assert_eq!(q.iter().map(|c| c.1).collect::<Vec<_>>(), [1, 3]);
+
+        q.set_max_len(2);
+        assert!(q.try_push_with(Counted(&drops, 4), alloc::alloc).is_ok());
+        assert_eq!(drops.get(), 2);
+        assert_eq!(q.iter().map(|c| c.1).collect::<Vec<_>>(), [3, 4]);
+        drop(q);
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn push_or_merge() {
+        let calls = Cell::new(0);
+        let same_page = |back: &mut (u32, u32), item: (u32, u32)| {
+            calls.set(calls.get() + 1);
+            if back.0 == item.0 {
+                back.1 += item.1;
+                None
+            } else {
+                Some(item)
+            }
+        };
+        let mut q = UnboundQueue::new();
+        assert_eq!(q.push_or_merge((1, 1), same_page), Ok(()));
+        assert_eq!(calls.get(), 0);
+        assert_eq!(q.push_or_merge((1, 2), same_page), Ok(()));
+        assert_eq!(q.push_or_merge((2, 1), same_page), Ok(()));
+        assert_eq!(q.push_or_merge((2, 1), same_page), Ok(()));
+        assert_eq!(calls.get(), 3);
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), [(1, 3), (2, 2)]);
+
+        // a declined merge still respects max_len
+        q.set_max_len(2);
+        assert_eq!(q.push_or_merge((3, 1), same_page), Ok(()));
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), [(2, 2), (3, 1)]);
+        q.pop();
+        q.pop();
+        assert_eq!(q.push_or_merge((3, 1), same_page), Ok(()));
+        assert_eq!(calls.get(), 4);
+        assert_eq!(q.len, 1);
+    }
+
+    // (key, original position) pairs with keys in 0..keys
+    fn random_pairs(seed: &mut u32, n: usize, keys: u32) -> Vec<(u32, usize)> {
+        (0..n)
+            .map(|i| {
+                *seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                ((*seed >> 16) % keys, i)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sort_matches_vec() {
+        let mut seed = 1;
+        for &n in &[0, 1, 2, 3, 7, 8, 9, 31, 64, 100] {
+            for &keys in &[1, 3, 1000] {
+                let v = random_pairs(&mut seed, n, keys);
+                let mut q = UnboundQueue::from_slice(&v);
+                let addrs: Vec<*const (u32, usize)> = q.iter().map(|x| x as *const _).collect();
+                q.sort_by(|a, b| a.0.cmp(&b.0));
+
+                let mut expected = v.clone();
+                expected.sort_by_key(|x| x.0);
+                assert_eq!(q.iter().cloned().collect::<Vec<_>>(), expected);
+                // the elements stayed in their nodes
+                for x in q.iter() {
+                    assert_eq!(x as *const _, addrs[x.1]);
+                }
+
+                assert_eq!(q.len, n);
+                q.push((keys, n));
+                assert_eq!(q.iter().last(), Some(&(keys, n)));
+                q.sort();
+                assert!(q
+                    .iter()
+                    .cloned()
+                    .eq(expected.into_iter().chain(Some((keys, n)))));
+            }
+        }
+    }
+
+    #[test]
+    fn sort_panic() {
+        let drops = Cell::new(0);
+        for stop in 0..12 {
+            let mut q = UnboundQueue::new();
+            for i in &[5, 3, 9, 1, 4, 4, 0, 8] {
+                q.push(Counted(&drops, *i));
+            }
+            let calls = Cell::new(0);
+            let r = panic::catch_unwind(AssertUnwindSafe(|| {
+                q.sort_by(|a, b| {
+                    calls.set(calls.get() + 1);
+                    if calls.get() > stop {
+                        panic!("comparison failed");
+                    }
+                    a.1.cmp(&b.1)
+                })
+            }));
+            assert_eq!(r.is_err(), stop < calls.get());
+            let mut items: Vec<_> = q.iter().map(|c| c.1).collect();
+            items.sort();
+            assert_eq!(items, [0, 1, 3, 4, 4, 5, 8, 9]);
+            assert_eq!(q.len, 8);
+            q.push(Counted(&drops, 10));
+            assert_eq!(q.iter().last().map(|c| c.1), Some(10));
+        }
+        assert_eq!(drops.get(), 12 * 9);
+    }
+
+    #[test]
+    fn peek_and_back() {
+        let mut q = UnboundQueue::new();
+        assert_eq!((q.peek(), q.back()), (None, None));
+        assert!(q.peek_mut().is_none() && q.back_mut().is_none());
+        q.push(1);
+        assert_eq!((q.peek(), q.back()), (Some(&1), Some(&1)));
+        q.push(2);
+        q.push(3);
+        *q.peek_mut().unwrap() *= 10;
+        *q.back_mut().unwrap() += 100;
+        assert_eq!(q.iter().cloned().collect::<Vec<_>>(), [10, 2, 103]);
+        q.pop();
+        assert_eq!((q.peek(), q.back()), (Some(&2), Some(&103)));
+    }
+
+    #[test]
+    fn pop_and_push() {
+        let drops = Cell::new(0);
+        let mut q = UnboundQueue::new();
+        assert!(q.pop_and_push(Counted(&drops, 0)).is_none());
+        // a single node is relinked onto itself
+        let node = q.head;
+        let old = q.pop_and_push(Counted(&drops, 1)).unwrap();
+        assert_eq!((old.1, drops.get()), (0, 0));
+        drop(old);
+        assert_eq!(drops.get(), 1);
+        assert_eq!((q.head, q.tail), (node, node));
+
+        for i in 2..5 {
+            q.push(Counted(&drops, i));
+        }
+        for i in 5..20 {
+            let head = q.head;
+            let old = q.pop_and_push(Counted(&drops, i)).unwrap();
+            assert_eq!(old.1, i - 4);
+            // the old head node now holds the new element at the back
+            assert_eq!(q.tail, head);
+            assert_eq!(q.iter().last().map(|c| c.1), Some(i));
+        }
+        assert_eq!(drops.get(), 16);
+        assert_eq!(q.len, 4);
+        assert_eq!(q.iter().map(|c| c.1).collect::<Vec<_>>(), [16, 17, 18, 19]);
+        drop(q);
+        assert_eq!(drops.get(), 20);
+    }
+
+    #[test]
+    fn take() {
+        let mut q = UnboundQueue::new();
+        q.set_max_len(3);
+        for i in 0..5 {
+            q.push(i);
+        }
+        let head = q.head;
+        let taken = q.take();
+        assert_eq!(taken.head, head);
+        assert_eq!(taken.iter().cloned().collect::<Vec<_>>(), [2, 3, 4]);
+        assert!(q.is_empty());
+        assert_eq!(q.max_len(), 3);
+
+        let mut other = UnboundQueue::new();
+        other.push(9);
+        q.push(1);
+        q.swap_with(&mut other);
+        assert_eq!(q.pop(), Some(9));
+        assert_eq!(other.max_len(), 3);
+        assert_eq!(other.pop(), Some(1));
+    }
+
+    #[test]
+    fn split_off() {
+        for &(at, front_first) in &[(0, true), (2, true), (2, false), (5, false)] {
+            let drops = Cell::new(0);
+            let mut q = UnboundQueue::with_node_cache(2);
+            q.set_max_len(8);
+            for i in 0..5 {
+                q.push(Counted(&drops, i));
+            }
+            let nodes: Vec<_> = q.iter().map(|c| c as *const _).collect();
+            let mut back = q.split_off(at);
+            assert_eq!((q.len(), back.len()), (at, 5 - at));
+            assert_eq!(back.max_len(), 8);
+            // the nodes themselves changed hands
+            let after: Vec<_> = q.iter().chain(back.iter()).map(|c| c as *const _).collect();
+            assert_eq!(after, nodes);
+            assert_eq!(drops.get(), 0);
+
+            // both halves stay usable at either end
+            q.push(Counted(&drops, 10));
+            back.push(Counted(&drops, 11));
+            back.push_front(Counted(&drops, 12));
+            let ids = |q: &UnboundQueue<Counted>| q.iter().map(|c| c.1).collect::<Vec<_>>();
+            let mut want: Vec<_> = (0..at as i32).collect();
+            want.push(10);
+            assert_eq!(ids(&q), want);
+            let mut want = vec![12];
+            want.extend(at as i32..5);
+            want.push(11);
+            assert_eq!(ids(&back), want);
+
+            if front_first {
+                drop(q);
+                assert_eq!(drops.get(), at + 1);
+                drop(back);
+            } else {
+                drop(back);
+                assert_eq!(drops.get(), 5 - at + 2);
+                drop(q);
+            }
+            assert_eq!(drops.get(), 8);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "split index 4 is past the end of a queue of length 3")]
+    fn split_off_past_len() {
+        UnboundQueue::from_slice(&[1, 2, 3]).split_off(4);
+    }
+
+    #[test]
+    fn node_cache() {
+        let drops = Cell::new(0);
+        let mut q = UnboundQueue::with_node_cache(4);
+        for i in 0..10 {
+            q.push(Counted(&drops, i));
+        }
+        let front = q.head;
+        while q.pop().is_some() {}
+        assert_eq!((q.cached, drops.get()), (4, 10));
+        // the last node popped is the first reused
+        q.push(Counted(&drops, 10));
+        assert_eq!(q.cached, 3);
+        assert_ne!(q.head, front);
+        q.push_front(Counted(&drops, 11));
+        q.cursor_front_mut().insert_after(Counted(&drops, 12));
+        assert!(q.try_push_alloc(Counted(&drops, 13)).is_ok());
+        assert_eq!((q.cached, q.len), (0, 4));
+        q.push(Counted(&drops, 14));
+        assert_eq!(q.pop_back().map(|c| c.1), Some(14));
+        q.retain(|c| c.1 != 12);
+        assert_eq!(q.pop_with(|c| c.1), Some(11));
+        assert_eq!(q.iter().map(|c| c.1).collect::<Vec<_>>(), [10, 13]);
+        assert_eq!(q.cached, 3);
+
+        // the nodes stay behind with the queue, and go with it
+        let taken = q.take();
+        assert_eq!((q.cached, taken.len), (3, 2));
+        drop(taken);
+        q.push(Counted(&drops, 15));
+        let r = panic::catch_unwind(AssertUnwindSafe(|| q.pop_with(|_| panic!("boom"))));
+        assert!(r.is_err());
+        assert_eq!(q.cached, 3);
+        q.push(Counted(&drops, 16));
+        drop(q);
+        assert_eq!(drops.get(), 17);
+
+        let mut q = UnboundQueue::with_node_cache(2);
+        q.extend(0..5);
+        q.drain().for_each(drop);
+        let clone = q.clone();
+        assert_eq!((clone.cache_cap, clone.cached), (2, 0));
+        let (head, tail, len) = q.into_parts();
+        assert_eq!((head, tail, len), (None, None, 0));
+    }
+
+    #[test]
+    fn conversions() {
+        let want: Vec<_> = (0..5).map(|i| i.to_string()).collect();
+        let v = want.clone();
+        let front = v[0].as_ptr();
+
+        // moved, not cloned, all the way through
+        let q = UnboundQueue::from(v);
+        assert_eq!(q.peek().unwrap().as_ptr(), front);
+        let d = VecDeque::from(q);
+        assert!(d.iter().eq(&want));
+        let q = UnboundQueue::from(d);
+        let v = Vec::from(q);
+        assert_eq!(v, want);
+        assert_eq!(v[0].as_ptr(), front);
+
+        let b = UnboundQueue::from(v).into_bound(3);
+        assert_eq!((b.cap(), b.len()), (5, 5));
+        assert!(b.iter().eq(&want));
+        assert_eq!(b.peek().unwrap().as_ptr(), front);
+        let b = b.into_unbound().into_bound(8);
+        assert_eq!((b.cap(), b.len()), (8, 5));
+        assert!(b.iter().eq(&want));
+    }
 }
@@ -0,0 +1,219 @@
+use super::{Queue, TryQueue};
+use core::marker::PhantomData;
+use error::PushError;
+
+/// Which watermark a `WatermarkQueue` just crossed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crossing {
+    /// The length went up to the high watermark.
+    High,
+    /// The length came back down to the low watermark.
+    Low,
+}
+
+/// Wraps any queue to report backpressure: `on_cross` is called with
+/// `High` when the length reaches `high`, and with `Low` once it has
+/// come back down to `low`. The two alternate, so a length hovering around
+/// either mark reports nothing until it gets to the other one.
+pub struct WatermarkQueue<Q, T, F> {
+    inner: Q,
+    high: usize,
+    low: usize,
+    // whether `High` was the last crossing reported
+    above: bool,
+    on_cross: F,
+    marker: PhantomData<T>,
+}
+
+impl<Q: Queue<T>, T, F: FnMut(Crossing)> WatermarkQueue<Q, T, F> {
+    /// Panics unless `low < high`. An `inner` that already holds `high`
+    /// elements or more starts out above the high mark, without a call to
+    /// `on_cross`.
+    pub fn new(inner: Q, high: usize, low: usize, on_cross: F) -> Self {
+        assert!(
+            low < high,
+            "low watermark {} is not below the high watermark {}",
+            low,
+            high
+        );
+        WatermarkQueue {
+            above: inner.len() >= high,
+            inner,
+            high,
+            low,
+            on_cross,
+            marker: PhantomData,
+        }
+    }
+
+    /// Whether the queue has reached the high mark and not yet drained
+    /// back down to the low one.
+    pub fn above_high(&self) -> bool {
+        self.above
+    }
+
+    pub fn get_ref(&self) -> &Q {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> Q {
+        self.inner
+    }
+
+    /// Pops every element; reports `Low` once at the end if the queue was
+    /// above the high mark.
+    pub fn clear(&mut self) {
+        while self.inner.pop().is_some() {}
+        self.check();
+    }
+
+    /// Pops every element, front to back, reporting `Low` at the pop that
+    /// gets down to the low mark. Whatever is left when the iterator is
+    /// dropped is popped then.
+    pub fn drain(&mut self) -> Drain<'_, Q, T, F> {
+        Drain { q: self }
+    }
+
+    fn check(&mut self) {
+        let len = self.inner.len();
+        if !self.above && len >= self.high {
+            self.above = true;
+            (self.on_cross)(Crossing::High);
+        } else if self.above && len <= self.low {
+            self.above = false;
+            (self.on_cross)(Crossing::Low);
+        }
+    }
+}
+
+impl<Q: Queue<T>, T, F: FnMut(Crossing)> Queue<T> for WatermarkQueue<Q, T, F> {
+    fn push(&mut self, item: T) {
+        self.inner.push(item);
+        self.check();
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let item = self.inner.pop();
+        self.check();
+        item
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+/// Pushes every item in turn, so a crossing is reported at the push that
+/// makes it.
+impl<Q: Queue<T>, T, F: FnMut(Crossing)> Extend<T> for WatermarkQueue<Q, T, F> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+impl<Q: TryQueue<T>, T, F: FnMut(Crossing)> TryQueue<T> for WatermarkQueue<Q, T, F> {
+    fn try_push(&mut self, item: T) -> Result<(), PushError<T>> {
+        self.inner.try_push(item)?;
+        self.check();
+        Ok(())
+    }
+}
+
+pub struct Drain<'a, Q: 'a, T: 'a, F: 'a>
+where
+    Q: Queue<T>,
+    F: FnMut(Crossing),
+{
+    q: &'a mut WatermarkQueue<Q, T, F>,
+}
+
+impl<'a, Q: Queue<T>, T, F: FnMut(Crossing)> Iterator for Drain<'a, Q, T, F> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.q.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.q.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, Q: Queue<T>, T, F: FnMut(Crossing)> Drop for Drain<'a, Q, T, F> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bound::BoundQueue;
+    use std::cell::{Cell, RefCell};
+    use unbound::UnboundQueue;
+
+    #[test]
+    fn crossings() {
+        let events = RefCell::new(vec![]);
+        let step = Cell::new(0);
+        let mut q = WatermarkQueue::new(UnboundQueue::new(), 4, 1, |c| {
+            events.borrow_mut().push((step.get(), c))
+        });
+        // + push, - pop; the length after each step is in the comment
+        let script = "+++ + -+-+ -- - ++++ -+ ----";
+        //            123 4 3434 32 1 2345 45 4321
+        for op in script.chars().filter(|c| *c != ' ') {
+            step.set(step.get() + 1);
+            if op == '+' {
+                q.push(());
+            } else {
+                q.pop();
+            }
+        }
+        assert!(!q.above_high());
+        drop(q);
+        assert_eq!(
+            events.into_inner(),
+            [
+                (4, Crossing::High),
+                (11, Crossing::Low),
+                (14, Crossing::High),
+                (21, Crossing::Low),
+            ]
+        );
+    }
+
+    #[test]
+    fn clear_drain_try_push() {
+        let events = RefCell::new(vec![]);
+        let mut q = WatermarkQueue::new(BoundQueue::new(3), 3, 0, |c| events.borrow_mut().push(c));
+        q.extend(0..3);
+        assert_eq!(q.try_push(3), Err(PushError::Full(3)));
+        q.clear();
+        assert!(q.is_empty());
+        q.extend(0..3);
+        let mut d = q.drain();
+        assert_eq!(d.next(), Some(0));
+        drop(d);
+        assert!(q.is_empty());
+        q.push(0);
+        assert_eq!(
+            events.into_inner(),
+            [Crossing::High, Crossing::Low, Crossing::High, Crossing::Low]
+        );
+
+        // starting out full counts as above, without a call
+        let q = WatermarkQueue::new(BoundQueue::from_slice(&[1, 2]), 2, 1, |_| panic!());
+        assert!(q.above_high());
+        assert_eq!(q.into_inner().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "low watermark 2 is not below the high watermark 2")]
+    fn marks_in_order() {
+        WatermarkQueue::new(UnboundQueue::<u8>::new(), 2, 2, |_| ());
+    }
+}